@@ -20,6 +20,22 @@
 //! Utils for swapping little-endian bytes to the compiling
 //! processor's native endianness (byte order).
 
+/// Byte order that a [`Datagram`] writes its multi-byte integers in,
+/// and that a [`DatagramIterator`] reads them back in.
+///
+/// The Donet protocol is little-endian, but big-endian can be selected
+/// with [`Datagram::new_with_order`] to bridge to other protocols.
+///
+/// [`Datagram`]: super::datagram::Datagram
+/// [`Datagram::new_with_order`]: super::datagram::Datagram::new_with_order
+/// [`DatagramIterator`]: super::iterator::DatagramIterator
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub enum ByteOrder {
+    #[default]
+    LittleEndian,
+    BigEndian,
+}
+
 /// Swaps 2 bytes in little endian byte order to big endian.
 /// Returns the input if the processor is little endian.
 #[cfg(target_endian = "big")]
@@ -0,0 +1,228 @@
+/*
+    This file is part of Donet.
+
+    Copyright © 2024 Max Rodriguez <me@maxrdz.com>
+
+    Donet is free software; you can redistribute it and/or modify
+    it under the terms of the GNU Affero General Public License,
+    as published by the Free Software Foundation, either version 3
+    of the License, or (at your option) any later version.
+
+    Donet is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+    GNU Affero General Public License for more details.
+
+    You should have received a copy of the GNU Affero General Public
+    License along with Donet. If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! Provides an opt-in cache for reusing serialized datagrams.
+
+use super::datagram::{Datagram, DatagramError};
+use std::borrow::Cow;
+use std::collections::VecDeque;
+
+/// Default number of entries kept by a [`DatagramCache`].
+pub const DEFAULT_CACHE_CAPACITY: usize = 16;
+
+#[derive(Debug)]
+struct CacheEntry {
+    checksum: u32,
+    body: Datagram,
+    message: Datagram,
+}
+
+/// Small least-recently-used cache of serialized messages, keyed
+/// by the [`Datagram::checksum`] of the message body.
+///
+/// Useful for roles that send the same message body to many channels,
+/// as an identical body does not need to be serialized again. A cache
+/// hit is only returned if the bodies are also byte-for-byte equal,
+/// so checksum collisions never return the wrong message.
+#[derive(Debug)]
+pub struct DatagramCache {
+    capacity: usize,
+    entries: VecDeque<CacheEntry>,
+    hits: usize,
+    misses: usize,
+}
+
+impl Default for DatagramCache {
+    fn default() -> Self {
+        Self::with_capacity(DEFAULT_CACHE_CAPACITY)
+    }
+}
+
+impl DatagramCache {
+    /// Creates a cache that keeps up to `capacity` messages. A capacity of
+    /// zero disables the cache, so every message is serialized and none kept.
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            capacity,
+            entries: VecDeque::with_capacity(capacity),
+            hits: 0,
+            misses: 0,
+        }
+    }
+
+    /// Returns the serialized message for the given body. If it is not
+    /// cached, `serialize` is called to build it, and the result is stored,
+    /// evicting the least recently used entry if the cache is full.
+    ///
+    /// If the cache is disabled, the newly serialized message is returned.
+    pub fn get_or_insert_with<F>(
+        &mut self,
+        body: &Datagram,
+        serialize: F,
+    ) -> Result<Cow<'_, Datagram>, DatagramError>
+    where
+        F: FnOnce(&Datagram) -> Result<Datagram, DatagramError>,
+    {
+        if self.capacity == 0 {
+            self.misses += 1;
+            return Ok(Cow::Owned(serialize(body)?));
+        }
+        let checksum: u32 = body.checksum();
+
+        let found: Option<usize> = self
+            .entries
+            .iter()
+            .position(|e| e.checksum == checksum && e.body.get_buffer() == body.get_buffer());
+
+        let entry: CacheEntry = match found {
+            Some(index) => {
+                self.hits += 1;
+                // safe to unwrap, we just found the entry's index
+                self.entries.remove(index).unwrap()
+            }
+            None => {
+                self.misses += 1;
+                CacheEntry {
+                    checksum,
+                    body: body.clone(),
+                    message: serialize(body)?,
+                }
+            }
+        };
+
+        if self.entries.len() >= self.capacity {
+            self.entries.pop_back();
+        }
+        // most recently used entries are kept in the front
+        self.entries.push_front(entry);
+
+        Ok(Cow::Borrowed(
+            &self.entries.front().expect("Cache entry was not stored.").message,
+        ))
+    }
+
+    /// Returns the number of cached messages.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Returns the number of lookups that reused a cached message.
+    pub fn get_hits(&self) -> usize {
+        self.hits
+    }
+
+    /// Returns the number of lookups that serialized a new message.
+    pub fn get_misses(&self) -> usize {
+        self.misses
+    }
+
+    /// Removes all cached messages.
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::globals::Channel;
+    use crate::Protocol;
+
+    fn serialize_to(recipient: Channel) -> impl Fn(&Datagram) -> Result<Datagram, DatagramError> {
+        move |body: &Datagram| {
+            let mut dg: Datagram = Datagram::default();
+
+            dg.add_internal_header(vec![recipient], 0, Protocol::MDAddChannel.into())?;
+            dg.add_data(body.get_data())?;
+            Ok(dg)
+        }
+    }
+
+    #[test]
+    fn repeated_message_hits_cache() -> Result<(), DatagramError> {
+        let mut cache: DatagramCache = DatagramCache::default();
+        let mut body: Datagram = Datagram::default();
+
+        body.add_string("Hello!")?;
+
+        let first: Datagram = cache.get_or_insert_with(&body, serialize_to(1000))?.into_owned();
+
+        assert_eq!(cache.get_misses(), 1);
+        assert_eq!(cache.get_hits(), 0);
+
+        let second: Datagram = cache
+            .get_or_insert_with(&body, |_| panic!("Identical body was serialized again!"))?
+            .into_owned();
+
+        assert_eq!(cache.get_hits(), 1);
+        assert_eq!(cache.len(), 1);
+        assert_eq!(first.get_buffer(), second.get_buffer());
+        Ok(())
+    }
+
+    #[test]
+    fn least_recently_used_is_evicted() -> Result<(), DatagramError> {
+        let mut cache: DatagramCache = DatagramCache::with_capacity(2);
+        let mut bodies: Vec<Datagram> = vec![];
+
+        for i in 0..3 {
+            let mut body: Datagram = Datagram::default();
+            body.add_u8(i)?;
+            bodies.push(body);
+        }
+
+        cache.get_or_insert_with(&bodies[0], serialize_to(1))?;
+        cache.get_or_insert_with(&bodies[1], serialize_to(1))?;
+        cache.get_or_insert_with(&bodies[0], serialize_to(1))?; // hit
+        cache.get_or_insert_with(&bodies[2], serialize_to(1))?; // evicts body 1
+
+        assert_eq!(cache.len(), 2);
+        assert_eq!(cache.get_hits(), 1);
+
+        cache.get_or_insert_with(&bodies[0], serialize_to(1))?; // hit
+        cache.get_or_insert_with(&bodies[1], serialize_to(1))?; // miss
+
+        assert_eq!(cache.get_hits(), 2);
+        assert_eq!(cache.get_misses(), 4);
+        Ok(())
+    }
+
+    #[test]
+    fn zero_capacity_disables_cache() -> Result<(), DatagramError> {
+        let mut cache: DatagramCache = DatagramCache::with_capacity(0);
+        let mut body: Datagram = Datagram::default();
+
+        body.add_string("Hello!")?;
+
+        let expected: Datagram = serialize_to(1000)(&body)?;
+
+        for _ in 0..2 {
+            let message: Datagram = cache.get_or_insert_with(&body, serialize_to(1000))?.into_owned();
+            assert_eq!(message.get_buffer(), expected.get_buffer());
+        }
+        assert!(cache.is_empty());
+        assert_eq!(cache.get_hits(), 0);
+        assert_eq!(cache.get_misses(), 2);
+        Ok(())
+    }
+}
@@ -19,9 +19,16 @@
 
 //! Provides structure to write network packets (datagrams).
 
-use crate::datagram::byte_order as endianness;
+use super::byte_order::ByteOrder;
+#[cfg(feature = "dcfile")]
+use crate::dcfield::{DCField, FieldValue, StructField};
+#[cfg(feature = "dcfile")]
+use crate::dcstruct::DCStruct;
 use crate::globals::*;
+use crate::protocol::Protocol;
 use anyhow::Result;
+use std::mem;
+use strum::IntoEnumIterator;
 use thiserror::Error;
 
 /// Custom error type for [`Datagram`].
@@ -31,6 +38,16 @@ pub enum DatagramError {
     DatagramOverflow(&'static str),
     #[error("impossible cast; {0}")]
     ImpossibleCast(&'static str),
+    /// Returned when a value does not fit the
+    /// data type of the field it is written for.
+    #[error("field constraint violation")]
+    FieldConstraintViolation,
+    #[error("invalid write; {0}")]
+    InvalidWrite(&'static str),
+    /// Returned when a message header is given more recipient
+    /// channels than its 8-bit recipient count can hold.
+    #[error("too many recipients; {0} exceeds the maximum of 255")]
+    TooManyRecipients(usize),
 }
 
 impl From<DatagramError> for std::io::Error {
@@ -40,12 +57,14 @@ impl From<DatagramError> for std::io::Error {
 }
 
 /// Representation of a new network message (datagram) to be sent.
-#[derive(Debug, Clone)]
+#[derive(Clone)]
 pub struct Datagram {
     buffer: Vec<u8>,
     index: usize,
     /// See [`Datagram::override_cap`].
     cap: usize,
+    /// See [`Datagram::new_with_order`].
+    order: ByteOrder,
 }
 
 impl Default for Datagram {
@@ -54,10 +73,58 @@ impl Default for Datagram {
             buffer: vec![],
             index: 0,
             cap: usize::from(DgSizeTag::MAX),
+            order: ByteOrder::default(),
         }
     }
 }
 
+/// Creates a [`Datagram`] that takes ownership of the given bytes,
+/// such as a buffer received from a socket.
+///
+/// Unlike [`Datagram::from_vec`], this cannot fail; if the buffer is
+/// larger than the default size cap, the cap is raised to its length.
+/// The size cap limits how much data is written to a datagram, and
+/// these bytes already exist, such as a single read from a TCP stream
+/// holding several frames. Use [`Datagram::from_vec`] to enforce the
+/// default cap on a buffer that must fit in a single frame.
+impl From<Vec<u8>> for Datagram {
+    fn from(value: Vec<u8>) -> Self {
+        Self {
+            index: value.len(),
+            cap: value.len().max(usize::from(DgSizeTag::MAX)),
+            buffer: value,
+            order: ByteOrder::default(),
+        }
+    }
+}
+
+/// Two datagrams are equal if their buffers hold the same bytes,
+/// regardless of their size caps.
+impl PartialEq for Datagram {
+    fn eq(&self, other: &Self) -> bool {
+        self.buffer == other.buffer
+    }
+}
+
+impl Eq for Datagram {}
+
+/// Formats the datagram as a hex dump of its bytes, with a `|`
+/// marking the position of the current index.
+impl std::fmt::Debug for Datagram {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut dump: Vec<String> = self.buffer.iter().map(|byte| format!("{:02x}", byte)).collect();
+        dump.insert(self.index.min(dump.len()), "|".to_owned());
+
+        write!(f, "Datagram [{}]", dump.join(" "))
+    }
+}
+
+impl AsRef<[u8]> for Datagram {
+    fn as_ref(&self) -> &[u8] {
+        &self.buffer
+    }
+}
+
 /// Appends another datagram's raw bytes to this datagram.
 ///
 /// Consumes the right-hand-side [`Datagram`].
@@ -73,6 +140,67 @@ impl std::ops::Add for Datagram {
 }
 
 impl Datagram {
+    /// Creates a new, empty [`Datagram`] with its buffer pre-allocated
+    /// to hold at least `cap` bytes. This does not change the
+    /// datagram's size cap; see [`Datagram::override_cap`].
+    pub fn with_capacity(cap: usize) -> Datagram {
+        Datagram {
+            buffer: Vec::with_capacity(cap),
+            ..Default::default()
+        }
+    }
+
+    /// Creates a new, empty [`Datagram`] that writes its multi-byte
+    /// integers in the given byte order, instead of little-endian.
+    ///
+    /// A [`DatagramIterator`] created from this datagram reads them
+    /// back in the same byte order.
+    ///
+    /// [`DatagramIterator`]: super::iterator::DatagramIterator
+    pub fn new_with_order(order: ByteOrder) -> Datagram {
+        Datagram {
+            order,
+            ..Default::default()
+        }
+    }
+
+    /// Returns the byte order of this datagram's multi-byte integers.
+    pub fn get_byte_order(&self) -> ByteOrder {
+        self.order
+    }
+
+    /// Empties this [`Datagram`] so it can be built again, keeping
+    /// its allocated buffer to avoid reallocating on reuse.
+    pub fn clear(&mut self) {
+        self.buffer.clear();
+        self.index = 0;
+    }
+
+    /// Returns the number of bytes this datagram can hold
+    /// without reallocating its buffer.
+    pub fn capacity(&self) -> usize {
+        self.buffer.capacity()
+    }
+
+    /// Creates a new [`Datagram`] that takes ownership of the given
+    /// byte buffer, without copying it.
+    ///
+    /// The buffer must fit within the default datagram size cap,
+    /// else [`DatagramError::DatagramOverflow`] is returned. Converting
+    /// the buffer with [`From`] instead raises the cap to fit it.
+    pub fn from_vec(buffer: Vec<u8>) -> Result<Datagram, DatagramError> {
+        let mut dg: Datagram = Datagram::default();
+
+        if buffer.len() > dg.cap {
+            return Err(DatagramError::DatagramOverflow(
+                "Given buffer is larger than the datagram's maximum size!",
+            ));
+        }
+        dg.index = buffer.len();
+        dg.buffer = buffer;
+        Ok(dg)
+    }
+
     /// Checks if we can add `length` number of bytes to the datagram.
     fn check_add_length(&mut self, length: usize) -> Result<(), DatagramError> {
         let new_index: usize = self.index + length;
@@ -107,6 +235,16 @@ impl Datagram {
         Ok(())
     }
 
+    /// Swaps the bytes of a little-endian value if this
+    /// datagram is written in big-endian byte order.
+    #[inline(always)]
+    fn ordered<T>(&self, v: T, swap_bytes: fn(T) -> T) -> T {
+        match self.order {
+            ByteOrder::LittleEndian => v,
+            ByteOrder::BigEndian => swap_bytes(v),
+        }
+    }
+
     /// Adds an unsigned 8-bit integer value to the datagram.
     pub fn add_u8(&mut self, v: u8) -> Result<(), DatagramError> {
         self.check_add_length(1)?;
@@ -116,47 +254,43 @@ impl Datagram {
     }
 
     /// Adds an unsigned 16-bit integer value to the datagram.
-    pub fn add_u16(&mut self, mut v: u16) -> Result<(), DatagramError> {
+    ///
+    /// Multi-byte integers are written in little-endian byte order, unless
+    /// another order was chosen with [`Datagram::new_with_order`].
+    /// Shifting operates on the value, not its memory representation, so
+    /// no byte swapping is needed on big-endian hosts.
+    pub fn add_u16(&mut self, v: u16) -> Result<(), DatagramError> {
         self.check_add_length(2)?;
+        let v: u16 = self.ordered(v, u16::swap_bytes);
 
-        v = endianness::swap_le_16(v);
-
-        self.buffer.push((v & 0x00ff) as u8);
-        self.buffer.push(((v & 0xff00) >> 8) as u8);
+        self.buffer.push(v as u8);
+        self.buffer.push((v >> 8) as u8);
 
         self.index += 2;
         Ok(())
     }
 
     /// Adds an unsigned 32-bit integer value to the datagram.
-    pub fn add_u32(&mut self, mut v: u32) -> Result<(), DatagramError> {
+    pub fn add_u32(&mut self, v: u32) -> Result<(), DatagramError> {
         self.check_add_length(4)?;
+        let v: u32 = self.ordered(v, u32::swap_bytes);
 
-        v = endianness::swap_le_32(v);
-
-        self.buffer.push((v & 0x000000ff) as u8);
-        self.buffer.push(((v & 0x0000ff00) >> 8) as u8);
-        self.buffer.push(((v & 0x00ff0000) >> 16) as u8);
-        self.buffer.push(((v & 0xff000000) >> 24) as u8);
+        for shift in (0..32).step_by(8) {
+            self.buffer.push((v >> shift) as u8);
+        }
 
         self.index += 4;
         Ok(())
     }
 
     /// Adds an unsigned 64-bit integer value to the datagram.
-    pub fn add_u64(&mut self, mut v: u64) -> Result<(), DatagramError> {
+    pub fn add_u64(&mut self, v: u64) -> Result<(), DatagramError> {
         self.check_add_length(8)?;
+        let v: u64 = self.ordered(v, u64::swap_bytes);
 
-        v = endianness::swap_le_64(v);
-
-        self.buffer.push((v & 0x00000000000000ff) as u8);
-        self.buffer.push(((v & 0x000000000000ff00) >> 8) as u8);
-        self.buffer.push(((v & 0x0000000000ff0000) >> 16) as u8);
-        self.buffer.push(((v & 0x00000000ff000000) >> 24) as u8);
-        self.buffer.push(((v & 0x000000ff00000000) >> 32) as u8);
-        self.buffer.push(((v & 0x0000ff0000000000) >> 40) as u8);
-        self.buffer.push(((v & 0x00ff000000000000) >> 48) as u8);
-        self.buffer.push(((v & 0xff00000000000000) >> 56) as u8);
+        for shift in (0..64).step_by(8) {
+            self.buffer.push((v >> shift) as u8);
+        }
 
         self.index += 8;
         Ok(())
@@ -183,16 +317,18 @@ impl Datagram {
         self.add_u64(v as u64)
     }
 
-    /// 32-bit IEEE 754 floating point. same bitwise operations.
+    /// 32-bit IEEE 754 floating point. The float's bits are
+    /// reinterpreted as a u32, not numerically converted.
     #[inline(always)]
     pub fn add_f32(&mut self, v: f32) -> Result<(), DatagramError> {
-        self.add_u32(v as u32)
+        self.add_u32(v.to_bits())
     }
 
-    /// 64-bit IEEE 754 floating point. same bitwise operations.
+    /// 64-bit IEEE 754 floating point. The float's bits are
+    /// reinterpreted as a u64, not numerically converted.
     #[inline(always)]
     pub fn add_f64(&mut self, v: f64) -> Result<(), DatagramError> {
-        self.add_u64(v as u64)
+        self.add_u64(v.to_bits())
     }
 
     /// Adds a Datagram / Field length tag to the end of the datagram.
@@ -207,12 +343,37 @@ impl Datagram {
         self.add_u64(v)
     }
 
+    /// Same as [`Datagram::add_channel`], but returns
+    /// [`DatagramError::InvalidWrite`] if given [`INVALID_CHANNEL`].
+    ///
+    /// Use this where an unset channel would be a programming error,
+    /// such as the sender of a message.
+    pub fn add_channel_checked(&mut self, v: Channel) -> Result<(), DatagramError> {
+        if v == INVALID_CHANNEL {
+            return Err(DatagramError::InvalidWrite("Tried to add the invalid channel."));
+        }
+        self.add_channel(v)
+    }
+
     /// Adds a 32-bit Distributed Object ID to the end of the datagram.
     #[inline(always)]
     pub fn add_doid(&mut self, v: DoId) -> Result<(), DatagramError> {
         self.add_u32(v)
     }
 
+    /// Same as [`Datagram::add_doid`], but returns
+    /// [`DatagramError::InvalidWrite`] if given [`INVALID_DOID`].
+    ///
+    /// Use this where the object must exist, such as the target of
+    /// a field update. Some messages use a zero DoId on purpose,
+    /// e.g. for an object without a parent, so this is not the default.
+    pub fn add_doid_checked(&mut self, v: DoId) -> Result<(), DatagramError> {
+        if v == INVALID_DOID {
+            return Err(DatagramError::InvalidWrite("Tried to add the invalid DoId."));
+        }
+        self.add_doid(v)
+    }
+
     /// Adds a 32-bit zone ID to the end of the datagram.
     #[inline(always)]
     pub fn add_zone(&mut self, v: Zone) -> Result<(), DatagramError> {
@@ -226,6 +387,24 @@ impl Datagram {
         self.add_u32(zone)
     }
 
+    /// Adds a 64-bit timestamp to the end of the datagram.
+    ///
+    /// The epoch and resolution of the timestamp are left up to the
+    /// message being written; this only fixes its width on the wire.
+    #[inline(always)]
+    pub fn add_timestamp(&mut self, ts: u64) -> Result<(), DatagramError> {
+        self.add_u64(ts)
+    }
+
+    /// Adds a 32-bit DC field keyword bitmask to the end of the datagram.
+    ///
+    /// Each bit is set by its keyword's index in the historical keyword
+    /// list, i.e. `ram` is bit 0, `required` is bit 1, and so on.
+    #[inline(always)]
+    pub fn add_keyword_flags(&mut self, flags: u32) -> Result<(), DatagramError> {
+        self.add_u32(flags)
+    }
+
     /// Adds raw bytes to the datagram via an unsigned 8-bit integer vector.
     ///
     /// **NOTE**: not to be confused with [`Datagram::add_blob`], which
@@ -289,6 +468,96 @@ impl Datagram {
         Ok(())
     }
 
+    /// Adds a value of the given DC struct to the end of the datagram,
+    /// encoding each of its fields in declaration order.
+    ///
+    /// Molecular fields are skipped, as they only alias other fields,
+    /// so `values` holds one value per non-molecular field.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`DatagramError::FieldConstraintViolation`] if the
+    /// number of values does not match the struct's fields, or if a
+    /// value does not fit the data type of its field.
+    ///
+    #[cfg(feature = "dcfile")]
+    pub fn add_struct_value(
+        &mut self,
+        strukt: &DCStruct,
+        values: &[FieldValue],
+    ) -> Result<(), DatagramError> {
+        let mut fields: Vec<&DCField> = vec![];
+
+        for index in 0..strukt.get_num_fields() {
+            match strukt.get_field(index) {
+                Some(StructField::Field(field)) => fields.push(field),
+                Some(StructField::Switch(_)) => {
+                    return Err(DatagramError::InvalidWrite(
+                        "Packing DC switches is not supported.",
+                    ));
+                }
+                _ => {}
+            }
+        }
+
+        if fields.len() != values.len() {
+            return Err(DatagramError::FieldConstraintViolation);
+        }
+        for (field, value) in fields.iter().zip(values) {
+            field.pack_value(self, value)?;
+        }
+        Ok(())
+    }
+
+    /// Adds a fixed-size char array value to the end of the datagram,
+    /// which has no length tag prefix, as its size is implied by its type.
+    ///
+    /// Exactly `len` bytes are written, padding the string with zeroes.
+    /// Returns [`DatagramError::FieldConstraintViolation`] if the
+    /// string is longer than `len` bytes.
+    pub fn add_fixed_string(&mut self, str: &str, len: usize) -> Result<(), DatagramError> {
+        if str.len() > len {
+            return Err(DatagramError::FieldConstraintViolation);
+        }
+        let mut bytes: Vec<u8> = str.as_bytes().to_vec();
+        bytes.resize(len, 0);
+
+        self.add_data(bytes)
+    }
+
+    /// Adds a dclass string value to the end of the datagram.
+    /// A 32-bit length tag prefix with the string's size in bytes is added.
+    ///
+    /// Strings over 65535 bytes only fit if the datagram's cap was
+    /// raised via [`Datagram::override_cap`].
+    pub fn add_string32(&mut self, str: &str) -> Result<(), DatagramError> {
+        self.add_blob32(str.as_bytes().to_vec())
+    }
+
+    /// Adds a dclass blob value (binary data) to the end of the datagram.
+    /// A 32-bit length tag prefix with the blob's size in bytes is added.
+    ///
+    /// Blobs over 65535 bytes only fit if the datagram's cap was
+    /// raised via [`Datagram::override_cap`].
+    pub fn add_blob32(&mut self, mut bytes: Vec<u8>) -> Result<(), DatagramError> {
+        let size: u32 = match bytes.len().try_into() {
+            Ok(n) => n,
+            Err(_) => {
+                return Err(DatagramError::ImpossibleCast(
+                    "Given blob has a size that does not fit in a 32-bit length tag.",
+                ))
+            }
+        };
+        // check the length tag and payload together, so that
+        // a blob that does not fit leaves no partial write behind
+        self.check_add_length(mem::size_of::<u32>() + bytes.len())?;
+        self.add_u32(size)?;
+
+        self.index += bytes.len();
+        self.buffer.append(&mut bytes);
+        Ok(())
+    }
+
     /// Reserves an amount of bytes in the datagram buffer.
     pub fn add_buffer(&mut self, size: usize) -> Result<usize, DatagramError> {
         self.check_add_length(size)?;
@@ -296,13 +565,37 @@ impl Datagram {
         // get start length (before push)
         let start: usize = self.index;
 
-        for _ in 1..size {
-            self.buffer.push(0);
-        }
+        self.buffer.resize(start + size, 0);
         self.index += size;
         Ok(start)
     }
 
+    /// Overwrites the two bytes at `offset` with an unsigned 16-bit
+    /// integer, such as a slot reserved with [`Datagram::add_buffer`].
+    ///
+    /// Returns [`DatagramError::InvalidWrite`] if the bytes at `offset`
+    /// have not been written yet; this never grows the datagram.
+    pub fn set_u16_at(&mut self, offset: usize, v: u16) -> Result<(), DatagramError> {
+        let v: u16 = self.ordered(v, u16::swap_bytes);
+
+        match self.buffer.get_mut(offset..offset.saturating_add(2)) {
+            Some(slot) => {
+                slot.copy_from_slice(&v.to_le_bytes());
+                Ok(())
+            }
+            None => Err(DatagramError::InvalidWrite(
+                "Tried to overwrite bytes past the end of the datagram.",
+            )),
+        }
+    }
+
+    /// Overwrites a Datagram / Field length tag at `offset`,
+    /// such as one reserved before its data was written.
+    #[inline(always)]
+    pub fn set_size_at(&mut self, offset: usize, v: DgSizeTag) -> Result<(), DatagramError> {
+        self.set_u16_at(offset, v)
+    }
+
     /// Appends a generic header for messages that are to be routed to
     /// one or more role instances within the server cluster.
     ///
@@ -316,8 +609,8 @@ impl Datagram {
     /// # Errors
     ///
     /// It is an error for the given `recipients` vector to have a size
-    /// larger than [`std::u8::MAX`]. Else, [`DatagramError::ImpossibleCast`]
-    /// will be returned.
+    /// larger than [`std::u8::MAX`]. Else, [`DatagramError::TooManyRecipients`]
+    /// will be returned, and nothing is written to the datagram.
     ///
     pub fn add_internal_header(
         &mut self,
@@ -327,13 +620,11 @@ impl Datagram {
     ) -> Result<(), DatagramError> {
         let n_recipients: usize = recipients.len();
 
-        if n_recipients > usize::from(u8::MAX) {
-            return Err(DatagramError::ImpossibleCast(
-                "Cannot convert recipient vec size to u8.",
-            ));
-        }
         // Add recipient(s) count
-        self.add_u8(n_recipients.try_into().expect("Recipients exceeds u8 limit."))?;
+        let Ok(count) = u8::try_from(n_recipients) else {
+            return Err(DatagramError::TooManyRecipients(n_recipients));
+        };
+        self.add_u8(count)?;
 
         for recipient in recipients {
             // append each recipient in vector given
@@ -352,16 +643,123 @@ impl Datagram {
     /// message DIRECTLY from a cluster subscriber, so it can be speculated
     /// that the sender is the participant on the other end of the connection.
     pub fn add_control_header(&mut self, msg_type: MsgType) -> Result<(), DatagramError> {
+        debug_assert!(
+            Protocol::iter().any(|message| message.is_control() && MsgType::from(message) == msg_type),
+            "Message type {} is not a control message.",
+            msg_type
+        );
         self.add_u8(1)?;
         self.add_channel(CONTROL_CHANNEL)?;
         self.add_u16(msg_type)
     }
 
+    /// Writes this [`Datagram`] to the given writer as a frame, which
+    /// is a size tag with the datagram's length followed by its bytes.
+    ///
+    /// Partial writes are retried until the whole frame is written,
+    /// so a slow socket may block for as long as it needs. This method
+    /// does not impose a timeout; set one on the writer itself (e.g.
+    /// [`std::net::TcpStream::set_write_timeout`]), and its timeout
+    /// error is returned as is, with the frame partially written.
+    pub fn write_framed_to(&self, w: &mut impl std::io::Write) -> std::io::Result<()> {
+        let size: DgSizeTag = match self.size().try_into() {
+            Ok(size) => size,
+            Err(_) => {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidInput,
+                    "Datagram is too large to fit in a size tag.",
+                ))
+            }
+        };
+        // `write_all` loops over partial writes and retries interrupted ones
+        w.write_all(&size.to_le_bytes())?;
+        w.write_all(&self.buffer)?;
+        w.flush()
+    }
+
     /// Returns the size of this [`Datagram`].
     pub fn size(&self) -> usize {
         self.buffer.len()
     }
 
+    /// Returns an Adler-32 checksum of this [`Datagram`]'s byte buffer.
+    ///
+    /// This is not a cryptographic hash; identical buffers are guaranteed
+    /// to have the same checksum, but different buffers may collide.
+    pub fn checksum(&self) -> u32 {
+        const MOD_ADLER: u32 = 65521;
+
+        let mut a: u32 = 1;
+        let mut b: u32 = 0;
+
+        for byte in &self.buffer {
+            a = (a + u32::from(*byte)) % MOD_ADLER;
+            b = (b + a) % MOD_ADLER;
+        }
+        (b << 16) | a
+    }
+
+    /// Returns `true` if both datagrams begin with the same internal
+    /// header routing, meaning the same recipient channels and sender
+    /// channel, without decoding the rest of either datagram.
+    ///
+    /// Returns `false` if either datagram is too short to hold the
+    /// routing header that its recipient count declares.
+    pub fn same_routing(&self, other: &Datagram) -> bool {
+        match (self.get_routing_bytes(), other.get_routing_bytes()) {
+            (Some(a), Some(b)) => a == b,
+            _ => false,
+        }
+    }
+
+    /// Returns the bytes of the recipient count, recipients, and sender
+    /// fields of the internal header, or `None` if they are out of bounds.
+    fn get_routing_bytes(&self) -> Option<&[u8]> {
+        let channel_size: usize = std::mem::size_of::<Channel>();
+        let n_recipients: usize = usize::from(*self.buffer.first()?);
+
+        let mut length: usize = 1 + n_recipients * channel_size;
+        let recipients: &[u8] = self.buffer.get(..length)?;
+
+        // control messages do not carry a sender channel
+        let control_bytes: [u8; 8] = match self.order {
+            ByteOrder::LittleEndian => CONTROL_CHANNEL.to_le_bytes(),
+            ByteOrder::BigEndian => CONTROL_CHANNEL.to_be_bytes(),
+        };
+        let is_control: bool = n_recipients == 1 && recipients[1..] == control_bytes;
+
+        if !is_control {
+            length += channel_size;
+        }
+        self.buffer.get(..length)
+    }
+
+    /// Returns a hexadecimal dump of this [`Datagram`]'s bytes, one space
+    /// between each byte, for use in log messages.
+    ///
+    /// At most `max_bytes` are written; if the datagram is longer, the
+    /// dump ends with a `... (N more bytes)` marker so that oversized
+    /// datagrams do not flood the log.
+    pub fn hex_dump_capped(&self, max_bytes: usize) -> String {
+        let shown: &[u8] = &self.buffer[..self.buffer.len().min(max_bytes)];
+
+        let mut dump: String = shown
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect::<Vec<String>>()
+            .join(" ");
+
+        let hidden: usize = self.buffer.len() - shown.len();
+
+        if hidden > 0 {
+            if !dump.is_empty() {
+                dump.push(' ');
+            }
+            dump.push_str(&format!("... ({} more bytes)", hidden));
+        }
+        dump
+    }
+
     /// Returns a reference to this [`Datagram`]'s byte buffer.
     pub fn get_buffer(&self) -> &[u8] {
         &self.buffer
@@ -371,12 +769,7 @@ impl Datagram {
     pub fn get_data(&self) -> Vec<u8> {
         // we can't give out ownership of our vector,
         // so a copy of the vector is made instead
-        let mut vec_copy: Vec<u8> = vec![];
-        for byte in &self.buffer {
-            // dereference the borrowed 'byte'
-            vec_copy.push(*byte);
-        }
-        vec_copy
+        self.buffer.clone()
     }
 }
 
@@ -403,6 +796,25 @@ mod tests {
         }
     }
 
+    #[test]
+    fn little_endian_byte_layout() {
+        // The wire format is always little-endian, regardless of the host.
+        let mut dg: Datagram = Datagram::default();
+
+        dg.add_u16(0x0102).unwrap();
+        assert_eq!(dg.get_buffer(), &[0x02, 0x01]);
+
+        let mut dg: Datagram = Datagram::default();
+
+        dg.add_u32(0x01020304).unwrap();
+        assert_eq!(dg.get_buffer(), &[0x04, 0x03, 0x02, 0x01]);
+
+        let mut dg: Datagram = Datagram::default();
+
+        dg.add_u64(0x0102030405060708).unwrap();
+        assert_eq!(dg.get_buffer(), &[0x08, 0x07, 0x06, 0x05, 0x04, 0x03, 0x02, 0x01]);
+    }
+
     #[test]
     fn add_integers_and_types() {
         // A bit repetitive, but we need coverage on all of these methods.
@@ -502,6 +914,24 @@ mod tests {
         ]);
     }
 
+    #[test]
+    fn too_many_recipients() {
+        let mut dg: Datagram = Datagram::default();
+
+        let recipients: Vec<Channel> = (0..256).collect();
+        let res = dg.add_internal_header(recipients, 0, Protocol::MDAddChannel.into());
+
+        assert!(matches!(res, Err(DatagramError::TooManyRecipients(256))));
+        assert_eq!(dg.size(), 0);
+
+        // the largest recipient count still fits
+        let recipients: Vec<Channel> = (0..255).collect();
+        assert!(dg
+            .add_internal_header(recipients, 0, Protocol::MDAddChannel.into())
+            .is_ok());
+        assert_eq!(dg.get_data()[0], u8::MAX);
+    }
+
     #[test]
     fn overflow_test() {
         let mut dg: Datagram = Datagram::default();
@@ -510,7 +940,7 @@ mod tests {
         assert!(!res_1.is_err(), "Could not append 2^16 bytes to datagram buffer.");
         assert_eq!(res_1.unwrap(), 0, "add_buffer() didn't return start of reserve.");
         assert_eq!(
-            dg.size() + 1,
+            dg.size(),
             usize::from(DG_SIZE_MAX),
             "Datagram didn't add 2^16 bytes to the buffer."
         );
@@ -527,4 +957,463 @@ mod tests {
             "Datagram overflow occurred, but failed to respond with DatagramOverflow err."
         );
     }
+
+    #[test]
+    fn datagram_from_vec() {
+        use crate::datagram::iterator::DatagramIterator;
+
+        let buffer: Vec<u8> = vec![0x39, 0x05, 0x02, 0, b'o', b'k'];
+        let buffer_ptr: *const u8 = buffer.as_ptr();
+
+        let mut dg: Datagram = Datagram::from_vec(buffer).unwrap();
+
+        // the datagram owns the very same allocation
+        assert_eq!(dg.get_buffer().as_ptr(), buffer_ptr);
+        assert_eq!(dg.size(), 6);
+
+        // appends go after the wrapped bytes
+        dg.add_u8(1).unwrap();
+
+        let mut dgi: DatagramIterator = dg.into();
+
+        assert_eq!(dgi.read_u16().unwrap(), 1337);
+        assert_eq!(dgi.read_string().unwrap(), "ok");
+        assert!(dgi.read_bool().unwrap());
+
+        assert!(Datagram::from_vec(vec![0; usize::from(DG_SIZE_MAX) + 1]).is_err());
+    }
+
+    #[test]
+    fn add_32_bit_length_tagged_values() {
+        use crate::datagram::iterator::{DatagramIterator, IteratorError};
+
+        let blob: Vec<u8> = (0..100_000_u32).map(|n| (n % 251) as u8).collect();
+        let string: String = "donet".repeat(20_000);
+
+        // too large for the default cap, and nothing is written
+        let mut dg: Datagram = Datagram::default();
+
+        assert!(dg.add_blob32(blob.clone()).is_err());
+        assert_eq!(dg.size(), 0);
+
+        dg.override_cap(1024 * 1024);
+        dg.add_blob32(blob.clone()).unwrap();
+        dg.add_string32(&string).unwrap();
+        dg.add_string32("").unwrap();
+
+        assert_eq!(dg.size(), 4 + blob.len() + 4 + string.len() + 4);
+        assert_eq!(dg.get_buffer()[..4], 100_000_u32.to_le_bytes());
+
+        let mut dgi: DatagramIterator = dg.into();
+
+        assert_eq!(dgi.read_blob32().unwrap(), blob);
+        assert_eq!(dgi.read_string32().unwrap(), string);
+        assert_eq!(dgi.read_string32().unwrap(), "");
+        assert_eq!(dgi.read_blob32(), Err(IteratorError::EndOfFile));
+    }
+
+    #[test]
+    fn clear_and_reuse_datagram() {
+        let mut dg: Datagram = Datagram::with_capacity(256);
+
+        assert_eq!(dg.size(), 0);
+        assert!(dg.capacity() >= 256);
+
+        let capacity: usize = dg.capacity();
+
+        for cycle in 0..5_u32 {
+            dg.add_u32(cycle).unwrap();
+            dg.add_string("reused").unwrap();
+
+            assert_eq!(dg.size(), 12);
+            assert_eq!(dg.get_buffer()[..4], cycle.to_le_bytes());
+
+            dg.clear();
+
+            assert_eq!(dg.size(), 0);
+            assert_eq!(dg.capacity(), capacity);
+        }
+        // the size cap is unaffected by reuse
+        assert!(dg.add_buffer(DG_SIZE_MAX.into()).is_ok());
+    }
+
+    #[test]
+    fn datagram_from_raw_bytes() {
+        use crate::datagram::iterator::DatagramIterator;
+
+        let mut built: Datagram = Datagram::default();
+
+        built.add_channel(4000).unwrap();
+        built.add_string("raw").unwrap();
+
+        let mut dg: Datagram = Datagram::from(built.get_data());
+
+        assert_eq!(dg.as_ref(), built.get_buffer());
+        assert_eq!(dg.as_ref().len(), dg.size());
+
+        let mut dgi: DatagramIterator = dg.clone().into();
+
+        assert_eq!(dgi.read_channel().unwrap(), 4000);
+        assert_eq!(dgi.read_string().unwrap(), "raw");
+
+        // appends go after the given bytes
+        dg.add_u8(1).unwrap();
+        assert_eq!(dg.size(), built.size() + 1);
+
+        // oversized buffers are accepted as they are
+        let dg: Datagram = vec![0_u8; usize::from(DG_SIZE_MAX) + 10].into();
+        assert_eq!(dg.as_ref().len(), dg.size());
+        assert_eq!(dg.size(), usize::from(DG_SIZE_MAX) + 10);
+    }
+
+    #[test]
+    #[should_panic(expected = "is not a control message")]
+    #[cfg(debug_assertions)]
+    fn non_control_message_on_control_channel() {
+        let mut dg: Datagram = Datagram::default();
+
+        let _ = dg.add_control_header(Protocol::ClientHello.into());
+    }
+
+    #[test]
+    fn write_frame_in_small_chunks() {
+        /// Accepts at most 3 bytes per write, and is interrupted every other write.
+        struct SlowWriter {
+            written: Vec<u8>,
+            interrupt: bool,
+        }
+
+        impl std::io::Write for SlowWriter {
+            fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+                self.interrupt = !self.interrupt;
+
+                if self.interrupt {
+                    return Err(std::io::ErrorKind::Interrupted.into());
+                }
+                let n: usize = buf.len().min(3);
+                self.written.extend_from_slice(&buf[..n]);
+                Ok(n)
+            }
+
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let mut dg: Datagram = Datagram::default();
+
+        dg.add_channel(0x0102030405060708).unwrap();
+        dg.add_string("framed").unwrap();
+
+        let mut writer: SlowWriter = SlowWriter {
+            written: vec![],
+            interrupt: false,
+        };
+        dg.write_framed_to(&mut writer).unwrap();
+
+        assert_eq!(writer.written[..2], [16, 0]); // size tag
+        assert_eq!(writer.written[2..], *dg.get_buffer());
+
+        // a datagram too large for a size tag is not written at all
+        let dg: Datagram = vec![0_u8; usize::from(DG_SIZE_MAX) + 1].into();
+        let mut written: Vec<u8> = vec![];
+
+        assert!(dg.write_framed_to(&mut written).is_err());
+        assert!(written.is_empty());
+    }
+
+    #[test]
+    fn get_data_copies_buffer() {
+        let mut dg: Datagram = Datagram::default();
+
+        dg.add_bool(true).unwrap();
+        dg.add_u16(0x0102).unwrap();
+        dg.add_i32(-1).unwrap();
+        dg.add_string("dc").unwrap();
+        dg.add_f32(1.0).unwrap();
+
+        let data: Vec<u8> = dg.get_data();
+
+        assert_eq!(data, dg.get_buffer());
+        assert_eq!(
+            data,
+            vec![1, 0x02, 0x01, 0xff, 0xff, 0xff, 0xff, 2, 0, b'd', b'c', 0, 0, 0x80, 0x3f]
+        );
+    }
+
+    #[test]
+    fn empty_datagram_accessors() {
+        let dg: Datagram = Datagram::default();
+
+        assert_eq!(dg.size(), 0);
+        assert!(dg.get_buffer().is_empty());
+        assert!(dg.get_data().is_empty());
+        assert_eq!(dg.checksum(), 1); // Adler-32 of no bytes
+        assert_eq!(dg.hex_dump_capped(16), "");
+        assert!(!dg.same_routing(&Datagram::default()));
+    }
+
+    #[test]
+    fn add_buffer_reserves_zeroed_bytes() {
+        let mut dg: Datagram = Datagram::default();
+
+        dg.add_u8(7).unwrap();
+        assert_eq!(dg.add_buffer(4), Ok(1));
+        dg.add_u8(9).unwrap();
+
+        assert_eq!(dg.get_data(), vec![7, 0, 0, 0, 0, 9]);
+    }
+
+    #[test]
+    fn compare_routing_headers() {
+        let mut dg_a: Datagram = Datagram::default();
+        let mut dg_b: Datagram = Datagram::default();
+        let mut dg_c: Datagram = Datagram::default();
+
+        dg_a.add_internal_header(vec![4000, 4001], 100, 2020).unwrap();
+        dg_a.add_string("body A").unwrap();
+
+        dg_b.add_internal_header(vec![4000, 4001], 100, 2021).unwrap();
+        dg_b.add_u64(u64::MAX).unwrap();
+
+        dg_c.add_internal_header(vec![4000, 4002], 100, 2020).unwrap();
+        dg_c.add_string("body A").unwrap();
+
+        assert!(dg_a.same_routing(&dg_b));
+        assert!(!dg_a.same_routing(&dg_c));
+
+        let mut dg_control: Datagram = Datagram::default();
+        dg_control.add_control_header(9000).unwrap();
+
+        assert!(dg_control.same_routing(&dg_control.clone()));
+        assert!(!dg_control.same_routing(&dg_a));
+
+        // truncated headers are never equal
+        let mut dg_truncated: Datagram = Datagram::default();
+        dg_truncated.add_u8(2).unwrap();
+        dg_truncated.add_channel(4000).unwrap();
+
+        assert!(!dg_truncated.same_routing(&dg_truncated.clone()));
+        assert!(!Datagram::default().same_routing(&Datagram::default()));
+    }
+
+    #[test]
+    #[cfg(feature = "dcfile")]
+    fn struct_value_round_trip() {
+        use crate::datagram::iterator::DatagramIterator;
+        use crate::dcfield::{DCField, FieldParent};
+        use crate::dcfile::{interim, DCFile};
+        use crate::dconfig::DCFileConfig;
+        use crate::dctype::DCTypeEnum;
+
+        let dcf: DCFile = interim::DCFile::from(DCFileConfig::default()).into();
+        let parent: DCStruct = DCStruct::new(&dcf);
+
+        // struct Item { uint32 id; string name; };
+        let id = StructField::Field(DCField::new(
+            "id",
+            DCTypeEnum::TUInt32.into(),
+            FieldParent::Strukt(&parent),
+        ));
+        let name = StructField::Field(DCField::new(
+            "name",
+            DCTypeEnum::TVarString.into(),
+            FieldParent::Strukt(&parent),
+        ));
+
+        let mut item: DCStruct = DCStruct::new(&dcf);
+        item.add_field(id);
+        item.add_field(name);
+
+        let value: Vec<FieldValue> = vec![
+            FieldValue::UnsignedInteger(1010),
+            FieldValue::String("Jellybean Jar".to_owned()),
+        ];
+        let mut dg: Datagram = Datagram::default();
+
+        dg.add_struct_value(&item, &value).unwrap();
+
+        // too few values, and a value of the wrong type
+        assert_eq!(
+            dg.add_struct_value(&item, &value[..1]),
+            Err(DatagramError::FieldConstraintViolation)
+        );
+        assert_eq!(
+            dg.add_struct_value(
+                &item,
+                &[FieldValue::Integer(1), FieldValue::String("".to_owned())]
+            ),
+            Err(DatagramError::FieldConstraintViolation)
+        );
+
+        let mut dgi: DatagramIterator = dg.into();
+
+        assert_eq!(dgi.read_struct(&item).unwrap(), value);
+        assert_eq!(dgi.get_remaining(), 0);
+    }
+
+    #[test]
+    fn backfill_length_tag() {
+        use crate::datagram::iterator::DatagramIterator;
+
+        let mut dg: Datagram = Datagram::default();
+        dg.add_u8(7).unwrap();
+
+        let slot: usize = dg.add_buffer(mem::size_of::<DgSizeTag>()).unwrap();
+        dg.add_u32(0xdeadbeef).unwrap();
+        dg.add_u16(1000).unwrap();
+
+        let body_size: usize = dg.size() - slot - mem::size_of::<DgSizeTag>();
+        dg.set_size_at(slot, body_size.try_into().unwrap()).unwrap();
+
+        // patching does not append anything
+        assert_eq!(dg.size(), 9);
+
+        let mut dgi: DatagramIterator = dg.clone().into();
+
+        assert_eq!(dgi.read_u8(), Ok(7));
+        assert_eq!(dgi.read_size(), Ok(6));
+        assert_eq!(dgi.read_u32(), Ok(0xdeadbeef));
+        assert_eq!(dgi.read_u16(), Ok(1000));
+
+        // only bytes already written can be overwritten
+        assert!(matches!(dg.set_u16_at(8, 0), Err(DatagramError::InvalidWrite(_))));
+        assert!(matches!(
+            dg.set_u16_at(usize::MAX, 0),
+            Err(DatagramError::InvalidWrite(_))
+        ));
+        assert!(dg.set_u16_at(7, 0).is_ok());
+        assert_eq!(dg.size(), 9);
+    }
+
+    #[test]
+    fn byte_order_layout() {
+        use crate::datagram::iterator::DatagramIterator;
+
+        let mut le: Datagram = Datagram::default();
+        let mut be: Datagram = Datagram::new_with_order(ByteOrder::BigEndian);
+
+        assert_eq!(le.get_byte_order(), ByteOrder::LittleEndian);
+        assert_eq!(be.get_byte_order(), ByteOrder::BigEndian);
+
+        le.add_u32(0x0a0b0c0d).unwrap();
+        be.add_u32(0x0a0b0c0d).unwrap();
+
+        assert_eq!(le.get_data(), vec![0x0d, 0x0c, 0x0b, 0x0a]);
+        assert_eq!(be.get_data(), vec![0x0a, 0x0b, 0x0c, 0x0d]);
+
+        be.add_u16(0x0102).unwrap();
+        be.add_u64(0x0102030405060708).unwrap();
+        be.add_string("dc").unwrap();
+
+        assert_eq!(
+            be.get_data()[4..],
+            [
+                0x01, 0x02, // u16
+                0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, // u64
+                0x00, 0x02, b'd', b'c', // string length tag and chars
+            ]
+        );
+
+        // an iterator reads values back in the datagram's byte order
+        let mut dgi: DatagramIterator = be.into();
+
+        assert_eq!(dgi.read_u32(), Ok(0x0a0b0c0d));
+        assert_eq!(dgi.read_u16(), Ok(0x0102));
+        assert_eq!(dgi.read_u64(), Ok(0x0102030405060708));
+        assert_eq!(dgi.read_string(), Ok("dc".to_owned()));
+
+        // control headers are still recognized in big-endian datagrams
+        let mut control: Datagram = Datagram::new_with_order(ByteOrder::BigEndian);
+        control.add_control_header(Protocol::MDAddChannel.into()).unwrap();
+
+        let mut dgi: DatagramIterator = control.into();
+        assert_eq!(dgi.peek_msg_type(), Ok(Protocol::MDAddChannel));
+    }
+
+    #[test]
+    fn checked_ids() {
+        let mut dg: Datagram = Datagram::default();
+
+        assert!(matches!(
+            dg.add_doid_checked(INVALID_DOID),
+            Err(DatagramError::InvalidWrite(_))
+        ));
+        assert!(matches!(
+            dg.add_channel_checked(INVALID_CHANNEL),
+            Err(DatagramError::InvalidWrite(_))
+        ));
+        assert_eq!(dg.size(), 0);
+
+        assert!(dg.add_doid_checked(DOID_MAX).is_ok());
+        assert!(dg.add_channel_checked(CONTROL_CHANNEL).is_ok());
+        assert_eq!(dg.size(), 12);
+
+        // the unchecked writers still accept zero
+        assert!(dg.add_doid(INVALID_DOID).is_ok());
+        assert!(dg.add_channel(INVALID_CHANNEL).is_ok());
+    }
+
+    #[test]
+    fn datagram_equality() {
+        let build = |name: &str, zone: Zone| -> Datagram {
+            let mut dg: Datagram = Datagram::default();
+            dg.add_internal_header(vec![4000], 100, Protocol::MDAddChannel.into())
+                .unwrap();
+            dg.add_string(name).unwrap();
+            dg.add_zone(zone).unwrap();
+            dg
+        };
+
+        assert_eq!(build("donut", 2000), build("donut", 2000));
+        assert_ne!(build("donut", 2000), build("donut", 2001));
+        assert_ne!(build("donut", 2000), build("bagel", 2000));
+
+        // the size cap is not part of the datagram's contents
+        let mut capped: Datagram = build("donut", 2000);
+        capped.override_cap(64);
+        assert_eq!(capped, build("donut", 2000));
+    }
+
+    #[test]
+    fn debug_marks_index() {
+        let mut dg: Datagram = Datagram::default();
+        assert_eq!(format!("{:?}", dg), "Datagram [|]");
+
+        dg.add_u16(0xbeef).unwrap();
+        assert_eq!(format!("{:?}", dg), "Datagram [ef be |]");
+    }
+
+    #[test]
+    fn hex_dump_is_capped() {
+        let mut dg: Datagram = Datagram::default();
+
+        dg.add_data(vec![0xde, 0xad, 0xbe, 0xef, 0x00, 0x01]).unwrap();
+
+        assert_eq!(dg.hex_dump_capped(16), "de ad be ef 00 01");
+        assert_eq!(dg.hex_dump_capped(6), "de ad be ef 00 01");
+        assert_eq!(dg.hex_dump_capped(4), "de ad be ef ... (2 more bytes)");
+        assert_eq!(dg.hex_dump_capped(0), "... (6 more bytes)");
+        assert_eq!(Datagram::default().hex_dump_capped(4), "");
+    }
+
+    #[test]
+    fn channel_little_endian_layout() {
+        use crate::datagram::iterator::DatagramIterator;
+
+        let mut dg: Datagram = Datagram::default();
+        let channel: Channel = 0x0102030405060708;
+
+        dg.add_channel(channel).unwrap();
+
+        assert_eq!(
+            dg.get_data(),
+            vec![0x08, 0x07, 0x06, 0x05, 0x04, 0x03, 0x02, 0x01]
+        );
+
+        let mut dgi: DatagramIterator = dg.into();
+
+        assert_eq!(dgi.read_channel().unwrap(), channel);
+        assert_eq!(dgi.get_remaining(), 0);
+    }
 }
@@ -19,13 +19,16 @@
 
 //! Provides structure for iterating over network packets (datagrams).
 
+use super::byte_order::ByteOrder;
 use super::datagram::{Datagram, DatagramError};
-use crate::datagram::byte_order as endianness;
+#[cfg(feature = "dcfile")]
+use crate::dcfield::{FieldValue, StructField};
+#[cfg(feature = "dcfile")]
+use crate::dcstruct::DCStruct;
 use crate::globals::*;
 use crate::protocol::*;
 use std::mem;
 use std::string::FromUtf8Error;
-use strum::IntoEnumIterator;
 use thiserror::Error;
 
 /// Custom error type for [`DatagramIterator`].
@@ -47,6 +50,10 @@ pub enum IteratorError {
     InvalidRead(&'static str),
     #[error("datagram error")]
     DatagramError(DatagramError),
+    /// This error kind is returned when a struct value is nested
+    /// deeper than the iterator's maximum struct depth.
+    #[error("struct nesting exceeds the maximum depth of {0}")]
+    MaxStructDepthExceeded(usize),
 }
 
 impl From<IteratorError> for std::io::Error {
@@ -61,11 +68,20 @@ impl From<IteratorError> for std::io::Error {
     }
 }
 
+/// Default limit on how deeply struct values may be nested
+/// when read via [`DatagramIterator::read_struct`].
+#[cfg(feature = "dcfile")]
+pub const DEFAULT_MAX_STRUCT_DEPTH: usize = 32;
+
 /// Utility for iterating value by value of a datagram message.
 #[derive(Debug)]
 pub struct DatagramIterator {
     datagram: Datagram,
     index: usize,
+    #[cfg(feature = "dcfile")]
+    struct_depth: usize,
+    #[cfg(feature = "dcfile")]
+    max_struct_depth: usize,
 }
 
 /// Create a new [`DatagramIterator`] from a [`Datagram`].
@@ -74,11 +90,22 @@ impl From<Datagram> for DatagramIterator {
         Self {
             datagram: value,
             index: 0,
+            #[cfg(feature = "dcfile")]
+            struct_depth: 0,
+            #[cfg(feature = "dcfile")]
+            max_struct_depth: DEFAULT_MAX_STRUCT_DEPTH,
         }
     }
 }
 
 impl DatagramIterator {
+    /// Creates a new [`DatagramIterator`] positioned at the start
+    /// of the given [`Datagram`]. Same as [`DatagramIterator::from`].
+    #[inline]
+    pub fn new(dg: Datagram) -> Self {
+        Self::from(dg)
+    }
+
     pub fn check_read_length(&mut self, bytes: usize) -> Result<(), IteratorError> {
         let new_index: usize = self.index + bytes;
 
@@ -95,9 +122,14 @@ impl DatagramIterator {
     }
 
     /// Manually sets the `index` position.
+    /// Returns [`IteratorError::EndOfFile`] if it's past the end of the buffer.
     #[inline]
-    pub fn seek(&mut self, index: usize) {
-        self.index = index
+    pub fn seek(&mut self, to: usize) -> Result<(), IteratorError> {
+        if to > self.datagram.size() {
+            return Err(IteratorError::EndOfFile);
+        }
+        self.index = to;
+        Ok(())
     }
 
     /// Increments the `index` by `bytes` length.
@@ -108,30 +140,37 @@ impl DatagramIterator {
         Ok(())
     }
 
-    /// Returns the number of unread bytes left in the datagram
+    /// Returns the number of unread bytes left in the datagram,
+    /// which is always 0 for an empty datagram.
     pub fn get_remaining(&mut self) -> usize {
-        self.datagram.size() - self.index
+        self.datagram.size().saturating_sub(self.index)
     }
 
     /// Reads the next number of bytes in the datagram.
     pub fn read_data(&mut self, bytes: usize) -> Result<Vec<u8>, IteratorError> {
         self.check_read_length(bytes)?;
-        let data: Vec<u8> = self.datagram.get_data();
-
-        let mut new_data: Vec<u8> = vec![];
         let read_end: usize = self.index + bytes;
 
-        for item in data.iter().take(read_end).skip(self.index) {
-            new_data.push(*item);
-        }
+        // only the bytes read are copied, not the whole buffer
+        let new_data: Vec<u8> = self.datagram.get_buffer()[self.index..read_end].to_vec();
         self.index += bytes;
 
         Ok(new_data)
     }
 
+    /// Swaps the bytes of a value read as little-endian if
+    /// the datagram is written in big-endian byte order.
+    #[inline(always)]
+    fn ordered<T>(&self, v: T, swap_bytes: fn(T) -> T) -> T {
+        match self.datagram.get_byte_order() {
+            ByteOrder::LittleEndian => v,
+            ByteOrder::BigEndian => swap_bytes(v),
+        }
+    }
+
     pub fn read_u8(&mut self) -> Result<u8, IteratorError> {
         self.check_read_length(1)?;
-        let data: Vec<u8> = self.datagram.get_data();
+        let data: &[u8] = self.datagram.get_buffer();
 
         match data.get(self.index) {
             Some(v) => {
@@ -144,7 +183,7 @@ impl DatagramIterator {
 
     pub fn read_u16(&mut self) -> Result<u16, IteratorError> {
         self.check_read_length(2)?;
-        let data: Vec<u8> = self.datagram.get_data();
+        let data: &[u8] = self.datagram.get_buffer();
 
         // bitwise operations to concatenate two u8's into one u16.
         // graphical explanation:
@@ -164,18 +203,18 @@ impl DatagramIterator {
         //
         //              01000110 00101000  (u16, 2 bytes; 0x2328; 9000 decimal)
         //
-        //  After, we use the swap_le_xx() function to make sure the bytes
-        //  are swapped to the native system byte endianness.
+        //  Shifting operates on values, not on their memory representation,
+        //  so this reads little-endian bytes correctly on any host.
         //
         let value: u16 = (data[self.index] as u16) | ((data[self.index + 1] as u16) << 8);
         self.index += 2;
 
-        Ok(endianness::swap_le_16(value))
+        Ok(self.ordered(value, u16::swap_bytes))
     }
 
     pub fn read_u32(&mut self) -> Result<u32, IteratorError> {
         self.check_read_length(4)?;
-        let data: Vec<u8> = self.datagram.get_data();
+        let data: &[u8] = self.datagram.get_buffer();
 
         let value: u32 = (data[self.index] as u32)
             | ((data[self.index + 1] as u32) << 8)
@@ -183,12 +222,12 @@ impl DatagramIterator {
             | ((data[self.index + 3] as u32) << 24);
 
         self.index += 4;
-        Ok(endianness::swap_le_32(value))
+        Ok(self.ordered(value, u32::swap_bytes))
     }
 
     pub fn read_u64(&mut self) -> Result<u64, IteratorError> {
         self.check_read_length(8)?;
-        let data: Vec<u8> = self.datagram.get_data();
+        let data: &[u8] = self.datagram.get_buffer();
 
         let value: u64 = (data[self.index] as u64)
             | ((data[self.index + 1] as u64) << 8)
@@ -200,7 +239,35 @@ impl DatagramIterator {
             | ((data[self.index + 7] as u64) << 56);
 
         self.index += 8;
-        Ok(endianness::swap_le_64(value))
+        Ok(self.ordered(value, u64::swap_bytes))
+    }
+
+    /// Calls the given read function, then restores the index,
+    /// so that the value read is not consumed, even on error.
+    fn peek<T>(
+        &mut self,
+        read: impl FnOnce(&mut Self) -> Result<T, IteratorError>,
+    ) -> Result<T, IteratorError> {
+        let start_index: usize = self.tell();
+        let value: Result<T, IteratorError> = read(self);
+
+        self.seek(start_index)?;
+        value
+    }
+
+    /// Same as [`Self::read_u8`], but does not advance the index.
+    pub fn peek_u8(&mut self) -> Result<u8, IteratorError> {
+        self.peek(Self::read_u8)
+    }
+
+    /// Same as [`Self::read_u16`], but does not advance the index.
+    pub fn peek_u16(&mut self) -> Result<u16, IteratorError> {
+        self.peek(Self::read_u16)
+    }
+
+    /// Same as [`Self::read_u32`], but does not advance the index.
+    pub fn peek_u32(&mut self) -> Result<u32, IteratorError> {
+        self.peek(Self::read_u32)
     }
 
     // Signed integer aliases, same read operation.
@@ -224,16 +291,16 @@ impl DatagramIterator {
         self.read_u64().map(|v| v as i64)
     }
 
-    /// 32-bit IEEE 754 floating point in native endianness.
+    /// 32-bit IEEE 754 floating point in little-endian.
     #[inline]
     pub fn read_f32(&mut self) -> Result<f32, IteratorError> {
-        self.read_u32().map(|v| v as f32)
+        self.read_u32().map(f32::from_bits)
     }
 
-    /// 64-bit IEEE 754 floating point in native endianness.
+    /// 64-bit IEEE 754 floating point in little-endian.
     #[inline]
     pub fn read_f64(&mut self) -> Result<f64, IteratorError> {
-        self.read_u64().map(|v| v as f64)
+        self.read_u64().map(f64::from_bits)
     }
 
     #[inline]
@@ -260,6 +327,47 @@ impl DatagramIterator {
         Ok(utf8_str)
     }
 
+    /// Reads a fixed-size char array value of `len` bytes, which has
+    /// no length tag prefix. Trailing zero padding is removed.
+    ///
+    /// If the value is not of UTF-8 format, a
+    /// [`IteratorError::Utf8Error`] variant will be returned.
+    pub fn read_fixed_string(&mut self, len: usize) -> Result<String, IteratorError> {
+        let mut str_bytes: Vec<u8> = self.read_data(len)?;
+
+        let padding: usize = str_bytes.iter().rev().take_while(|b| **b == 0).count();
+        str_bytes.truncate(len - padding);
+
+        String::from_utf8(str_bytes).map_err(IteratorError::Utf8Error)
+    }
+
+    /// Same as [`DatagramIterator::read_string`], but for strings
+    /// prefixed by a 32-bit length tag.
+    pub fn read_string32(&mut self) -> Result<String, IteratorError> {
+        let str_bytes: Vec<u8> = self.read_blob32()?;
+
+        String::from_utf8(str_bytes).map_err(IteratorError::Utf8Error)
+    }
+
+    /// Same as [`DatagramIterator::read_blob`], but for blobs
+    /// prefixed by a 32-bit length tag.
+    pub fn read_blob32(&mut self) -> Result<Vec<u8>, IteratorError> {
+        let blob_len: u32 = self.read_u32()?;
+
+        self.read_data(blob_len as usize)
+    }
+
+    /// Reads a dclass blob value (binary data) from the datagram,
+    /// which is prefixed by a 16-bit length tag with its size in bytes.
+    ///
+    /// Returns [`IteratorError::EndOfFile`] if the datagram holds
+    /// fewer bytes than the length tag declares.
+    pub fn read_blob(&mut self) -> Result<Vec<u8>, IteratorError> {
+        let blob_len: DgSizeTag = self.read_size()?;
+
+        self.read_data(usize::from(blob_len))
+    }
+
     #[inline]
     pub fn read_size(&mut self) -> Result<DgSizeTag, IteratorError> {
         self.read_u16()
@@ -280,13 +388,99 @@ impl DatagramIterator {
         self.read_u32()
     }
 
+    /// Reads a 64-bit timestamp, as written by [`Datagram::add_timestamp`].
+    #[inline]
+    pub fn read_timestamp(&mut self) -> Result<u64, IteratorError> {
+        self.read_u64()
+    }
+
+    /// Reads a 32-bit DC field keyword bitmask, as written
+    /// by [`Datagram::add_keyword_flags`].
+    #[inline]
+    pub fn read_keyword_flags(&mut self) -> Result<u32, IteratorError> {
+        self.read_u32()
+    }
+
+    /// Reads `count` number of consecutive [`DoId`] values.
+    pub fn read_doid_array(&mut self, count: usize) -> Result<Vec<DoId>, IteratorError> {
+        self.check_read_length(count * mem::size_of::<DoId>())?;
+
+        let mut doids: Vec<DoId> = Vec::with_capacity(count);
+
+        for _ in 0..count {
+            doids.push(self.read_doid()?);
+        }
+        Ok(doids)
+    }
+
+    /// Reads an array of [`DoId`] values that is prefixed
+    /// with a 16-bit count of the number of elements.
+    pub fn read_var_doid_array(&mut self) -> Result<Vec<DoId>, IteratorError> {
+        let count: DgSizeTag = self.read_size()?;
+
+        self.read_doid_array(usize::from(count))
+    }
+
+    /// Reads a value of the given DC struct, decoding each of its
+    /// fields in declaration order. Fields of a struct type are
+    /// decoded recursively, as a nested [`FieldValue::Struct`].
+    ///
+    /// Molecular fields are skipped, as they only alias other fields.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`IteratorError::MaxStructDepthExceeded`] if struct
+    /// values are nested deeper than the iterator's maximum depth,
+    /// which is [`DEFAULT_MAX_STRUCT_DEPTH`] unless set otherwise.
+    ///
+    #[cfg(feature = "dcfile")]
+    pub fn read_struct(&mut self, strukt: &DCStruct) -> Result<Vec<FieldValue>, IteratorError> {
+        if self.struct_depth >= self.max_struct_depth {
+            return Err(IteratorError::MaxStructDepthExceeded(self.max_struct_depth));
+        }
+        self.struct_depth += 1;
+
+        let mut values: Vec<FieldValue> = Vec::with_capacity(strukt.get_num_fields());
+        let mut result: Result<(), IteratorError> = Ok(());
+
+        for index in 0..strukt.get_num_fields() {
+            match strukt.get_field(index) {
+                Some(StructField::Field(field)) => match field.unpack_value(self) {
+                    Ok(value) => values.push(value),
+                    Err(err) => {
+                        result = Err(err);
+                        break;
+                    }
+                },
+                Some(StructField::Switch(_)) => {
+                    result = Err(IteratorError::InvalidRead(
+                        "Unpacking DC switches is not supported.",
+                    ));
+                    break;
+                }
+                _ => {}
+            }
+        }
+        // restore depth even on error, so the iterator stays usable
+        self.struct_depth -= 1;
+
+        result.map(|_| values)
+    }
+
+    /// Sets the limit on how deeply struct values may be nested
+    /// when read via [`DatagramIterator::read_struct`].
+    #[cfg(feature = "dcfile")]
+    pub fn set_max_struct_depth(&mut self, depth: usize) {
+        self.max_struct_depth = depth;
+    }
+
     /// Reads a `blob` data type and returns a [`Datagram`].
     pub fn read_datagram(&mut self) -> Result<Datagram, IteratorError> {
         let dg_size: DgSizeTag = self.read_size()?;
 
         let dg_payload: Vec<u8> = self.read_data(usize::from(dg_size))?;
 
-        let mut new_dg: Datagram = Datagram::default();
+        let mut new_dg: Datagram = Datagram::new_with_order(self.datagram.get_byte_order());
 
         if let Err(e) = new_dg.add_data(dg_payload) {
             return Err(IteratorError::DatagramError(e));
@@ -306,13 +500,7 @@ impl DatagramIterator {
     pub fn read_msg_type(&mut self) -> Result<Protocol, IteratorError> {
         let msg_type: MsgType = self.read_u16()?; // read message type
 
-        for message in Protocol::iter() {
-            let msg_id: MsgType = message.into();
-            if msg_type == msg_id {
-                return Ok(message);
-            }
-        }
-        Err(IteratorError::InvalidRead(
+        Protocol::from_u16(msg_type).ok_or(IteratorError::InvalidRead(
             "Tried to read an invalid message type.",
         ))
     }
@@ -320,35 +508,34 @@ impl DatagramIterator {
     /// Get the recipient count in a datagram message.
     /// Does not advance the index.
     pub fn peek_recipient_count(&mut self) -> Result<u8, IteratorError> {
-        let start_index: usize = self.index;
-        let value: u8 = self.read_u8()?;
-        self.index = start_index;
-        Ok(value)
+        self.peek_u8()
     }
 
     /// Returns the datagram's message type. Does not advance the index.
     /// Useful for if index needs to be saved or if next field isn't msg type.
     /// If iterating through a fresh datagram, use [`Self::read_msg_type`].
+    ///
+    /// Works for both server headers and control headers, which
+    /// are not followed by a sender channel.
     pub fn peek_msg_type(&mut self) -> Result<Protocol, IteratorError> {
-        let start_index: usize = self.index;
+        // seek message type, then read it; the index is restored even on error
+        let msg_type: MsgType = self.peek(|dgi| dgi.seek_msg_type().and_then(|_| dgi.read_u16()))?;
 
-        self.index = 1
-            + usize::from(self.peek_recipient_count()?) * mem::size_of::<Channel>()
-            + mem::size_of::<Channel>(); // seek message type
-
-        let msg_type: MsgType = self.read_u16()?; // read message type
-        self.index = start_index; // do not advance dgi index
+        Protocol::from_u16(msg_type).ok_or(IteratorError::InvalidRead(
+            "Tried to read an invalid message type.",
+        ))
+    }
 
-        for message in Protocol::iter() {
-            let msg_id: MsgType = message.into();
+    /// Moves the index to the message type field of the datagram's header.
+    fn seek_msg_type(&mut self) -> Result<(), IteratorError> {
+        self.seek(0)?;
+        let n_recipients: usize = usize::from(self.read_recipient_count()?);
 
-            if msg_type == msg_id {
-                return Ok(message);
-            }
+        // control messages do not carry a sender channel
+        if n_recipients == 1 && self.read_channel()? == CONTROL_CHANNEL {
+            return Ok(());
         }
-        Err(IteratorError::InvalidRead(
-            "Tried to read an invalid message type.",
-        ))
+        self.seek(1 + n_recipients * mem::size_of::<Channel>() + mem::size_of::<Channel>())
     }
 }
 
@@ -357,6 +544,304 @@ mod tests {
     use super::*;
     use crate::datagram::datagram::DatagramError;
 
+    #[test]
+    fn dgi_float_round_trip() -> Result<(), IteratorError> {
+        let mut dg: Datagram = Datagram::default();
+
+        let f32s: [f32; 8] = [
+            2.75,
+            -0.5,
+            -0.0,
+            f32::MIN_POSITIVE / 2.0, // subnormal
+            f32::from_bits(1),       // smallest subnormal
+            f32::INFINITY,
+            f32::NEG_INFINITY,
+            f32::MAX,
+        ];
+        let f64s: [f64; 8] = [
+            1.0e-300,
+            -1234.5678,
+            -0.0,
+            f64::MIN_POSITIVE / 2.0,
+            f64::from_bits(1),
+            f64::INFINITY,
+            f64::NEG_INFINITY,
+            f64::MIN,
+        ];
+
+        for v in f32s {
+            dg.add_f32(v).unwrap();
+        }
+        for v in f64s {
+            dg.add_f64(v).unwrap();
+        }
+        dg.add_f32(f32::NAN).unwrap();
+        dg.add_f64(f64::NAN).unwrap();
+
+        let mut dgi: DatagramIterator = dg.into();
+
+        // compare bits, so that signed zeroes are told apart
+        for v in f32s {
+            assert_eq!(dgi.read_f32()?.to_bits(), v.to_bits());
+        }
+        for v in f64s {
+            assert_eq!(dgi.read_f64()?.to_bits(), v.to_bits());
+        }
+        assert!(dgi.read_f32()?.is_nan());
+        assert!(dgi.read_f64()?.is_nan());
+        assert_eq!(dgi.get_remaining(), 0);
+        Ok(())
+    }
+
+    #[test]
+    fn dgi_empty_datagram() {
+        let mut dgi: DatagramIterator = Datagram::default().into();
+
+        assert_eq!(dgi.tell(), 0);
+        assert_eq!(dgi.get_remaining(), 0);
+
+        assert_eq!(dgi.read_u8(), Err(IteratorError::EndOfFile));
+        assert_eq!(dgi.read_u16(), Err(IteratorError::EndOfFile));
+        assert_eq!(dgi.read_u32(), Err(IteratorError::EndOfFile));
+        assert_eq!(dgi.read_u64(), Err(IteratorError::EndOfFile));
+        assert_eq!(dgi.read_f64(), Err(IteratorError::EndOfFile));
+        assert_eq!(dgi.read_bool(), Err(IteratorError::EndOfFile));
+        assert_eq!(dgi.read_size(), Err(IteratorError::EndOfFile));
+        assert_eq!(dgi.read_channel(), Err(IteratorError::EndOfFile));
+        assert_eq!(dgi.read_string(), Err(IteratorError::EndOfFile));
+        assert_eq!(dgi.read_blob(), Err(IteratorError::EndOfFile));
+        assert_eq!(dgi.read_data(1), Err(IteratorError::EndOfFile));
+        assert_eq!(dgi.read_recipient_count(), Err(IteratorError::EndOfFile));
+        assert_eq!(dgi.read_msg_type(), Err(IteratorError::EndOfFile));
+        assert_eq!(dgi.peek_recipient_count(), Err(IteratorError::EndOfFile));
+        assert_eq!(dgi.peek_msg_type(), Err(IteratorError::EndOfFile));
+        assert!(dgi.read_datagram().is_err());
+        assert_eq!(dgi.skip(1), Err(IteratorError::EndOfFile));
+        assert_eq!(dgi.seek(1), Err(IteratorError::EndOfFile));
+
+        // reading nothing is not an error, and no read moved the index
+        assert_eq!(dgi.read_data(0), Ok(vec![]));
+        assert_eq!(dgi.seek(0), Ok(()));
+        assert_eq!(dgi.tell(), 0);
+        assert_eq!(dgi.get_remaining(), 0);
+
+        // a failed peek restores the index, even past the recipient count
+        let mut dg: Datagram = Datagram::default();
+        dg.add_u8(1).unwrap();
+
+        let mut dgi: DatagramIterator = dg.into();
+
+        assert_eq!(dgi.peek_msg_type(), Err(IteratorError::EndOfFile));
+        assert_eq!(dgi.tell(), 0);
+        assert_eq!(dgi.get_remaining(), 1);
+    }
+
+    #[test]
+    fn peek_msg_type_of_headers() {
+        let mut dg: Datagram = Datagram::default();
+        dg.add_internal_header(vec![4000, 4001], 100, Protocol::MDAddChannel.into())
+            .unwrap();
+
+        let mut dgi: DatagramIterator = dg.into();
+
+        assert_eq!(dgi.peek_msg_type(), Ok(Protocol::MDAddChannel));
+        assert_eq!(dgi.tell(), 0);
+
+        // a single recipient that is not the control channel has a sender
+        let mut dg: Datagram = Datagram::default();
+        dg.add_internal_header(vec![4000], 100, Protocol::MDRemoveChannel.into())
+            .unwrap();
+
+        let mut dgi: DatagramIterator = dg.into();
+
+        assert_eq!(dgi.peek_msg_type(), Ok(Protocol::MDRemoveChannel));
+
+        let mut dg: Datagram = Datagram::default();
+        dg.add_control_header(Protocol::MDAddChannel.into()).unwrap();
+        dg.add_channel(4000).unwrap();
+
+        let mut dgi: DatagramIterator = dg.into();
+
+        assert_eq!(dgi.peek_msg_type(), Ok(Protocol::MDAddChannel));
+        assert_eq!(dgi.tell(), 0);
+
+        // reading the header in order gives the same message type
+        assert_eq!(dgi.read_recipient_count(), Ok(1));
+        assert_eq!(dgi.read_channel(), Ok(CONTROL_CHANNEL));
+        assert_eq!(dgi.read_msg_type(), Ok(Protocol::MDAddChannel));
+        assert_eq!(dgi.read_channel(), Ok(4000));
+    }
+
+    #[test]
+    fn peek_does_not_advance() {
+        let mut dg: Datagram = Datagram::default();
+        dg.add_u8(u8::MAX).unwrap();
+        dg.add_u16(9000).unwrap();
+        dg.add_u32(0xdeadbeef).unwrap();
+
+        let mut dgi: DatagramIterator = dg.into();
+
+        assert_eq!(dgi.peek_u8(), Ok(u8::MAX));
+        assert_eq!(dgi.tell(), 0);
+        assert_eq!(dgi.read_u8(), Ok(u8::MAX));
+
+        assert_eq!(dgi.peek_u16(), Ok(9000));
+        assert_eq!(dgi.tell(), 1);
+        assert_eq!(dgi.read_u16(), Ok(9000));
+
+        assert_eq!(dgi.peek_u32(), Ok(0xdeadbeef));
+        assert_eq!(dgi.tell(), 3);
+        assert_eq!(dgi.read_u32(), Ok(0xdeadbeef));
+
+        // peeking past the end fails without moving the index
+        assert_eq!(dgi.peek_u8(), Err(IteratorError::EndOfFile));
+        assert_eq!(dgi.tell(), 7);
+    }
+
+    #[test]
+    fn unknown_msg_type() {
+        let invalid = Err(IteratorError::InvalidRead(
+            "Tried to read an invalid message type.",
+        ));
+
+        let mut dg: Datagram = Datagram::default();
+        dg.add_internal_header(vec![4000], 100, u16::MAX).unwrap();
+
+        let mut dgi: DatagramIterator = dg.into();
+
+        assert_eq!(dgi.peek_msg_type(), invalid);
+        assert_eq!(dgi.tell(), 0);
+
+        dgi.seek(1 + 2 * mem::size_of::<Channel>()).unwrap();
+        assert_eq!(dgi.read_msg_type(), invalid);
+
+        // an empty datagram has no header to read
+        let mut dgi: DatagramIterator = Datagram::default().into();
+
+        assert_eq!(dgi.read_recipient_count(), Err(IteratorError::EndOfFile));
+        assert_eq!(dgi.read_msg_type(), Err(IteratorError::EndOfFile));
+    }
+
+    #[test]
+    fn dgi_read_one_byte_short() {
+        let short_dgi = |len: usize| -> DatagramIterator {
+            let mut dg: Datagram = Datagram::default();
+            dg.add_data(vec![u8::MAX; len - 1]).unwrap();
+            dg.into()
+        };
+
+        assert_eq!(short_dgi(2).read_u16(), Err(IteratorError::EndOfFile));
+        assert_eq!(short_dgi(4).read_u32(), Err(IteratorError::EndOfFile));
+        assert_eq!(short_dgi(8).read_u64(), Err(IteratorError::EndOfFile));
+        assert_eq!(short_dgi(2).read_i16(), Err(IteratorError::EndOfFile));
+        assert_eq!(short_dgi(4).read_i32(), Err(IteratorError::EndOfFile));
+        assert_eq!(short_dgi(8).read_i64(), Err(IteratorError::EndOfFile));
+        assert_eq!(short_dgi(4).read_f32(), Err(IteratorError::EndOfFile));
+        assert_eq!(short_dgi(8).read_f64(), Err(IteratorError::EndOfFile));
+
+        // a failed read does not consume the bytes that were there
+        let mut dgi: DatagramIterator = short_dgi(8);
+
+        assert_eq!(dgi.read_u64(), Err(IteratorError::EndOfFile));
+        assert_eq!(dgi.tell(), 0);
+        assert_eq!(dgi.get_remaining(), 7);
+    }
+
+    #[test]
+    fn dgi_read_full_datagram_field_by_field() -> Result<(), IteratorError> {
+        // Reads tens of thousands of fields from a max size datagram. If each
+        // read copied the whole buffer, this would copy gigabytes of data.
+        let mut dg: Datagram = Datagram::default();
+        let count: u16 = DG_SIZE_MAX / 2;
+
+        for v in 0..count {
+            dg.add_u16(v).unwrap();
+        }
+        let mut dgi: DatagramIterator = dg.into();
+
+        for v in 0..count {
+            assert_eq!(dgi.read_u16()?, v);
+        }
+        assert_eq!(dgi.get_remaining(), 0);
+        Ok(())
+    }
+
+    #[test]
+    fn dgi_new_constructor() -> Result<(), IteratorError> {
+        let mut dg: Datagram = Datagram::default();
+        dg.add_u32(42).unwrap();
+
+        // no existing iterator is needed to make one
+        let mut dgi: DatagramIterator = DatagramIterator::new(dg);
+
+        assert_eq!(dgi.tell(), 0);
+        assert_eq!(dgi.read_u32()?, 42);
+        Ok(())
+    }
+
+    #[test]
+    fn dgi_mixed_width_alignment() -> Result<(), IteratorError> {
+        let mut dg: Datagram = Datagram::default();
+
+        dg.add_u16(0xbeef).unwrap();
+        dg.add_u32(0xdeadc0de).unwrap();
+        dg.add_u16(0xcafe).unwrap();
+
+        let mut dgi: DatagramIterator = dg.into();
+
+        assert_eq!(dgi.read_u16()?, 0xbeef);
+        assert_eq!(dgi.tell(), 2);
+        assert_eq!(dgi.read_u32()?, 0xdeadc0de);
+        assert_eq!(dgi.tell(), 6);
+        assert_eq!(dgi.read_u16()?, 0xcafe);
+        assert_eq!(dgi.get_remaining(), 0);
+        Ok(())
+    }
+
+    #[test]
+    fn dgi_integer_round_trip() -> Result<(), IteratorError> {
+        let mut dg: Datagram = Datagram::default();
+
+        let u16s: [u16; 4] = [0, 1, 0x1234, u16::MAX];
+        let u32s: [u32; 4] = [0, 1, 0x12345678, u32::MAX];
+        let u64s: [u64; 4] = [0, 1, 0x123456789abcdef0, u64::MAX];
+        let i64s: [i64; 4] = [i64::MIN, -1, 0, i64::MAX];
+
+        for v in u16s {
+            dg.add_u16(v).unwrap();
+        }
+        for v in u32s {
+            dg.add_u32(v).unwrap();
+        }
+        for v in u64s {
+            dg.add_u64(v).unwrap();
+        }
+        for v in i64s {
+            dg.add_i64(v).unwrap();
+        }
+        dg.add_i16(i16::MIN).unwrap();
+        dg.add_i32(i32::MIN).unwrap();
+
+        let mut dgi: DatagramIterator = dg.into();
+
+        for v in u16s {
+            assert_eq!(dgi.read_u16()?, v);
+        }
+        for v in u32s {
+            assert_eq!(dgi.read_u32()?, v);
+        }
+        for v in u64s {
+            assert_eq!(dgi.read_u64()?, v);
+        }
+        for v in i64s {
+            assert_eq!(dgi.read_i64()?, v);
+        }
+        assert_eq!(dgi.read_i16()?, i16::MIN);
+        assert_eq!(dgi.read_i32()?, i32::MIN);
+        assert_eq!(dgi.get_remaining(), 0);
+        Ok(())
+    }
+
     #[test]
     #[rustfmt::skip]
     fn dgi_read_integers() -> Result<(), IteratorError> {
@@ -451,6 +936,299 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn dgi_read_multibyte_string() -> Result<(), IteratorError> {
+        let mut dg: Datagram = Datagram::default();
+        let text: &str = "héllo wörld ✓";
+
+        dg.add_string(text).unwrap();
+        dg.add_u8(u8::MAX).unwrap(); // trailing byte must not be consumed
+
+        let mut dgi: DatagramIterator = dg.into();
+
+        // The length tag is the size in bytes, not the count of characters.
+        assert_ne!(text.len(), text.chars().count());
+        assert_eq!(usize::from(dgi.read_size()?), text.len());
+
+        dgi.seek(0)?;
+
+        assert_eq!(dgi.read_string()?, text);
+        assert_eq!(dgi.read_u8()?, u8::MAX);
+        assert_eq!(dgi.get_remaining(), 0);
+
+        // A string payload that is not valid UTF-8 is rejected.
+        let mut dg: Datagram = Datagram::default();
+
+        dg.add_blob(vec![0x68, 0xc3]).unwrap(); // 'h' and a truncated 2-byte sequence
+
+        let mut dgi: DatagramIterator = dg.into();
+
+        assert!(matches!(dgi.read_string(), Err(IteratorError::Utf8Error(_))));
+        Ok(())
+    }
+
+    #[test]
+    fn dgi_read_string_and_blob() -> Result<(), IteratorError> {
+        let mut dg: Datagram = Datagram::default();
+        let strings: [&str; 3] = ["", "Toontown", "日本語のテキスト"];
+
+        for s in strings {
+            dg.add_string(s).unwrap();
+        }
+        dg.add_blob(vec![]).unwrap();
+        dg.add_blob(vec![0xde, 0xad, 0xbe, 0xef]).unwrap();
+
+        let mut dgi: DatagramIterator = dg.into();
+
+        for s in strings {
+            assert_eq!(dgi.read_string()?, s);
+        }
+        assert_eq!(dgi.read_blob()?, Vec::<u8>::new());
+        assert_eq!(dgi.read_blob()?, vec![0xde, 0xad, 0xbe, 0xef]);
+        assert_eq!(dgi.get_remaining(), 0);
+
+        // The length tag declares more bytes than are left.
+        let mut dg: Datagram = Datagram::default();
+
+        dg.add_size(8).unwrap();
+        dg.add_data(vec![b'a', b'b', b'c']).unwrap();
+
+        let mut dgi: DatagramIterator = dg.clone().into();
+        assert_eq!(dgi.read_string(), Err(IteratorError::EndOfFile));
+
+        let mut dgi: DatagramIterator = dg.into();
+        assert_eq!(dgi.read_blob(), Err(IteratorError::EndOfFile));
+        Ok(())
+    }
+
+    #[test]
+    fn dgi_read_fixed_string() -> Result<(), IteratorError> {
+        let mut dg: Datagram = Datagram::default();
+
+        dg.add_fixed_string("donut", 5).unwrap(); // exact
+        dg.add_fixed_string("ab", 4).unwrap(); // padded
+        dg.add_fixed_string("", 2).unwrap();
+
+        // over-long strings are rejected, without writing anything
+        assert_eq!(
+            dg.add_fixed_string("too long", 4),
+            Err(DatagramError::FieldConstraintViolation)
+        );
+        assert_eq!(dg.get_buffer(), b"donutab\0\0\0\0");
+
+        let mut dgi: DatagramIterator = dg.into();
+
+        assert_eq!(dgi.read_fixed_string(5)?, "donut");
+        assert_eq!(dgi.read_fixed_string(4)?, "ab");
+        assert_eq!(dgi.read_fixed_string(2)?, "");
+        assert_eq!(dgi.read_fixed_string(1), Err(IteratorError::EndOfFile));
+        Ok(())
+    }
+
+    #[test]
+    fn dgi_read_doid_arrays() -> Result<(), IteratorError> {
+        let mut dg: Datagram = Datagram::default();
+        let doids: Vec<DoId> = vec![1000, 2000, DOID_MAX];
+
+        for doid in &doids {
+            dg.add_doid(*doid).unwrap();
+        }
+        dg.add_size(doids.len() as DgSizeTag).unwrap();
+
+        for doid in &doids {
+            dg.add_doid(*doid).unwrap();
+        }
+        let mut dgi: DatagramIterator = dg.into();
+
+        assert_eq!(dgi.read_doid_array(doids.len())?, doids);
+        assert_eq!(dgi.read_var_doid_array()?, doids);
+        assert_eq!(dgi.get_remaining(), 0);
+
+        // reading past the end does not consume any bytes
+        assert_eq!(dgi.read_doid_array(1), Err(IteratorError::EndOfFile));
+        Ok(())
+    }
+
+    #[test]
+    fn dgi_read_timestamp() -> Result<(), IteratorError> {
+        let mut dg: Datagram = Datagram::default();
+        let timestamps: Vec<u64> = vec![0, 1_700_000_000, u64::MAX];
+
+        for ts in &timestamps {
+            dg.add_timestamp(*ts).unwrap();
+        }
+        let mut dgi: DatagramIterator = dg.into();
+
+        for ts in &timestamps {
+            assert_eq!(dgi.read_timestamp()?, *ts);
+        }
+        assert_eq!(dgi.get_remaining(), 0);
+        Ok(())
+    }
+
+    #[test]
+    fn dgi_read_keyword_flags() -> Result<(), IteratorError> {
+        // bit positions follow the historical keyword list order
+        const REQUIRED: u32 = 1 << 1;
+        const BROADCAST: u32 = 1 << 6;
+
+        let mut dg: Datagram = Datagram::default();
+
+        dg.add_keyword_flags(REQUIRED | BROADCAST).unwrap();
+        dg.add_keyword_flags(0).unwrap();
+
+        let mut dgi: DatagramIterator = dg.into();
+        let flags: u32 = dgi.read_keyword_flags()?;
+
+        assert_eq!(flags, REQUIRED | BROADCAST);
+        assert_ne!(flags & BROADCAST, 0);
+        assert_eq!(dgi.read_keyword_flags()?, 0);
+        Ok(())
+    }
+
+    #[test]
+    fn dgi_seek_bounds() -> Result<(), IteratorError> {
+        let mut dg: Datagram = Datagram::default();
+
+        dg.add_u32(0xdeadbeef).unwrap();
+        let mut dgi: DatagramIterator = dg.into();
+
+        dgi.seek(2)?;
+        assert_eq!(dgi.tell(), 2);
+
+        dgi.seek(4)?; // exactly the end is allowed
+        assert_eq!(dgi.get_remaining(), 0);
+
+        assert_eq!(dgi.seek(5), Err(IteratorError::EndOfFile));
+        assert_eq!(dgi.tell(), 4);
+
+        dgi.seek(0)?;
+        assert_eq!(dgi.read_u32()?, 0xdeadbeef);
+        Ok(())
+    }
+
+    #[test]
+    fn dgi_read_nested_struct() -> Result<(), IteratorError> {
+        use crate::dcfield::{DCField, FieldParent};
+        use crate::dcfile::{interim, DCFile};
+        use crate::dconfig::DCFileConfig;
+        use crate::dctype::DCTypeEnum;
+        use std::sync::Arc;
+
+        let dcf: DCFile = interim::DCFile::from(DCFileConfig::default()).into();
+        let parent: DCStruct = DCStruct::new(&dcf);
+
+        // struct Point { int16 x; int16 y; };
+        let x = StructField::Field(DCField::new(
+            "x",
+            DCTypeEnum::TInt16.into(),
+            FieldParent::Strukt(&parent),
+        ));
+        let y = StructField::Field(DCField::new(
+            "y",
+            DCTypeEnum::TInt16.into(),
+            FieldParent::Strukt(&parent),
+        ));
+
+        let mut point: DCStruct = DCStruct::new(&dcf);
+        point.add_field(x);
+        point.add_field(y);
+
+        // struct Waypoint { Point position; uint8 flags; };
+        let mut position = DCField::new(
+            "position",
+            DCTypeEnum::TStruct.into(),
+            FieldParent::Strukt(&parent),
+        );
+        position.set_struct_type(Arc::new(point.clone()));
+
+        let position = StructField::Field(position);
+        let flags = StructField::Field(DCField::new(
+            "flags",
+            DCTypeEnum::TUInt8.into(),
+            FieldParent::Strukt(&parent),
+        ));
+
+        let mut waypoint: DCStruct = DCStruct::new(&dcf);
+        waypoint.add_field(position);
+        waypoint.add_field(flags);
+
+        let mut dg: Datagram = Datagram::default();
+
+        dg.add_i16(-20).unwrap();
+        dg.add_i16(35).unwrap();
+        dg.add_i16(100).unwrap();
+        dg.add_i16(-100).unwrap();
+        dg.add_u8(3).unwrap();
+
+        let mut dgi: DatagramIterator = dg.into();
+
+        assert_eq!(
+            dgi.read_struct(&point)?,
+            vec![FieldValue::Integer(-20), FieldValue::Integer(35)]
+        );
+        assert_eq!(
+            dgi.read_struct(&waypoint)?,
+            vec![
+                FieldValue::Struct(vec![FieldValue::Integer(100), FieldValue::Integer(-100)]),
+                FieldValue::UnsignedInteger(3),
+            ]
+        );
+        assert_eq!(dgi.get_remaining(), 0);
+        Ok(())
+    }
+
+    #[test]
+    fn dgi_struct_depth_limit() {
+        use crate::dcfield::{DCField, FieldParent};
+        use crate::dcfile::{interim, DCFile};
+        use crate::dconfig::DCFileConfig;
+        use crate::dctype::DCTypeEnum;
+        use std::sync::Arc;
+
+        let dcf: DCFile = interim::DCFile::from(DCFileConfig::default()).into();
+
+        // Builds `depth` levels of structs, each wrapping the one
+        // below it, with a single uint8 field at the innermost level.
+        let nested_struct = |depth: usize| -> DCStruct {
+            let mut strukt: DCStruct = DCStruct::new(&dcf);
+            let leaf = DCField::new("leaf", DCTypeEnum::TUInt8.into(), FieldParent::Strukt(&strukt));
+            strukt.add_field(StructField::Field(leaf));
+
+            for _ in 1..depth {
+                let mut outer: DCStruct = DCStruct::new(&dcf);
+                let mut inner =
+                    DCField::new("inner", DCTypeEnum::TStruct.into(), FieldParent::Strukt(&outer));
+                inner.set_struct_type(Arc::new(strukt));
+
+                outer.add_field(StructField::Field(inner));
+                strukt = outer;
+            }
+            strukt
+        };
+        let mut dg: Datagram = Datagram::default();
+        dg.add_u8(1).unwrap();
+
+        let mut dgi: DatagramIterator = dg.clone().into();
+        assert!(dgi.read_struct(&nested_struct(DEFAULT_MAX_STRUCT_DEPTH)).is_ok());
+
+        let mut dgi: DatagramIterator = dg.clone().into();
+        assert_eq!(
+            dgi.read_struct(&nested_struct(DEFAULT_MAX_STRUCT_DEPTH + 1)),
+            Err(IteratorError::MaxStructDepthExceeded(DEFAULT_MAX_STRUCT_DEPTH))
+        );
+
+        // the limit is configurable, and is restored after an error
+        let mut dgi: DatagramIterator = dg.into();
+        dgi.set_max_struct_depth(2);
+
+        assert!(dgi.read_struct(&nested_struct(3)).is_err());
+        assert_eq!(
+            dgi.read_struct(&nested_struct(2)),
+            Ok(vec![FieldValue::Struct(vec![FieldValue::UnsignedInteger(1)])])
+        );
+    }
+
     #[test]
     fn dgi_read_message_type() -> Result<(), IteratorError> {
         let mut dg: Datagram = Datagram::default();
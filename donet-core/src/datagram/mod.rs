@@ -25,7 +25,9 @@
 //! - Iterating through and extracting information from received datagrams.
 //! - Converting endianness of datagram bytes to native byte order.
 //! - Datagram-level error handling.
+//! - Caching serialized datagrams for messages sent more than once.
 
 pub mod byte_order;
+pub mod cache;
 pub mod datagram;
 pub mod iterator;
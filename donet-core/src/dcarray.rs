@@ -25,21 +25,23 @@ use crate::dctype::{DCNumber, DCTypeDefinition, DCTypeEnum};
 use crate::hashgen::*;
 
 pub struct DCArrayType {
-    base_type: Option<DCTypeDefinition>,
+    base_type: DCTypeDefinition,
     element_type: Option<DCTypeDefinition>,
+    /// Number of elements if this is a fixed-length array, else zero.
     array_size: u16,
     array_range: Option<DCNumericRange>,
 }
 
 impl LegacyDCHash for DCArrayType {
     fn generate_hash(&self, hashgen: &mut DCHashGenerator) {
-        self.base_type.clone().unwrap().generate_hash(hashgen);
+        self.base_type.generate_hash(hashgen);
 
-        if let Some(element_type) = self.element_type.clone() {
+        if let Some(element_type) = &self.element_type {
             element_type.generate_hash(hashgen);
         }
-        if self.has_range() {
-            hashgen.add_int(self.array_range.as_ref().unwrap().min.into())
+        if let Some(range) = &self.array_range {
+            hashgen.add_int(range.min.into());
+            hashgen.add_int(range.max.into());
         } else {
             hashgen.add_int(i32::from(self.array_size))
         }
@@ -49,32 +51,22 @@ impl LegacyDCHash for DCArrayType {
 impl DCArrayType {
     pub fn new(element_type: Option<DCTypeDefinition>, size: Option<DCNumericRange>) -> Self {
         let mut new_array_type: Self = Self {
-            base_type: None,
+            base_type: DCTypeEnum::TVarArray.into(),
             element_type,
             array_size: 0_u16,
             array_range: size,
         };
 
-        if new_array_type.array_range.is_none() {
-            new_array_type.array_range = None;
-            let range: &mut DCNumericRange = new_array_type.array_range.as_mut().unwrap();
-
-            range.min = DCNumber::UnsignedInteger(0_u64);
-            range.max = DCNumber::UnsignedInteger(u64::MAX);
-        } else {
-            let range: &mut DCNumericRange = new_array_type.array_range.as_mut().unwrap();
-
+        if let Some(range) = &new_array_type.array_range {
             if range.min == range.max {
-                new_array_type.array_size = u64::from(range.min) as u16;
+                new_array_type.array_size = i32::from(range.min).try_into().unwrap_or(0_u16);
             }
         }
 
-        if new_array_type.element_type.is_some() {
-            let e_type: DCTypeDefinition = new_array_type.element_type.clone().unwrap();
+        if let Some(e_type) = &new_array_type.element_type {
+            let new_base_type: &mut DCTypeDefinition = &mut new_array_type.base_type;
 
-            let new_base_type: &mut DCTypeDefinition = new_array_type.base_type.as_mut().unwrap();
-
-            if !e_type.is_variable_length() && new_base_type.size > 0 {
+            if !e_type.is_variable_length() && new_array_type.array_size > 0 {
                 new_base_type.data_type = DCTypeEnum::TArray;
                 new_base_type.size = new_array_type.array_size * e_type.get_size();
             } else {
@@ -103,9 +95,16 @@ impl DCArrayType {
         new_array_type
     }
 
+    #[inline(always)]
+    pub fn get_base_type(&self) -> &DCTypeDefinition {
+        &self.base_type
+    }
+
+    /// Returns the number of elements in a fixed-length
+    /// array, or zero if the array has a variable length.
     #[inline(always)]
     pub fn get_array_size(&self) -> u16 {
-        self.base_type.clone().unwrap().size
+        self.array_size
     }
 
     #[inline(always)]
@@ -122,4 +121,92 @@ impl DCArrayType {
     pub fn has_range(&self) -> bool {
         self.array_range.is_some()
     }
+
+    /// Checks that an array of `length` elements is allowed
+    /// by the size constraint of this array type.
+    pub fn within_range(&self, length: u64) -> Result<(), String> {
+        let Some(range) = &self.array_range else {
+            return Ok(());
+        };
+        let num: DCNumber = match range.min {
+            DCNumber::Integer(_) => DCNumber::Integer(length.try_into().unwrap_or(i64::MAX)),
+            DCNumber::UnsignedInteger(_) => DCNumber::UnsignedInteger(length),
+            DCNumber::FloatingPoint(_) => DCNumber::FloatingPoint(length as f64),
+        };
+
+        match range.contains(num) {
+            true => Ok(()),
+            false => Err(format!(
+                "Array length {} is outside of its allowed range.",
+                length
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dcnumeric::DCNumericType;
+
+    fn hash_of(value: &impl LegacyDCHash) -> u32 {
+        let mut hashgen: DCHashGenerator = DCHashGenerator::default();
+
+        value.generate_hash(&mut hashgen);
+        hashgen.get_hash()
+    }
+
+    #[test]
+    fn fixed_and_variable_length_arrays() {
+        let element: DCNumericType = DCTypeEnum::TInt16.into();
+        let mut element_type: DCTypeDefinition = DCTypeEnum::TInt16.into();
+        element_type.size = 2;
+
+        let fixed: DCArrayType = DCArrayType::new(Some(element_type.clone()), Some((3_u64..3_u64).into()));
+
+        assert_eq!(fixed.get_array_size(), 3);
+        assert_eq!(fixed.get_element_type(), Some(element_type.clone()));
+        assert_eq!(fixed.get_base_type().data_type, DCTypeEnum::TArray);
+        assert_eq!(fixed.get_base_type().get_size(), 6);
+        assert!(fixed.within_range(3).is_ok());
+        assert!(fixed.within_range(4).is_err());
+
+        let bounded: DCArrayType = DCArrayType::new(Some(element_type.clone()), Some((0_u64..5_u64).into()));
+
+        assert_eq!(bounded.get_array_size(), 0);
+        assert_eq!(bounded.get_base_type().data_type, DCTypeEnum::TVarArray);
+        assert!(bounded.get_base_type().is_variable_length());
+        assert!(bounded.within_range(5).is_ok());
+        assert!(bounded.within_range(6).is_err());
+
+        let unbounded: DCArrayType = DCArrayType::new(Some(element_type), None);
+
+        assert!(!unbounded.has_range());
+        assert!(unbounded.within_range(u64::MAX).is_ok());
+
+        assert_ne!(hash_of(&fixed), hash_of(&element));
+        assert_ne!(hash_of(&bounded), hash_of(&element));
+        assert_ne!(hash_of(&fixed), hash_of(&bounded));
+    }
+
+    #[test]
+    fn char_and_uint8_arrays() {
+        let mut char_type: DCTypeDefinition = DCTypeEnum::TChar.into();
+        char_type.size = 1;
+
+        let string: DCArrayType = DCArrayType::new(Some(char_type.clone()), Some((8_u64..8_u64).into()));
+        let var_string: DCArrayType = DCArrayType::new(Some(char_type), None);
+
+        assert_eq!(string.get_base_type().data_type, DCTypeEnum::TString);
+        assert_eq!(var_string.get_base_type().data_type, DCTypeEnum::TVarString);
+
+        let mut uint8_type: DCTypeDefinition = DCTypeEnum::TUInt8.into();
+        uint8_type.size = 1;
+
+        let blob: DCArrayType = DCArrayType::new(Some(uint8_type.clone()), Some((4_u64..4_u64).into()));
+        let var_blob: DCArrayType = DCArrayType::new(Some(uint8_type), None);
+
+        assert_eq!(blob.get_base_type().data_type, DCTypeEnum::TBlob);
+        assert_eq!(var_blob.get_base_type().data_type, DCTypeEnum::TVarBlob);
+    }
 }
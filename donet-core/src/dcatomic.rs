@@ -20,27 +20,56 @@
 //! Data model for a DC Atomic Field, which represents a remote
 //! procedure call method of a Distributed Class.
 
-use crate::dcfield::DCField;
+use crate::dcfield::{DCField, FieldParent};
 use crate::dckeyword::DCKeywordList;
 use crate::dcparameter::DCParameter;
+use crate::dctype::DCTypeEnum;
+use crate::globals;
 use crate::hashgen::*;
 
 /// Represents an atomic field of a Distributed Class.
 /// This defines the interface to a DClass object, and is
 /// always implemented as a remote procedure call (RPC).
-#[derive(Debug)]
-pub struct DCAtomicField<'dc> {
-    base_field: DCField<'dc>,
-    elements: Vec<&'dc DCParameter<'dc>>,
+#[derive(Debug, Clone)]
+pub struct DCAtomicField {
+    base_field: DCField,
+    elements: Vec<DCParameter>,
 }
 
-impl std::fmt::Display for DCAtomicField<'_> {
+/// Method fields in the DC language are atomic fields,
+/// which store an ordered list of typed parameters.
+pub type DCMethodField = DCAtomicField;
+
+/// Compares the base field and the declaration of each parameter.
+impl PartialEq for DCAtomicField {
+    fn eq(&self, other: &Self) -> bool {
+        self.base_field == other.base_field
+            && self.elements.len() == other.elements.len()
+            && self
+                .elements
+                .iter()
+                .zip(&other.elements)
+                .all(|(a, b)| a.to_string() == b.to_string())
+    }
+}
+
+impl std::fmt::Display for DCAtomicField {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        writeln!(f, "TODO")
+        f.write_str(&self.base_field.get_field_name())?;
+        write!(f, "(")?;
+
+        for (i, param) in self.elements.iter().enumerate() {
+            param.fmt(f)?;
+
+            if i != self.elements.len() - 1 {
+                write!(f, ", ")?;
+            }
+        }
+        writeln!(f, ");")
     }
 }
 
-impl LegacyDCHash for DCAtomicField<'_> {
+impl LegacyDCHash for DCAtomicField {
     fn generate_hash(&self, hashgen: &mut DCHashGenerator) {
         self.base_field.generate_hash(hashgen);
 
@@ -52,18 +81,161 @@ impl LegacyDCHash for DCAtomicField<'_> {
     }
 }
 
-impl<'dc> DCAtomicField<'dc> {
+impl DCAtomicField {
+    pub fn new(name: &str, parent: FieldParent<'_>, parameters: Vec<DCParameter>) -> Self {
+        Self {
+            base_field: DCField::new(name, DCTypeEnum::TMethod.into(), parent),
+            elements: parameters,
+        }
+    }
+
+    #[inline(always)]
+    pub fn get_field_id(&self) -> globals::FieldId {
+        self.base_field.get_field_id()
+    }
+
+    #[inline(always)]
+    pub fn get_field_name(&self) -> String {
+        self.base_field.get_field_name()
+    }
+
+    /// Returns the plain field this atomic field is declared as,
+    /// such as to assign it a field ID when it is added to a DC file.
+    #[inline(always)]
+    pub(crate) fn base_field_mut(&mut self) -> &mut DCField {
+        &mut self.base_field
+    }
+
     #[inline(always)]
     pub fn get_num_elements(&self) -> usize {
         self.elements.len()
     }
 
     #[inline(always)]
-    pub fn get_element(&self, index: usize) -> Option<&'dc DCParameter<'dc>> {
-        self.elements.get(index).copied()
+    pub fn get_element(&self, index: usize) -> Option<&DCParameter> {
+        self.elements.get(index)
     }
 
-    pub fn set_keyword_list(&mut self, kw_list: DCKeywordList<'dc>) {
+    /// Appends a parameter to the end of this method's parameter list.
+    pub fn add_element(&mut self, parameter: DCParameter) {
+        self.elements.push(parameter);
+    }
+
+    pub fn set_keyword_list(&mut self, kw_list: DCKeywordList) {
         self.base_field.set_field_keyword_list(kw_list)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dcfile::{interim, DCFile};
+    use crate::dconfig::DCFileConfig;
+    use crate::dcstruct::DCStruct;
+    use crate::parser::ast;
+    use crate::parser::lexer::Lexer;
+    use crate::parser::parser::parse;
+
+    /// Parses a single dclass with one atomic field and returns its parameters.
+    fn parse_method_parameters(input: &str) -> ast::MethodBody {
+        let root: ast::Root = parse(Lexer::new(input)).unwrap();
+
+        match root.type_declarations.into_iter().next() {
+            Some(ast::TypeDeclaration::DClassType(dclass)) => match dclass.fields.into_iter().next() {
+                Some(ast::AtomicOrMolecular::Atomic(atomic)) => atomic.parameters,
+                _ => panic!("Expected an atomic field."),
+            },
+            _ => panic!("Expected a dclass declaration."),
+        }
+    }
+
+    #[test]
+    fn method_parameter_list() {
+        let dcf: DCFile = interim::DCFile::from(DCFileConfig::default()).into();
+        let strukt: DCStruct = DCStruct::new(&dcf);
+
+        let params = parse_method_parameters("dclass A { setXYZ(int16, int16, int16 z); };");
+
+        let method: DCMethodField = DCAtomicField::new(
+            "setXYZ",
+            FieldParent::Strukt(&strukt),
+            params.into_iter().map(DCParameter::from).collect(),
+        );
+
+        assert_eq!(method.get_num_elements(), 3);
+
+        for i in 0..3 {
+            let param: &DCParameter = method.get_element(i).unwrap();
+            assert_eq!(param.get_type().get_dc_type(), DCTypeEnum::TInt16);
+        }
+        assert_eq!(method.get_element(0).unwrap().get_identifier(), None);
+        assert_eq!(method.get_element(2).unwrap().get_identifier(), Some("z"));
+        assert_eq!(method.to_string(), "setXYZ(int16, int16, int16 z);\n");
+
+        // parameter types are folded into the hash, in order
+        let params = parse_method_parameters("dclass A { setXYZ(int16, int16, int32); };");
+
+        let other: DCMethodField = DCAtomicField::new(
+            "setXYZ",
+            FieldParent::Strukt(&strukt),
+            params.into_iter().map(DCParameter::from).collect(),
+        );
+
+        let mut hashgen_a: DCHashGenerator = DCHashGenerator::default();
+        let mut hashgen_b: DCHashGenerator = DCHashGenerator::default();
+
+        method.generate_hash(&mut hashgen_a);
+        other.generate_hash(&mut hashgen_b);
+
+        assert_ne!(hashgen_a.get_hash(), hashgen_b.get_hash());
+    }
+
+    #[test]
+    fn method_parameter_order_and_hash_stability() {
+        let dcf: DCFile = interim::DCFile::from(DCFileConfig::default()).into();
+        let strukt: DCStruct = DCStruct::new(&dcf);
+
+        let build = |types: &[(DCTypeEnum, Option<&str>)]| {
+            let mut method: DCMethodField =
+                DCAtomicField::new("setPosHpr", FieldParent::Strukt(&strukt), vec![]);
+
+            for (dtype, name) in types {
+                method.add_element(DCParameter::new(dtype.clone().into(), *name));
+            }
+            method
+        };
+        let hash = |method: &DCMethodField| {
+            let mut hashgen: DCHashGenerator = DCHashGenerator::default();
+
+            method.generate_hash(&mut hashgen);
+            hashgen.get_hash()
+        };
+
+        let signature = [
+            (DCTypeEnum::TFloat64, Some("x")),
+            (DCTypeEnum::TUInt32, None),
+            (DCTypeEnum::TVarString, Some("name")),
+        ];
+        let method: DCMethodField = build(&signature);
+
+        assert_eq!(method.get_num_elements(), 3);
+        assert_eq!(method.get_element(3).map(|p| p.to_string()), None);
+
+        for (i, (dtype, name)) in signature.iter().enumerate() {
+            let param: &DCParameter = method.get_element(i).unwrap();
+
+            assert_eq!(&param.get_type().get_dc_type(), dtype);
+            assert_eq!(param.get_identifier(), *name);
+        }
+
+        // an identical signature always hashes the same
+        assert_eq!(hash(&method), hash(&build(&signature)));
+        assert_eq!(hash(&method), hash(&method));
+
+        // while reordering the parameters changes the hash
+        let mut reordered = signature;
+        reordered.swap(0, 1);
+
+        assert_ne!(hash(&method), hash(&build(&reordered)));
+    }
+}
@@ -20,16 +20,22 @@
 //! Base data model for DC Field elements. Alone, it represents
 //! an attribute of a structure or Distributed Class.
 
-use crate::datagram::datagram::Datagram;
+use crate::datagram::datagram::{Datagram, DatagramError};
+use crate::datagram::iterator::{DatagramIterator, IteratorError};
 use crate::dcatomic::DCAtomicField;
+use crate::dcfile::DCFile;
 use crate::dckeyword::{DCKeywordList, IdentifyKeyword};
 use crate::dclass::DClass;
 use crate::dcmolecular::DCMolecularField;
+use crate::dcnumeric::DCNumericType;
 use crate::dconfig::*;
 use crate::dcstruct::DCStruct;
-use crate::dctype::DCTypeDefinition;
+use crate::dcswitch::DCSwitch;
+use crate::dctype::{DCTypeDefinition, DCTypeEnum};
 use crate::globals;
 use crate::hashgen::*;
+use crate::Protocol;
+use std::sync::Arc;
 
 /// Enumerator representing the 3 types of fields that inherit DC Field,
 /// which can legally be declared within a Distributed Class.
@@ -46,28 +52,74 @@ use crate::hashgen::*;
 /// of a molecular field are the parameters of all the fields it
 /// represents, joined together in the order in which they were declared
 /// when the molecular field was declared.
-#[derive(Debug)]
-pub enum ClassField<'dc> {
-    Field(DCField<'dc>),
-    Atomic(DCAtomicField<'dc>),
-    Molecular(DCMolecularField<'dc>),
+#[derive(Debug, Clone, PartialEq)]
+pub enum ClassField {
+    Field(DCField),
+    Atomic(DCAtomicField),
+    Molecular(DCMolecularField),
+}
+
+impl ClassField {
+    /// Returns the identifier of the field, regardless of its kind.
+    pub fn get_field_name(&self) -> String {
+        match self {
+            Self::Field(field) => field.get_field_name(),
+            Self::Atomic(atomic) => atomic.get_field_name(),
+            Self::Molecular(molecular) => molecular.get_field_name(),
+        }
+    }
+
+    /// Returns the field ID of the field, regardless of its kind.
+    pub fn get_field_id(&self) -> globals::FieldId {
+        match self {
+            Self::Field(field) => field.get_field_id(),
+            Self::Atomic(atomic) => atomic.get_field_id(),
+            Self::Molecular(molecular) => molecular.get_field_id(),
+        }
+    }
+
+    /// Returns the plain field the field is declared as, regardless of its kind.
+    pub(crate) fn base_field_mut(&mut self) -> &mut DCField {
+        match self {
+            Self::Field(field) => field,
+            Self::Atomic(atomic) => atomic.base_field_mut(),
+            Self::Molecular(molecular) => molecular.base_field_mut(),
+        }
+    }
 }
 
 /// A different enumerator representing DC Field types used
-/// for DC Structs, since they cannot contain DC Atomic Fields.
-#[derive(Debug)]
-pub enum StructField<'dc> {
-    Field(DCField<'dc>),
-    Molecular(DCMolecularField<'dc>),
+/// for DC Structs, since they cannot contain DC Atomic Fields,
+/// but can contain DC Switches.
+#[derive(Debug, Clone)]
+pub enum StructField {
+    Field(DCField),
+    Molecular(DCMolecularField),
+    Switch(DCSwitch),
 }
 
 /// A DC field element can be declared within a dclass or a
-/// struct declaration. The DC field element must have a
-/// reference to its parent, which is stored in this enum type.
+/// struct declaration. The parent element is given when the
+/// field is created, which copies the DC file configuration
+/// of its parent, as it affects how the field is hashed.
 #[derive(Debug)]
-pub enum FieldParent<'dc> {
-    DClass(&'dc DClass<'dc>),
-    Strukt(&'dc DCStruct<'dc>), // 'strukt' due to reserved keyword
+pub enum FieldParent<'a> {
+    DClass(&'a DClass),
+    Strukt(&'a DCStruct), // 'strukt' due to reserved keyword
+}
+
+/// A single value unpacked from a datagram, decoded
+/// according to the data type of the DC field it belongs to.
+#[derive(Debug, Clone, PartialEq)]
+pub enum FieldValue {
+    Integer(i64),
+    UnsignedInteger(u64),
+    FloatingPoint(f64),
+    Char(char),
+    String(String),
+    Blob(Vec<u8>),
+    /// The values of each field of a struct, in declaration order.
+    Struct(Vec<FieldValue>),
 }
 
 /// Macro for Panda historical keywords inline functions.
@@ -82,35 +134,46 @@ macro_rules! has_keyword {
 /// A field of a Distributed Class. The DCField struct is a base for
 /// struct and dclass fields. In the DC language, there are three types
 /// of field declarations, which are: plain fields, atomic, and molecular.
-#[derive(Debug)]
-pub struct DCField<'dc> {
-    keyword_list: DCKeywordList<'dc>,
-    parent_element: FieldParent<'dc>,
+#[derive(Debug, Clone)]
+pub struct DCField {
+    dc_config: DCFileConfig,
+    keyword_list: DCKeywordList,
     field_name: String,
     field_id: globals::FieldId,
     field_type: Option<DCTypeDefinition>,
-    default_value_stale: bool,
-    has_default_value: bool,
-    default_value: Vec<u8>, // stored as byte array
+    /// Shared with the DC file and every other field of the same struct type.
+    struct_type: Option<Arc<DCStruct>>,
+    /// Range and divisor transforms of a numeric data type, if any.
+    numeric_type: Option<DCNumericType>,
+    default_value: Option<Vec<u8>>, // stored as packed bytes
     bogus_field: bool,
 }
 
-impl std::fmt::Display for DCField<'_> {
+/// Compares the field's name, data type, keywords, and default
+/// value. The parent element and field ID are not compared, so
+/// fields from two different DC files can be diffed.
+impl PartialEq for DCField {
+    fn eq(&self, other: &Self) -> bool {
+        self.field_name == other.field_name
+            && self.field_type == other.field_type
+            && self.keyword_list == other.keyword_list
+            && self.default_value == other.default_value
+    }
+}
+
+impl std::fmt::Display for DCField {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         writeln!(f, "TODO")
     }
 }
 
-impl DCFileConfigAccessor for DCField<'_> {
+impl DCFileConfigAccessor for DCField {
     fn get_dc_config(&self) -> &DCFileConfig {
-        match self.parent_element {
-            FieldParent::DClass(dc) => dc.get_dc_config(),
-            FieldParent::Strukt(s) => s.get_dc_config(),
-        }
+        &self.dc_config
     }
 }
 
-impl LegacyDCHash for DCField<'_> {
+impl LegacyDCHash for DCField {
     fn generate_hash(&self, hashgen: &mut DCHashGenerator) {
         self.keyword_list.generate_hash(hashgen);
         self.field_type.clone().unwrap().generate_hash(hashgen);
@@ -122,6 +185,12 @@ impl LegacyDCHash for DCField<'_> {
         // significant.
         hashgen.add_string(self.field_name.clone());
 
+        // A field with no default value hashes the same as before
+        // default values were stored, so existing hashes are kept.
+        if let Some(default) = &self.default_value {
+            hashgen.add_blob(default);
+        }
+
         // The field ID is added to the hash here, since we need to
         // ensure the hash code comes out different in the
         // DC_MULTIPLE_INHERITANCE case.
@@ -131,7 +200,26 @@ impl LegacyDCHash for DCField<'_> {
     }
 }
 
-impl<'dc> DCField<'dc> {
+impl DCField {
+    pub fn new(name: &str, dtype: DCTypeDefinition, parent: FieldParent<'_>) -> Self {
+        let dc_config: &DCFileConfig = match parent {
+            FieldParent::DClass(dclass) => dclass.get_dc_config(),
+            FieldParent::Strukt(strukt) => strukt.get_dc_config(),
+        };
+
+        Self {
+            dc_config: dc_config.clone(),
+            keyword_list: DCKeywordList::default(),
+            field_name: name.to_owned(),
+            field_id: 0_u16,
+            field_type: Some(dtype),
+            struct_type: None,
+            numeric_type: None,
+            default_value: None,
+            bogus_field: false,
+        }
+    }
+
     #[inline(always)]
     pub fn get_field_id(&self) -> globals::FieldId {
         self.field_id
@@ -142,14 +230,16 @@ impl<'dc> DCField<'dc> {
         self.field_name.clone()
     }
 
-    /// Gets the parent DClass element reference.
-    ///
-    /// Panics if this field's parent element is not a DClass.
-    pub fn get_dclass(&self) -> &'dc DClass {
-        match self.parent_element {
-            FieldParent::DClass(dclass_ref) => dclass_ref,
-            FieldParent::Strukt(_) => panic!("Field parent is not a DClass."),
-        }
+    #[inline(always)]
+    pub fn get_field_type(&self) -> Option<&DCTypeDefinition> {
+        self.field_type.as_ref()
+    }
+
+    /// Returns the struct declaration of this field's
+    /// data type, if this field is of a struct type.
+    #[inline(always)]
+    pub fn get_struct_type(&self) -> Option<&DCStruct> {
+        self.struct_type.as_deref()
     }
 
     #[inline(always)]
@@ -162,20 +252,28 @@ impl<'dc> DCField<'dc> {
         self.field_name = name
     }
 
+    pub fn set_struct_type(&mut self, strukt: Arc<DCStruct>) {
+        self.struct_type = Some(strukt);
+    }
+
+    /// Sets the numeric type transforms of this field's data type,
+    /// which values are checked against when they are packed.
+    pub fn set_numeric_type(&mut self, numeric: DCNumericType) {
+        self.numeric_type = Some(numeric);
+    }
+
     pub fn set_field_type(&mut self, dtype: DCTypeDefinition) {
         self.field_type = Some(dtype);
-        self.has_default_value = false;
-        self.default_value = vec![];
+        self.default_value = None;
     }
 
-    pub fn set_field_keyword_list(&mut self, kw_list: DCKeywordList<'dc>) {
+    pub fn set_field_keyword_list(&mut self, kw_list: DCKeywordList) {
         self.keyword_list = kw_list;
     }
 
+    /// Sets the default value of this field, packed as its data type.
     pub fn set_default_value(&mut self, value: Vec<u8>) {
-        self.default_value = value;
-        self.has_default_value = true;
-        self.default_value_stale = false;
+        self.default_value = Some(value);
     }
 
     #[inline(always)]
@@ -185,13 +283,156 @@ impl<'dc> DCField<'dc> {
 
     #[inline(always)]
     pub fn has_default_value(&self) -> bool {
-        self.has_default_value
+        self.default_value.is_some()
+    }
+
+    /// Returns the packed bytes of this field's default value, if any.
+    #[inline(always)]
+    pub fn get_default_value(&self) -> Option<&[u8]> {
+        self.default_value.as_deref()
     }
 
     pub fn validate_ranges(&self, _packed_data: &Datagram) -> bool {
         todo!()
     }
 
+    /// Reads the next value of this field's data type from the given
+    /// [`DatagramIterator`], and returns it as a [`FieldValue`].
+    pub fn unpack_value(&self, dgi: &mut DatagramIterator) -> Result<FieldValue, IteratorError> {
+        let dtype: &DCTypeDefinition = match &self.field_type {
+            Some(dtype) => dtype,
+            None => return Err(IteratorError::InvalidRead("DC field has no data type.")),
+        };
+
+        match dtype.get_dc_type() {
+            DCTypeEnum::TInt8 => Ok(FieldValue::Integer(dgi.read_i8()?.into())),
+            DCTypeEnum::TInt16 => Ok(FieldValue::Integer(dgi.read_i16()?.into())),
+            DCTypeEnum::TInt32 => Ok(FieldValue::Integer(dgi.read_i32()?.into())),
+            DCTypeEnum::TInt64 => Ok(FieldValue::Integer(dgi.read_i64()?)),
+            DCTypeEnum::TUInt8 => Ok(FieldValue::UnsignedInteger(dgi.read_u8()?.into())),
+            DCTypeEnum::TUInt16 => Ok(FieldValue::UnsignedInteger(dgi.read_u16()?.into())),
+            DCTypeEnum::TUInt32 => Ok(FieldValue::UnsignedInteger(dgi.read_u32()?.into())),
+            DCTypeEnum::TUInt64 => Ok(FieldValue::UnsignedInteger(dgi.read_u64()?)),
+            DCTypeEnum::TChar => Ok(FieldValue::Char(dgi.read_u8()?.into())),
+            DCTypeEnum::TFloat32 => Ok(FieldValue::FloatingPoint(dgi.read_f32()?.into())),
+            DCTypeEnum::TFloat64 => Ok(FieldValue::FloatingPoint(dgi.read_f64()?)),
+            DCTypeEnum::TString | DCTypeEnum::TVarString => {
+                if dtype.is_variable_length() {
+                    return Ok(FieldValue::String(dgi.read_string()?));
+                }
+                // fixed length strings do not carry a size tag
+                let bytes: Vec<u8> = dgi.read_data(usize::from(dtype.get_size()))?;
+
+                match String::from_utf8(bytes) {
+                    Ok(string) => Ok(FieldValue::String(string)),
+                    Err(err) => Err(IteratorError::Utf8Error(err)),
+                }
+            }
+            DCTypeEnum::TBlob | DCTypeEnum::TVarBlob => {
+                let length: usize = match dtype.is_variable_length() {
+                    true => usize::from(dgi.read_size()?),
+                    false => usize::from(dtype.get_size()),
+                };
+                Ok(FieldValue::Blob(dgi.read_data(length)?))
+            }
+            DCTypeEnum::TBlob32 | DCTypeEnum::TVarBlob32 => match dtype.is_variable_length() {
+                true => Ok(FieldValue::Blob(dgi.read_blob32()?)),
+                false => Ok(FieldValue::Blob(dgi.read_data(usize::from(dtype.get_size()))?)),
+            },
+            DCTypeEnum::TStruct => match self.get_struct_type() {
+                Some(strukt) => Ok(FieldValue::Struct(dgi.read_struct(strukt)?)),
+                None => Err(IteratorError::InvalidRead("DC field has no struct type.")),
+            },
+            _ => Err(IteratorError::InvalidRead(
+                "Unpacking this DC field data type is not supported.",
+            )),
+        }
+    }
+
+    /// Writes the given [`FieldValue`] to the end of the [`Datagram`], packed
+    /// as this field's data type. This is the inverse of [`Self::unpack_value`].
+    ///
+    /// Returns [`DatagramError::FieldConstraintViolation`] if the value is not
+    /// of this field's data type, does not fit within it, or is outside the
+    /// range of a numeric data type. Values are given as packed on the wire,
+    /// so they are checked against the range scaled by the divisor.
+    pub fn pack_value(&self, dg: &mut Datagram, value: &FieldValue) -> Result<(), DatagramError> {
+        let dtype: &DCTypeDefinition = match &self.field_type {
+            Some(dtype) => dtype,
+            None => return Err(DatagramError::InvalidWrite("DC field has no data type.")),
+        };
+        let mut packed: Datagram = Datagram::default();
+
+        pack_typed_value(&mut packed, dtype, self.get_struct_type(), value)?;
+
+        if let Some(numeric) = &self.numeric_type {
+            numeric
+                .within_range(packed.get_data(), 1)
+                .map_err(|_| DatagramError::FieldConstraintViolation)?;
+        }
+        dg.add_data(packed.get_data())
+    }
+
+    /// Writes the default value of this field to the end of the [`Datagram`],
+    /// such as when a distributed object is created without a value for it.
+    ///
+    /// If this field has no default value, the zero value of its data type is
+    /// written instead: zero for numeric types, an empty string, blob, or array
+    /// for variable length types, and zeroed bytes for fixed length types.
+    /// The fields of a struct are each packed with their own default value.
+    pub fn pack_default(&self, dg: &mut Datagram) -> Result<(), DatagramError> {
+        if let Some(default) = &self.default_value {
+            return dg.add_data(default.clone());
+        }
+        let dtype: &DCTypeDefinition = match &self.field_type {
+            Some(dtype) => dtype,
+            None => return Err(DatagramError::InvalidWrite("DC field has no data type.")),
+        };
+
+        match dtype.get_dc_type() {
+            DCTypeEnum::TInt8 | DCTypeEnum::TUInt8 | DCTypeEnum::TChar => dg.add_u8(0),
+            DCTypeEnum::TInt16 | DCTypeEnum::TUInt16 => dg.add_u16(0),
+            DCTypeEnum::TInt32 | DCTypeEnum::TUInt32 => dg.add_u32(0),
+            DCTypeEnum::TInt64 | DCTypeEnum::TUInt64 => dg.add_u64(0),
+            DCTypeEnum::TFloat32 => dg.add_f32(0.0),
+            DCTypeEnum::TFloat64 => dg.add_f64(0.0),
+            DCTypeEnum::TBlob32 | DCTypeEnum::TVarBlob32 if dtype.is_variable_length() => dg.add_u32(0),
+            DCTypeEnum::TString
+            | DCTypeEnum::TVarString
+            | DCTypeEnum::TBlob
+            | DCTypeEnum::TVarBlob
+            | DCTypeEnum::TBlob32
+            | DCTypeEnum::TVarBlob32
+            | DCTypeEnum::TArray
+            | DCTypeEnum::TVarArray => match dtype.is_variable_length() {
+                true => dg.add_size(0),
+                false => dg.add_data(vec![0; usize::from(dtype.get_size())]),
+            },
+            DCTypeEnum::TStruct => {
+                let strukt: &DCStruct = match self.get_struct_type() {
+                    Some(strukt) => strukt,
+                    None => return Err(DatagramError::InvalidWrite("DC field has no struct type.")),
+                };
+
+                for index in 0..strukt.get_num_fields() {
+                    match strukt.get_field(index) {
+                        Some(StructField::Field(field)) => field.pack_default(dg)?,
+                        Some(StructField::Switch(_)) => {
+                            return Err(DatagramError::InvalidWrite(
+                                "Packing DC switches is not supported.",
+                            ))
+                        }
+                        _ => {}
+                    }
+                }
+                Ok(())
+            }
+            DCTypeEnum::TMethod => Err(DatagramError::InvalidWrite(
+                "Packing this DC field data type is not supported.",
+            )),
+        }
+    }
+
     /// Given a blob that represents the packed data for this field, returns a
     /// string formatting it for human consumption.
     pub fn format_packed_data(
@@ -252,8 +493,127 @@ impl<'dc> DCField<'dc> {
     pub fn is_airecv(&self) -> bool {
         has_keyword!(self, "airecv")
     }
+}
 
-    fn _refresh_default_value(&self) {
-        todo!()
+/// Writes a [`FieldValue`] to the end of the [`Datagram`], packed as the
+/// given data type. See [`DCField::pack_value`].
+pub(crate) fn pack_typed_value(
+    dg: &mut Datagram,
+    dtype: &DCTypeDefinition,
+    struct_type: Option<&DCStruct>,
+    value: &FieldValue,
+) -> Result<(), DatagramError> {
+    let violation = |_| DatagramError::FieldConstraintViolation;
+
+    match (dtype.get_dc_type(), value) {
+        (DCTypeEnum::TInt8, FieldValue::Integer(v)) => dg.add_i8((*v).try_into().map_err(violation)?),
+        (DCTypeEnum::TInt16, FieldValue::Integer(v)) => dg.add_i16((*v).try_into().map_err(violation)?),
+        (DCTypeEnum::TInt32, FieldValue::Integer(v)) => dg.add_i32((*v).try_into().map_err(violation)?),
+        (DCTypeEnum::TInt64, FieldValue::Integer(v)) => dg.add_i64(*v),
+        (DCTypeEnum::TUInt8, FieldValue::UnsignedInteger(v)) => {
+            dg.add_u8((*v).try_into().map_err(violation)?)
+        }
+        (DCTypeEnum::TUInt16, FieldValue::UnsignedInteger(v)) => {
+            dg.add_u16((*v).try_into().map_err(violation)?)
+        }
+        (DCTypeEnum::TUInt32, FieldValue::UnsignedInteger(v)) => {
+            dg.add_u32((*v).try_into().map_err(violation)?)
+        }
+        (DCTypeEnum::TUInt64, FieldValue::UnsignedInteger(v)) => dg.add_u64(*v),
+        (DCTypeEnum::TChar, FieldValue::Char(c)) => dg.add_u8(u32::from(*c).try_into().map_err(violation)?),
+        (DCTypeEnum::TFloat32, FieldValue::FloatingPoint(f)) => {
+            // casting a finite value beyond the range of f32 would give infinity
+            if f.is_finite() && f.abs() > f64::from(f32::MAX) {
+                return Err(DatagramError::FieldConstraintViolation);
+            }
+            dg.add_f32(*f as f32)
+        }
+        (DCTypeEnum::TFloat64, FieldValue::FloatingPoint(f)) => dg.add_f64(*f),
+        (DCTypeEnum::TString | DCTypeEnum::TVarString, FieldValue::String(s)) => {
+            if dtype.is_variable_length() {
+                return dg.add_string(s);
+            }
+            // fixed length strings do not carry a size tag
+            if s.len() != usize::from(dtype.get_size()) {
+                return Err(DatagramError::FieldConstraintViolation);
+            }
+            dg.add_data(s.as_bytes().to_vec())
+        }
+        (DCTypeEnum::TBlob | DCTypeEnum::TVarBlob, FieldValue::Blob(b)) => {
+            if dtype.is_variable_length() {
+                return dg.add_blob(b.clone());
+            }
+            if b.len() != usize::from(dtype.get_size()) {
+                return Err(DatagramError::FieldConstraintViolation);
+            }
+            dg.add_data(b.clone())
+        }
+        (DCTypeEnum::TBlob32 | DCTypeEnum::TVarBlob32, FieldValue::Blob(b)) => {
+            if dtype.is_variable_length() {
+                return dg.add_blob32(b.clone());
+            }
+            if b.len() != usize::from(dtype.get_size()) {
+                return Err(DatagramError::FieldConstraintViolation);
+            }
+            dg.add_data(b.clone())
+        }
+        (DCTypeEnum::TStruct, FieldValue::Struct(values)) => match struct_type {
+            Some(strukt) => dg.add_struct_value(strukt, values),
+            None => Err(DatagramError::InvalidWrite("DC field has no struct type.")),
+        },
+        (DCTypeEnum::TArray | DCTypeEnum::TVarArray | DCTypeEnum::TMethod, _) => Err(
+            DatagramError::InvalidWrite("Packing this DC field data type is not supported."),
+        ),
+        _ => Err(DatagramError::FieldConstraintViolation),
     }
 }
+
+/// Decodes a field update message body, which carries the
+/// [`globals::DoId`] of the distributed object, the [`globals::FieldId`]
+/// of the field being updated, and the packed value of the field.
+///
+/// The field is resolved using the given [`DCFile`], and its value
+/// is decoded according to the field's data type.
+pub fn decode_field_update(
+    dgi: &mut DatagramIterator,
+    dcfile: &DCFile,
+) -> Result<(globals::DoId, globals::FieldId, FieldValue), IteratorError> {
+    let doid: globals::DoId = dgi.read_doid()?;
+    let field_id: globals::FieldId = dgi.read_u16()?;
+
+    let field: &DCField = match dcfile.get_field_by_id(field_id) {
+        Some(field) => field,
+        None => {
+            return Err(IteratorError::InvalidRead(
+                "Received update for an unknown field ID.",
+            ))
+        }
+    };
+
+    Ok((doid, field_id, field.unpack_value(dgi)?))
+}
+
+/// Builds a complete field update message, which is the inverse of
+/// [`decode_field_update`] prefixed with the message type.
+///
+/// The field is resolved by name using the given [`DCFile`], and the
+/// value is validated against the field's data type as it is packed.
+pub fn build_field_update(
+    dcfile: &DCFile,
+    doid: globals::DoId,
+    field_name: &str,
+    value: &FieldValue,
+) -> Result<Datagram, DatagramError> {
+    let field: &DCField = match dcfile.get_field_by_name(field_name) {
+        Some(field) => field,
+        None => return Err(DatagramError::InvalidWrite("Unknown DC field name.")),
+    };
+
+    let mut dg: Datagram = Datagram::default();
+
+    dg.add_u16(Protocol::ClientObjectSetField.into())?;
+    dg.add_doid(doid)?;
+    dg.add_u16(field.get_field_id())?;
+    field.pack_value(&mut dg, value)?;
+    Ok(dg)
+}
@@ -20,15 +20,24 @@
 //! Root structure that stores the collection of DC elements
 //! in memory. Provides functions for manipulating the tree.
 
-use crate::dcfield::DCField;
-use crate::dckeyword::DCKeyword;
-use crate::dclass::DClass;
+use crate::dcatomic::DCAtomicField;
+use crate::dcfield::{ClassField, DCField, FieldParent, StructField};
+use crate::dckeyword::{DCKeyword, DCKeywordList};
+use crate::dclass::{self, DClass};
+use crate::dcmolecular::DCMolecularField;
+use crate::dcnumeric::{DCNumericRange, DCNumericType};
 use crate::dconfig::*;
-use crate::dcstruct::DCStruct;
-use crate::dctype::DCTypeDefinition;
+use crate::dcparameter::DCParameter;
+use crate::dcstruct::{self, DCStruct};
+use crate::dcswitch::DCSwitch;
+use crate::dctype::{DCTypeDefinition, DCTypeEnum};
 use crate::globals;
 use crate::hashgen::*;
 use crate::parser::ast;
+use log::warn;
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::sync::Arc;
 
 /// Represents a Python-style import statement in the DC file.
 #[derive(Debug, Clone)]
@@ -72,49 +81,305 @@ impl std::fmt::Display for DCPythonImport {
 /// or collection, of DC files and their elements such as class imports,
 /// type definitions, structures, and Distributed Classes.
 #[derive(Debug, Clone)]
-pub struct DCFile<'dc> {
+pub struct DCFile {
     config: DCFileConfig,
     baked_legacy_hash: globals::DCFileHash,
-    structs: Vec<DCStruct<'dc>>,
-    dclasses: Vec<DClass<'dc>>,
+    structs: Vec<Arc<DCStruct>>,
+    dclasses: Vec<DClass>,
+    dclass_name_2_index: HashMap<String, usize>,
     imports: Vec<DCPythonImport>,
     keywords: Vec<DCKeyword>,
     type_defs: Vec<DCTypeDefinition>,
-    field_id_2_field: Vec<&'dc DCField<'dc>>,
+    /// Copies of each field, indexed by their field ID.
+    field_id_2_field: Vec<DCField>,
     // TODO: type_id_2_type, type_name_2_type
     all_object_valid: bool,
     inherited_fields_stale: bool,
+    sort_hash_by_name: bool,
 }
 
-impl From<interim::DCFile> for DCFile<'_> {
+impl From<interim::DCFile> for DCFile {
     fn from(value: interim::DCFile) -> Self {
-        let mut imports: Vec<DCPythonImport> = vec![];
-        let mut keywords: Vec<DCKeyword> = vec![];
+        let mut dcf: Self = Self {
+            config: value.config.clone(),
+            baked_legacy_hash: 0_u32,
+            structs: vec![],
+            dclasses: vec![],
+            dclass_name_2_index: HashMap::default(),
+            imports: vec![],
+            keywords: vec![],
+            type_defs: value.type_defs.clone(),
+            field_id_2_field: vec![],
+            all_object_valid: value.all_object_valid,
+            inherited_fields_stale: false,
+            sort_hash_by_name: false,
+        };
+
+        if !value.structs.is_empty() || !value.dclasses.is_empty() {
+            ElementBuilder::new(&value).build(&mut dcf);
+            dcf.all_object_valid = value.all_object_valid;
+        }
 
         for imp in value.imports {
-            imports.push(imp.into());
+            dcf.imports.push(imp.into());
         }
 
         for kw in value.keywords {
-            keywords.push(kw.into());
+            dcf.keywords.push(kw.into());
         }
+        dcf
+    }
+}
 
+/// Builds the structs and dclasses of a DC file from their declarations.
+///
+/// The built elements are owned by the DC file. Fields of a struct type
+/// share the struct with the DC file, and a class keeps a copy of each
+/// of its parents, which are always built before the class itself.
+struct ElementBuilder<'a> {
+    interim: &'a interim::DCFile,
+    /// Built structs, in the same order as the interim structs.
+    structs: Vec<Arc<DCStruct>>,
+}
+
+impl<'a> ElementBuilder<'a> {
+    fn new(interim: &'a interim::DCFile) -> Self {
         Self {
-            config: value.config,
-            baked_legacy_hash: 0_u32,
+            interim,
             structs: vec![],
-            dclasses: vec![],
-            imports,
-            keywords,
-            type_defs: vec![],
-            field_id_2_field: vec![],
-            all_object_valid: true,
-            inherited_fields_stale: false,
         }
     }
+
+    /// Builds the structs and dclasses in declaration order, so that
+    /// their fields are assigned field IDs in the order they are declared.
+    fn build(mut self, dcf: &mut DCFile) {
+        for declaration in &self.interim.type_declarations {
+            match *declaration {
+                interim::TypeDeclaration::Struct(index) => {
+                    let built: DCStruct = self.build_struct(dcf, &self.interim.structs[index]);
+                    let index: usize = dcf.add_struct(built);
+
+                    self.structs.push(dcf.structs[index].clone());
+                }
+                interim::TypeDeclaration::DClass(index) => {
+                    let built: DClass = self.build_dclass(dcf, &self.interim.dclasses[index]);
+
+                    dcf.add_dclass(built);
+                }
+            }
+        }
+        dcf.rebuild_inherited_fields();
+    }
+
+    fn build_struct(&self, dcf: &mut DCFile, strukt: &dcstruct::interim::DCStruct) -> DCStruct {
+        let mut built: DCStruct = DCStruct::new(dcf);
+
+        for field in &strukt.fields {
+            match field {
+                ast::StructField::ParameterField(pf) => {
+                    let mut field: DCField = self.build_field(&pf.parameter, FieldParent::Strukt(&built));
+                    field.set_field_keyword_list(build_keyword_list(&pf.keywords));
+                    dcf.add_field(&mut field);
+
+                    built.add_field(StructField::Field(field));
+                }
+                ast::StructField::Switch(switch) => {
+                    let switch: DCSwitch = self.build_switch(switch, &built);
+
+                    built.add_field(StructField::Switch(switch));
+                }
+                // rejected by semantic analysis
+                ast::StructField::MethodAsField(_) => {}
+            }
+        }
+        built
+    }
+
+    /// Builds a switch of a struct. The key parameter and case fields
+    /// are not assigned field IDs, as they are parameters of the switch.
+    fn build_switch(&self, switch: &ast::Switch, parent: &DCStruct) -> DCSwitch {
+        let key: &ast::Parameter = &switch.key_parameter.parameter;
+
+        let mut built: DCSwitch = DCSwitch::new(
+            switch.identifier.as_deref(),
+            self.build_field(key, FieldParent::Strukt(parent)),
+        );
+
+        for case in &switch.cases {
+            let fields: Vec<DCField> = case
+                .fields
+                .iter()
+                .filter_map(|field| match field {
+                    ast::NamedField::ParameterField(pf) => {
+                        Some(self.build_field(&pf.parameter, FieldParent::Strukt(parent)))
+                    }
+                    // rejected by semantic analysis
+                    ast::NamedField::MethodAsField(_) => None,
+                })
+                .collect();
+
+            // redundant cases are rejected by semantic analysis
+            match &case.condition {
+                Some(condition) => {
+                    if let Some(value) = self.interim.packed_case_value(key, condition) {
+                        built.add_case(value, fields, case.breaks);
+                    }
+                }
+                None => {
+                    built.add_default_case(fields, case.breaks);
+                }
+            }
+        }
+        built
+    }
+
+    fn build_dclass(&self, dcf: &mut DCFile, dclass: &dclass::interim::DClass) -> DClass {
+        let mut built: DClass = DClass::with_fields(dcf, &dclass.identifier, vec![]);
+
+        // parents are copied before the fields are built, so that
+        // molecular fields can join atomic fields of a parent
+        for parent in &dclass.parents {
+            if let Some(parent) = dcf.get_dclass_by_name(parent) {
+                built.add_parent(parent);
+            }
+        }
+
+        for field in &dclass.fields {
+            let mut field: ClassField = match field {
+                ast::AtomicOrMolecular::Atomic(atomic) if atomic.parameter_field => {
+                    let mut field: DCField =
+                        self.build_field(&atomic.parameters[0], FieldParent::DClass(&built));
+                    field.set_field_keyword_list(build_keyword_list(&atomic.keywords));
+
+                    ClassField::Field(field)
+                }
+                ast::AtomicOrMolecular::Atomic(atomic) => {
+                    let parameters: Vec<DCParameter> = atomic
+                        .parameters
+                        .iter()
+                        .map(|param| {
+                            let dtype: DCTypeDefinition = self.interim.lookup_type(param.data_type.clone());
+                            let mut built: DCParameter = DCParameter::new(dtype, param.identifier.as_deref());
+
+                            if let Some(default) = self.interim.packed_default_value(param) {
+                                built.set_default_value(default);
+                            }
+                            built
+                        })
+                        .collect();
+                    let name: String = atomic.identifier.clone().unwrap_or_default();
+
+                    let mut field: DCAtomicField =
+                        DCAtomicField::new(&name, FieldParent::DClass(&built), parameters);
+                    field.set_keyword_list(build_keyword_list(&atomic.keywords));
+
+                    ClassField::Atomic(field)
+                }
+                ast::AtomicOrMolecular::Molecular(molecular) => {
+                    // TODO: Report molecular fields that name an unknown atomic field.
+                    let atomics: Vec<DCAtomicField> = molecular
+                        .atomic_field_identifiers
+                        .iter()
+                        .filter_map(|name| find_atomic_field(&built, name))
+                        .cloned()
+                        .collect();
+
+                    ClassField::Molecular(DCMolecularField::new(
+                        &molecular.identifier,
+                        FieldParent::DClass(&built),
+                        atomics,
+                    ))
+                }
+            };
+            dcf.add_field(field.base_field_mut());
+
+            built.add_field(field);
+        }
+        built
+    }
+
+    /// Builds a plain field, resolving its data type, struct type, and default value.
+    fn build_field(&self, param: &ast::Parameter, parent: FieldParent<'_>) -> DCField {
+        let dtype: DCTypeDefinition = self.interim.lookup_type(param.data_type.clone());
+        let name: String = param.identifier.clone().unwrap_or_default();
+
+        let mut field: DCField = DCField::new(&name, dtype, parent);
+
+        if let Some(default) = self.interim.packed_default_value(param) {
+            field.set_default_value(default);
+        }
+
+        if let ast::NonMethodDataType::NumericType(numeric) = &param.data_type {
+            if let Some(numeric_type) = build_numeric_type(numeric) {
+                field.set_numeric_type(numeric_type);
+            }
+        }
+
+        if let ast::NonMethodDataType::StructType(identifier) = &param.data_type {
+            // structs are built in declaration order, and may only refer to earlier ones
+            if let Some((index, _)) = self.interim.get_struct(identifier) {
+                if let Some(strukt) = self.structs.get(index) {
+                    field.set_struct_type(strukt.clone());
+                }
+            }
+        }
+        field
+    }
+}
+
+fn build_keyword_list(keywords: &[String]) -> DCKeywordList {
+    let mut list: DCKeywordList = DCKeywordList::default();
+
+    for name in keywords {
+        list.add_keyword(&DCKeyword::new(name));
+    }
+    list
+}
+
+/// Builds the range and divisor transforms of a numeric data type, which
+/// values of the field are checked against when packed. Returns `None` if
+/// the data type has neither, or if they cannot be applied to it.
+fn build_numeric_type(numeric: &ast::NumericType) -> Option<DCNumericType> {
+    if numeric.range.is_none() && numeric.divisor.is_none() {
+        return None;
+    }
+    let mut numeric_type: DCNumericType = DCNumericType::from(numeric.base_type.clone());
+
+    if let Some(divisor) = numeric.divisor {
+        if divisor.fract() != 0.0 || !(1.0..=f64::from(u16::MAX)).contains(&divisor) {
+            return None;
+        }
+        numeric_type.set_divisor(divisor as u16).ok()?;
+    }
+    if let Some(range) = &numeric.range {
+        let range: DCNumericRange = match numeric.base_type {
+            DCTypeEnum::TInt8 | DCTypeEnum::TInt16 | DCTypeEnum::TInt32 | DCTypeEnum::TInt64 => {
+                (range.start as i64..range.end as i64).into()
+            }
+            DCTypeEnum::TFloat32 | DCTypeEnum::TFloat64 => range.clone().into(),
+            _ => (range.start as u64..range.end as u64).into(),
+        };
+        numeric_type.set_range(range).ok()?;
+    }
+    Some(numeric_type)
 }
 
-impl std::fmt::Display for DCFile<'_> {
+/// Finds an atomic field by name among the fields of a class
+/// built so far, and then among the fields of its parents.
+fn find_atomic_field<'a>(dclass: &'a DClass, name: &str) -> Option<&'a DCAtomicField> {
+    let field: &ClassField = match dclass.get_field_by_name(name) {
+        Some(field) => field,
+        None => (0..dclass.get_num_parents())
+            .filter_map(|index| dclass.get_parent(index))
+            .find_map(|parent| parent.get_field_by_name(name))?,
+    };
+    match field {
+        ClassField::Atomic(atomic) => Some(atomic),
+        _ => None,
+    }
+}
+
+impl std::fmt::Display for DCFile {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         // output dc parser configuration variables used
         f.write_str(&self.config.to_string())?;
@@ -151,13 +416,13 @@ impl std::fmt::Display for DCFile<'_> {
     }
 }
 
-impl DCFileConfigAccessor for DCFile<'_> {
+impl DCFileConfigAccessor for DCFile {
     fn get_dc_config(&self) -> &DCFileConfig {
         &self.config
     }
 }
 
-impl LegacyDCHash for DCFile<'_> {
+impl LegacyDCHash for DCFile {
     fn generate_hash(&self, hashgen: &mut DCHashGenerator) {
         if self.config.dc_virtual_inheritance {
             // Just to change the hash output in this case.
@@ -173,13 +438,34 @@ impl LegacyDCHash for DCFile<'_> {
             strukt.generate_hash(hashgen);
         }
 
-        for dclass in &self.dclasses {
-            dclass.generate_hash(hashgen);
+        if self.sort_hash_by_name {
+            let mut dclasses: Vec<&DClass> = self.dclasses.iter().collect();
+            dclasses.sort_by_key(|dclass| dclass.get_name());
+
+            for dclass in dclasses {
+                dclass.generate_hash_sorted_by_name(hashgen);
+            }
+        } else {
+            for dclass in &self.dclasses {
+                dclass.generate_hash(hashgen);
+            }
+        }
+
+        // Historical keywords are left out, so that files which only
+        // declare them keep the same hash as in Panda and Astron.
+        let keywords: Vec<&DCKeyword> = self.keywords.iter().filter(|kw| !kw.is_historical()).collect();
+
+        if !keywords.is_empty() {
+            hashgen.add_int(keywords.len().try_into().unwrap());
+
+            for keyword in keywords {
+                keyword.generate_hash(hashgen);
+            }
         }
     }
 }
 
-impl<'dc> DCFile<'dc> {
+impl DCFile {
     /// Returns a 32-bit hash index associated with this file.  This number is
     /// guaranteed to be consistent if the contents of the file have not changed,
     /// and it is very likely to be different if the contents of the file do change.
@@ -187,86 +473,1050 @@ impl<'dc> DCFile<'dc> {
     /// If called more than once, it will reuse the already calculated hash,
     /// as this structure is guaranteed to be immutable after initialization.
     pub fn get_legacy_hash(&self) -> globals::DCFileHash {
+        if !self.all_object_valid {
+            warn!("Generating the hash of a DC file that is not valid.");
+        }
         if self.baked_legacy_hash != 0 {
             self.baked_legacy_hash
         } else {
             let mut hashgen: DCHashGenerator = DCHashGenerator::default();
 
-            self.generate_hash(&mut hashgen);
-            hashgen.get_hash()
+            self.generate_hash(&mut hashgen);
+            hashgen.get_hash()
+        }
+    }
+
+    /// Empties all DC elements from this file and resets its flags,
+    /// so that the same structure can be reused. The DC file
+    /// configuration is kept, and any baked hash is invalidated.
+    pub fn clear(&mut self) {
+        self.baked_legacy_hash = 0_u32;
+        self.structs.clear();
+        self.dclasses.clear();
+        self.dclass_name_2_index.clear();
+        self.imports.clear();
+        self.keywords.clear();
+        self.type_defs.clear();
+        self.field_id_2_field.clear();
+        self.all_object_valid = true;
+        self.inherited_fields_stale = false;
+    }
+
+    /// Returns `false` if a semantic error, such as a redeclared
+    /// dclass name, was found while building this DC file.
+    pub fn is_valid(&self) -> bool {
+        self.all_object_valid
+    }
+
+    /// If enabled, dclasses and their fields are folded into the hash
+    /// ordered by name, so the hash no longer depends on the order in
+    /// which they were declared. Disabled by default, which keeps the
+    /// hash compatible with Panda and Astron.
+    pub fn set_sort_hash_by_name(&mut self, sort: bool) {
+        self.sort_hash_by_name = sort;
+        self.baked_legacy_hash = 0_u32;
+    }
+
+    pub fn get_sort_hash_by_name(&self) -> bool {
+        self.sort_hash_by_name
+    }
+
+    /// Returns a string with the hash as a pretty format hexadecimal.
+    ///
+    /// The hash is always written as `0x` followed by 8 zero-padded,
+    /// lowercase hex digits, most significant digit first, which is
+    /// the form Astron prints. This is the numeric value of the hash;
+    /// in datagrams it is still sent as a little-endian `u32`.
+    pub fn get_pretty_hash(&self) -> String {
+        format!("0x{:0width$x}", self.get_legacy_hash(), width = 8) // 2 hex / byte = 8 hex
+    }
+
+    // ---------- Python Imports ---------- //
+
+    pub fn get_num_imports(&self) -> usize {
+        self.imports.len()
+    }
+
+    pub fn get_python_import(&self, index: usize) -> &DCPythonImport {
+        self.imports.get(index).expect("Index out of bounds.")
+    }
+
+    // ---------- DC Keyword ---------- //
+
+    pub fn get_num_keywords(&self) -> usize {
+        self.keywords.len()
+    }
+
+    /// Returns the keyword at the given index, in declaration order.
+    pub fn get_keyword(&self, index: usize) -> Option<&DCKeyword> {
+        self.keywords.get(index)
+    }
+
+    pub fn has_keyword(&self, keyword: &str) -> bool {
+        self.keywords.iter().any(|kw| kw.get_name() == keyword)
+    }
+
+    /// Declares a new keyword in this file. Returns `false`, without
+    /// adding it, if a keyword with the same name is already declared.
+    pub fn add_keyword(&mut self, keyword: &str) -> bool {
+        if self.has_keyword(keyword) {
+            return false;
+        }
+        self.keywords.push(DCKeyword::new(keyword));
+        true
+    }
+
+    // ---------- Distributed Class ---------- //
+
+    pub fn get_num_dclasses(&self) -> usize {
+        self.dclasses.len()
+    }
+
+    /// Returns the dclass at the given index, in declaration order.
+    pub fn get_dclass(&self, index: usize) -> Option<&DClass> {
+        self.dclasses.get(index)
+    }
+
+    /// Returns the dclass with the given ID. As IDs are assigned
+    /// sequentially by [`DCFile::add_dclass`], this is its index.
+    pub fn get_dclass_by_id(&self, id: globals::DClassId) -> Option<&DClass> {
+        self.get_dclass(usize::from(id))
+    }
+
+    /// Returns the first dclass declared with the given name, or
+    /// `None` if no dclass in this file has that name.
+    pub fn get_dclass_by_name(&self, name: &str) -> Option<&DClass> {
+        self.dclass_name_2_index
+            .get(name)
+            .and_then(|index| self.dclasses.get(*index))
+    }
+
+    /// Adds a dclass to this file, assigning it the next sequential
+    /// class ID, which is returned. Inherited fields are marked stale,
+    /// as the new class may inherit from classes already in the file.
+    ///
+    /// Panics if this file has run out of 16-bit dclass IDs.
+    pub fn add_dclass(&mut self, mut dclass: DClass) -> globals::DClassId {
+        let id: globals::DClassId = self
+            .dclasses
+            .len()
+            .try_into()
+            .expect("Ran out of 16-bit DClass IDs!");
+
+        dclass.set_dclass_id(id);
+
+        match self.dclass_name_2_index.entry(dclass.get_name()) {
+            Entry::Occupied(_) => self.all_object_valid = false,
+            Entry::Vacant(entry) => {
+                entry.insert(self.dclasses.len());
+            }
+        }
+        self.dclasses.push(dclass);
+        self.inherited_fields_stale = true;
+        id
+    }
+
+    /// Rebuilds the inherited fields of every dclass, if any dclass
+    /// has been added since they were last rebuilt. This should be
+    /// called before looking up inherited fields after adding dclasses.
+    pub fn rebuild_inherited_fields(&mut self) {
+        if !self.inherited_fields_stale {
+            return;
+        }
+        for dclass in &mut self.dclasses {
+            dclass.rebuild_inherited_fields();
+        }
+        self.inherited_fields_stale = false;
+    }
+
+    /// Returns a human-readable table of every dclass in this file, with
+    /// its class ID, followed by each of its fields and their types.
+    pub fn get_class_table(&self) -> String {
+        let mut table: String = String::new();
+
+        for dclass in &self.dclasses {
+            table.push_str(&format!("{:<6} {}\n", dclass.get_dclass_id(), dclass.get_name()));
+
+            for index in 0..dclass.get_num_fields() {
+                if let Some(field) = dclass.get_field(index) {
+                    table.push_str(&format!("{:<6}   {}\n", "", format_class_field(field)));
+                }
+            }
+        }
+        table
+    }
+
+    // ---------- DC Field ---------- //
+
+    /// Returns the total number of fields declared across all
+    /// dclasses and structs, which is the number of field IDs.
+    pub fn get_num_fields(&self) -> usize {
+        self.field_id_2_field.len()
+    }
+
+    /// Returns the [`DCField`] with the given ID, wrapped in an Option.
+    pub fn get_field_by_id(&self, id: globals::FieldId) -> Option<&DCField> {
+        self.field_id_2_field.get(usize::from(id))
+    }
+
+    /// Returns the first [`DCField`] declared with the given name, if any.
+    pub fn get_field_by_name(&self, name: &str) -> Option<&DCField> {
+        self.field_id_2_field
+            .iter()
+            .find(|field| field.get_field_name() == name)
+    }
+
+    /// Assigns the field the next file-wide field ID, which is its
+    /// position in the field table, and adds a copy of it to the table.
+    ///
+    /// Panics if this file has run out of 16-bit field IDs.
+    pub fn add_field(&mut self, field: &mut DCField) -> globals::FieldId {
+        let id: globals::FieldId = self
+            .field_id_2_field
+            .len()
+            .try_into()
+            .expect("Ran out of 16-bit field IDs!");
+
+        field.set_field_id(id);
+
+        self.field_id_2_field.push(field.clone());
+        id
+    }
+
+    // ---------- DC Struct ---------- //
+
+    pub fn get_num_structs(&self) -> usize {
+        self.structs.len()
+    }
+
+    /// Returns the struct at the given index, in declaration order.
+    pub fn get_struct(&self, index: usize) -> Option<&DCStruct> {
+        self.structs.get(index).map(Arc::as_ref)
+    }
+
+    /// Appends a struct to this file, returning its index.
+    pub fn add_struct(&mut self, strukt: DCStruct) -> usize {
+        self.structs.push(Arc::new(strukt));
+        self.structs.len() - 1
+    }
+
+    // ---------- Merging ---------- //
+
+    /// Merges the imports, keywords, type definitions, structs, and
+    /// dclasses of another DC file into this one, such as a DC file
+    /// that was read separately. Keywords declared in both are kept once.
+    ///
+    /// Dclasses and type definitions that this file already declares are
+    /// skipped, so this file keeps its own declaration of them. Returns
+    /// their names; if there are any, this file is no longer valid.
+    ///
+    /// The field table is not merged, as field IDs are assigned per file;
+    /// see [`DCFile::add_field`].
+    pub fn import_from(&mut self, other: DCFile) -> Vec<String> {
+        let mut collisions: Vec<String> = vec![];
+
+        self.imports.extend(other.imports);
+
+        for keyword in other.keywords {
+            self.add_keyword(&keyword.get_name());
+        }
+
+        for type_def in other.type_defs {
+            if let Some(alias) = type_def.get_alias() {
+                if self.type_defs.iter().any(|td| td.get_alias() == Some(alias)) {
+                    collisions.push(alias.to_owned());
+                    continue;
+                }
+            }
+            self.type_defs.push(type_def);
+        }
+
+        self.structs.extend(other.structs);
+
+        for dclass in other.dclasses {
+            let name: String = dclass.get_name();
+
+            if self.get_dclass_by_name(&name).is_some() {
+                collisions.push(name);
+                continue;
+            }
+            self.add_dclass(dclass);
+        }
+
+        if !collisions.is_empty() || !other.all_object_valid {
+            self.all_object_valid = false;
+        }
+        self.baked_legacy_hash = 0_u32;
+        collisions
+    }
+}
+
+/// Formats a dclass field as its name and type, for [`DCFile::get_class_table`].
+fn format_class_field(field: &ClassField) -> String {
+    match field {
+        ClassField::Field(field) => {
+            let dtype: String = match field.get_field_type() {
+                Some(dtype) => match dtype.get_alias() {
+                    Some(alias) => alias.to_owned(),
+                    None => dtype.get_dc_type().to_string(),
+                },
+                None => "?".to_owned(),
+            };
+            format!("{} : {}", field.get_field_name(), dtype)
+        }
+        // atomic fields are already formatted as their declaration
+        ClassField::Atomic(atomic) => atomic.to_string().trim_end_matches([';', '\n']).to_owned(),
+        ClassField::Molecular(molecular) => {
+            let atomics: Vec<String> = (0..molecular.get_num_atomics())
+                .filter_map(|index| molecular.get_atomic_field(index))
+                .map(|atomic| atomic.get_field_name())
+                .collect();
+
+            format!("{} : {}", molecular.get_field_name(), atomics.join(", "))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::datagram::datagram::{Datagram, DatagramError};
+    use crate::datagram::iterator::{DatagramIterator, IteratorError};
+    use crate::dcfield::{build_field_update, decode_field_update, FieldParent, FieldValue, StructField};
+    use crate::dctype::DCTypeEnum;
+    use crate::Protocol;
+
+    fn empty_dcfile() -> DCFile {
+        DCFile {
+            config: DCFileConfig::default(),
+            baked_legacy_hash: 0_u32,
+            structs: vec![],
+            dclasses: vec![],
+            dclass_name_2_index: HashMap::default(),
+            imports: vec![],
+            keywords: vec![],
+            type_defs: vec![],
+            field_id_2_field: vec![],
+            all_object_valid: true,
+            inherited_fields_stale: false,
+            sort_hash_by_name: false,
+        }
+    }
+
+    #[test]
+    fn list_class_table() {
+        use crate::dcatomic::DCAtomicField;
+        use crate::dcparameter::DCParameter;
+        use crate::dcstruct::DCStruct;
+
+        let base: DCFile = empty_dcfile();
+        let strukt: DCStruct = DCStruct::new(&base);
+
+        let x: ClassField = ClassField::Field(DCField::new(
+            "setX",
+            DCTypeEnum::TInt16.into(),
+            FieldParent::Strukt(&strukt),
+        ));
+        let xy: ClassField = ClassField::Atomic(DCAtomicField::new(
+            "setXY",
+            FieldParent::Strukt(&strukt),
+            vec![
+                DCParameter::new(DCTypeEnum::TInt16.into(), Some("x")),
+                DCParameter::new(DCTypeEnum::TInt16.into(), Some("y")),
+            ],
+        ));
+
+        let mut dcf: DCFile = empty_dcfile();
+        dcf.dclasses
+            .push(DClass::with_fields(&base, "DistributedNode", vec![x, xy]));
+
+        let table: String = dcf.get_class_table();
+        let lines: Vec<&str> = table.lines().collect();
+
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0], "0      DistributedNode");
+        assert_eq!(lines[1].trim(), "setX : int16");
+        assert_eq!(lines[2].trim(), "setXY(int16 x, int16 y)");
+        assert_eq!(empty_dcfile().get_class_table(), "");
+    }
+
+    #[test]
+    fn import_from_other_file() {
+        let base: DCFile = empty_dcfile();
+
+        let mut main: DCFile = empty_dcfile();
+        main.add_keyword("abcdef");
+        main.add_dclass(DClass::with_fields(&base, "DistributedNode", vec![]));
+
+        let mut game: DCFile = empty_dcfile();
+        game.add_keyword("abcdef");
+        game.add_keyword("ghijkl");
+        game.imports.push(DCPythonImport {
+            module: "views".to_string(),
+            symbols: vec!["DistributedDonut".to_string()],
+        });
+        game.add_struct(DCStruct::new(&base));
+        game.add_dclass(DClass::with_fields(&base, "DistributedDonut", vec![]));
+        game.add_dclass(DClass::with_fields(&base, "DistributedBagel", vec![]));
+
+        assert!(main.import_from(game).is_empty());
+        assert!(main.is_valid());
+
+        assert_eq!(main.get_num_imports(), 1);
+        assert_eq!(main.get_num_keywords(), 2);
+        assert_eq!(main.get_num_structs(), 1);
+        assert_eq!(main.get_num_dclasses(), 3);
+
+        // imported classes are given the next dclass IDs
+        let bagel: &DClass = main.get_dclass_by_name("DistributedBagel").unwrap();
+        assert_eq!(bagel.get_dclass_id(), 2);
+
+        // a class declared in both files is a collision
+        let mut other: DCFile = empty_dcfile();
+        other.add_dclass(DClass::with_fields(&base, "DistributedNode", vec![]));
+        other.type_defs.push(DCTypeEnum::TUInt32.into());
+
+        assert_eq!(main.import_from(other), vec!["DistributedNode".to_owned()]);
+        assert!(!main.is_valid());
+
+        // the colliding class is skipped, keeping this file's declaration
+        assert_eq!(main.get_num_dclasses(), 3);
+        assert_eq!(
+            main.get_dclass_by_name("DistributedNode")
+                .unwrap()
+                .get_dclass_id(),
+            0
+        );
+    }
+
+    #[test]
+    fn hash_sorted_by_name() {
+        let base: DCFile = empty_dcfile();
+        let strukt: DCStruct = DCStruct::new(&base);
+
+        let new_field = |name: &str| {
+            ClassField::Field(DCField::new(
+                name,
+                DCTypeEnum::TUInt32.into(),
+                FieldParent::Strukt(&strukt),
+            ))
+        };
+        let set_x: ClassField = new_field("setX");
+        let set_y: ClassField = new_field("setY");
+
+        // same classes and fields, declared in a different order
+        let mut dcf: DCFile = empty_dcfile();
+        dcf.add_dclass(DClass::with_fields(
+            &base,
+            "DistributedNode",
+            vec![set_x.clone(), set_y.clone()],
+        ));
+        dcf.add_dclass(DClass::with_fields(&base, "DistributedAvatar", vec![]));
+
+        let mut reordered: DCFile = empty_dcfile();
+        reordered.add_dclass(DClass::with_fields(&base, "DistributedAvatar", vec![]));
+        reordered.add_dclass(DClass::with_fields(
+            &base,
+            "DistributedNode",
+            vec![set_y, set_x.clone()],
+        ));
+
+        assert!(!dcf.get_sort_hash_by_name());
+        assert_ne!(dcf.get_legacy_hash(), reordered.get_legacy_hash());
+
+        dcf.set_sort_hash_by_name(true);
+        reordered.set_sort_hash_by_name(true);
+
+        assert!(dcf.get_sort_hash_by_name());
+        assert_eq!(dcf.get_legacy_hash(), reordered.get_legacy_hash());
+
+        // only the order is ignored, not the fields themselves
+        let mut fewer_fields: DCFile = empty_dcfile();
+        fewer_fields.set_sort_hash_by_name(true);
+        fewer_fields.add_dclass(DClass::with_fields(&base, "DistributedAvatar", vec![]));
+        fewer_fields.add_dclass(DClass::with_fields(&base, "DistributedNode", vec![set_x]));
+
+        assert_ne!(dcf.get_legacy_hash(), fewer_fields.get_legacy_hash());
+    }
+
+    #[test]
+    fn pretty_hash_of_small_file() {
+        let base: DCFile = empty_dcfile();
+        let mut dcf: DCFile = empty_dcfile();
+
+        // The default config folds in 1 * 2 for sorted virtual
+        // inheritance, and then 0 * 3 for the dclass count.
+        assert_eq!(dcf.get_legacy_hash(), 2);
+        assert_eq!(dcf.get_pretty_hash(), "0x00000002");
+
+        dcf.add_dclass(DClass::with_fields(&base, "A", vec![]));
+
+        // 1 * 2, then 1 dclass * 3, then the name 'A' as its length
+        // (1 * 5) and byte (65 * 7), then 0 parents and 0 fields.
+        assert_eq!(dcf.get_legacy_hash(), 2 + 3 + 5 + 455);
+        assert_eq!(dcf.get_pretty_hash(), "0x000001d1");
+    }
+
+    #[test]
+    fn add_dclasses_with_sequential_ids() {
+        let base: DCFile = empty_dcfile();
+        let mut dcf: DCFile = empty_dcfile();
+
+        let names = ["DistributedNode", "DistributedAvatar", "DistributedDonut"];
+
+        for (expected_id, name) in names.iter().enumerate() {
+            assert_eq!(dcf.get_num_dclasses(), expected_id);
+
+            let id: globals::DClassId = dcf.add_dclass(DClass::with_fields(&base, name, vec![]));
+
+            assert_eq!(usize::from(id), expected_id);
+            assert_eq!(dcf.get_num_dclasses(), expected_id + 1);
+        }
+        assert!(dcf.inherited_fields_stale);
+
+        for (index, name) in names.iter().enumerate() {
+            let dclass: &DClass = dcf.get_dclass(index).unwrap();
+
+            assert_eq!(usize::from(dclass.get_dclass_id()), index);
+            assert_eq!(dclass.get_name(), *name);
+            assert_eq!(
+                dcf.get_dclass_by_name(name).unwrap().get_dclass_id(),
+                dclass.get_dclass_id()
+            );
+        }
+        assert!(dcf.get_dclass(names.len()).is_none());
+        assert!(dcf.get_dclass_by_name("DistributedToon").is_none());
+
+        dcf.clear();
+        assert!(dcf.get_dclass_by_name("DistributedNode").is_none());
+    }
+
+    #[test]
+    fn lookup_dclass_by_name() {
+        let base: DCFile = empty_dcfile();
+        let mut dcf: DCFile = empty_dcfile();
+
+        assert!(dcf.get_dclass_by_name("DistributedNode").is_none());
+
+        dcf.add_dclass(DClass::with_fields(&base, "DistributedNode", vec![]));
+        dcf.add_dclass(DClass::with_fields(&base, "DistributedAvatar", vec![]));
+
+        // hit
+        let avatar: &DClass = dcf.get_dclass_by_name("DistributedAvatar").unwrap();
+        assert_eq!(avatar.get_name(), "DistributedAvatar");
+        assert_eq!(avatar.get_dclass_id(), 1);
+
+        // miss, including names that only differ by case
+        assert!(dcf.get_dclass_by_name("DistributedToon").is_none());
+        assert!(dcf.get_dclass_by_name("distributednode").is_none());
+        assert!(dcf.get_dclass_by_name("").is_none());
+
+        // a redeclared name still resolves to the first declaration,
+        // but marks the file as not valid
+        assert!(dcf.is_valid());
+        dcf.add_dclass(DClass::with_fields(&base, "DistributedNode", vec![]));
+        assert!(!dcf.is_valid());
+        assert_eq!(
+            dcf.get_dclass_by_name("DistributedNode").unwrap().get_dclass_id(),
+            0
+        );
+    }
+
+    #[test]
+    fn get_dclass_by_index_and_id() {
+        let base: DCFile = empty_dcfile();
+        let mut dcf: DCFile = empty_dcfile();
+
+        assert!(dcf.get_dclass(0).is_none());
+        assert!(dcf.get_dclass_by_id(0).is_none());
+
+        dcf.add_dclass(DClass::with_fields(&base, "DistributedNode", vec![]));
+        let id: globals::DClassId = dcf.add_dclass(DClass::with_fields(&base, "DistributedAvatar", vec![]));
+
+        let by_index: &DClass = dcf.get_dclass(1).unwrap();
+        let by_id: &DClass = dcf.get_dclass_by_id(id).unwrap();
+
+        assert!(std::ptr::eq(by_index, by_id));
+        assert_eq!(by_id.get_name(), "DistributedAvatar");
+
+        assert!(dcf.get_dclass(2).is_none());
+        assert!(dcf.get_dclass_by_id(globals::DClassId::MAX).is_none());
+    }
+
+    #[test]
+    fn next_dclass_id_is_sequential() {
+        use crate::dclass::interim::DClass as InterimDClass;
+        use crate::parser::lexer::Span;
+        use crate::parser::pipeline::PipelineData;
+
+        let new_dclass = |identifier: &str| InterimDClass {
+            span: Span {
+                min: 0,
+                max: 0,
+                line: 1,
+                column: 1,
+            },
+            identifier: identifier.to_owned(),
+            parents: vec![],
+            fields: vec![],
+            class_id: 0,
+        };
+
+        let mut pipeline: PipelineData = DCFileConfig::default().into();
+        let mut dcf: interim::DCFile = DCFileConfig::default().into();
+
+        // the first dclass in an empty file is assigned ID 0
+        for expected in 0..3 {
+            let dclass: InterimDClass = new_dclass("DistributedNode");
+            let id: globals::DClassId = dcf.get_next_dclass_id(&mut pipeline, &dclass).unwrap();
+
+            assert_eq!(id, expected);
+            dcf.dclasses.push(dclass);
+        }
+        assert!(!pipeline.failing());
+
+        // the last ID before the limit is still assigned
+        let dclass: InterimDClass = new_dclass("DistributedNode");
+
+        dcf.dclasses
+            .resize_with(usize::from(globals::DClassId::MAX - 1), || new_dclass("A"));
+
+        assert_eq!(
+            dcf.get_next_dclass_id(&mut pipeline, &dclass).unwrap(),
+            globals::DClassId::MAX - 1
+        );
+        assert!(!pipeline.failing());
+
+        // while the file runs out of IDs once it reaches the limit
+        dcf.dclasses.push(new_dclass("A"));
+
+        assert!(dcf.get_next_dclass_id(&mut pipeline, &dclass).is_err());
+        assert!(pipeline.failing());
+    }
+
+    #[test]
+    fn add_and_get_structs() {
+        use crate::dcfield::StructField;
+
+        let base: DCFile = empty_dcfile();
+        let strukt: DCStruct = DCStruct::new(&base);
+
+        let x: StructField = StructField::Field(DCField::new(
+            "x",
+            DCTypeEnum::TFloat32.into(),
+            FieldParent::Strukt(&strukt),
+        ));
+
+        let mut point: DCStruct = DCStruct::new(&base);
+        point.add_field(x);
+
+        let mut dcf: DCFile = empty_dcfile();
+        assert_eq!(dcf.get_num_structs(), 0);
+        assert!(dcf.get_struct(0).is_none());
+
+        assert_eq!(dcf.add_struct(DCStruct::new(&base)), 0);
+        assert_eq!(dcf.add_struct(point), 1);
+        assert_eq!(dcf.get_num_structs(), 2);
+
+        assert_eq!(dcf.get_struct(0).unwrap().get_num_fields(), 0);
+        assert_eq!(dcf.get_struct(1).unwrap().get_num_fields(), 1);
+        assert!(dcf.get_struct(2).is_none());
+    }
+
+    #[test]
+    fn keywords_fold_into_file_hash() {
+        let mut dcf: DCFile = empty_dcfile();
+        let empty_hash: globals::DCFileHash = dcf.get_legacy_hash();
+
+        // historical keywords keep the legacy hash
+        assert!(dcf.add_keyword("broadcast"));
+        assert!(dcf.add_keyword("ram"));
+        assert_eq!(dcf.get_legacy_hash(), empty_hash);
+
+        assert!(dcf.add_keyword("abcdef"));
+        assert!(!dcf.add_keyword("abcdef"));
+
+        let custom_hash: globals::DCFileHash = dcf.get_legacy_hash();
+        assert_ne!(custom_hash, empty_hash);
+
+        assert_eq!(dcf.get_num_keywords(), 3);
+        assert_eq!(dcf.get_keyword(2).unwrap().get_name(), "abcdef");
+        assert!(dcf.get_keyword(3).is_none());
+        assert!(dcf.has_keyword("ram"));
+        assert!(!dcf.has_keyword("db"));
+
+        // keywords are hashed in declaration order
+        let mut reordered: DCFile = empty_dcfile();
+        reordered.add_keyword("ghijkl");
+        reordered.add_keyword("abcdef");
+
+        dcf.add_keyword("ghijkl");
+        assert_ne!(dcf.get_legacy_hash(), custom_hash);
+        assert_ne!(dcf.get_legacy_hash(), reordered.get_legacy_hash());
+    }
+
+    #[test]
+    fn rebuild_inherited_fields_of_hierarchy() {
+        let base: DCFile = empty_dcfile();
+        let strukt: DCStruct = DCStruct::new(&base);
+
+        let new_field = |name: &str| {
+            ClassField::Field(DCField::new(
+                name,
+                DCTypeEnum::TUInt32.into(),
+                FieldParent::Strukt(&strukt),
+            ))
+        };
+        let set_name: ClassField = new_field("setName");
+        let set_hp: ClassField = new_field("setHp");
+        let set_max_hp: ClassField = new_field("setMaxHp");
+        let set_toon_name: ClassField = ClassField::Field(DCField::new(
+            "setName",
+            DCTypeEnum::TString.into(),
+            FieldParent::Strukt(&strukt),
+        ));
+
+        let node: DClass = DClass::with_fields(&base, "DistributedNode", vec![set_name.clone()]);
+
+        let mut avatar: DClass = DClass::with_fields(&base, "DistributedAvatar", vec![set_hp.clone()]);
+        avatar.add_parent(&node);
+
+        let mut toon: DClass = DClass::with_fields(
+            &base,
+            "DistributedToon",
+            vec![set_max_hp.clone(), set_toon_name.clone()],
+        );
+        toon.add_parent(&avatar);
+
+        let mut dcf: DCFile = empty_dcfile();
+        dcf.add_dclass(toon);
+        assert!(dcf.inherited_fields_stale);
+        assert_eq!(dcf.get_dclass(0).unwrap().get_num_inherited_fields(), 0);
+
+        dcf.rebuild_inherited_fields();
+        assert!(!dcf.inherited_fields_stale);
+
+        let toon: &DClass = dcf.get_dclass(0).unwrap();
+        assert_eq!(toon.get_num_fields(), 2);
+        assert_eq!(toon.get_num_inherited_fields(), 3);
+
+        // the redeclared `setName` replaces the grandparent's field in place
+        assert_eq!(toon.get_inherited_field(0), Some(&set_toon_name));
+        assert_ne!(toon.get_inherited_field(0), Some(&set_name));
+        assert_eq!(toon.get_inherited_field(1), Some(&set_hp));
+        assert_eq!(toon.get_inherited_field(2), Some(&set_max_hp));
+        assert!(toon.get_inherited_field(3).is_none());
+    }
+
+    #[test]
+    fn write_dc_python_import() {
+        let import: DCPythonImport = DCPythonImport {
+            module: "views".to_string(),
+            symbols: vec![],
+        };
+
+        assert_eq!(import.to_string(), "import views");
+    }
+
+    #[test]
+    fn clear_and_reuse_dcfile() {
+        let mut dcf: DCFile = empty_dcfile();
+        let empty_hash: globals::DCFileHash = dcf.get_legacy_hash();
+
+        dcf.imports.push(DCPythonImport {
+            module: "views".to_string(),
+            symbols: vec![],
+        });
+        dcf.type_defs.push(DCTypeEnum::TUInt32.into());
+        dcf.baked_legacy_hash = 0xdeadbeef;
+        dcf.all_object_valid = false;
+
+        dcf.clear();
+
+        assert_eq!(dcf.get_num_imports(), 0);
+        assert_eq!(dcf.get_num_dclasses(), 0);
+        assert!(dcf.type_defs.is_empty());
+        assert!(dcf.all_object_valid);
+        assert_eq!(dcf.get_legacy_hash(), empty_hash);
+
+        // the cleared file can be populated again
+        dcf.imports.push(DCPythonImport {
+            module: "views".to_string(),
+            symbols: vec!["DistributedDonut".to_string()],
+        });
+
+        assert_eq!(dcf.get_num_imports(), 1);
+        assert_eq!(
+            dcf.get_python_import(0).to_string(),
+            "from views import DistributedDonut"
+        );
+    }
+
+    #[test]
+    fn count_fields_across_elements() {
+        let dcf: DCFile = empty_dcfile();
+        assert_eq!(dcf.get_num_fields(), 0);
+
+        let dcf: DCFile = crate::read_dc(
+            DCFileConfig::default(),
+            "struct Point { int16 x; int16 y; };
+             dclass DistributedNode { setX(int16 x); setPos(Point pos); setXPos : setX, setPos; };"
+                .into(),
+        )
+        .unwrap();
+
+        // two struct fields, and three class fields
+        assert_eq!(dcf.get_num_fields(), 5);
+    }
+
+    #[test]
+    fn add_fields_with_sequential_ids() {
+        let parent_file: DCFile = empty_dcfile();
+        let strukt: DCStruct = DCStruct::new(&parent_file);
+
+        let mut set_x = DCField::new("setX", DCTypeEnum::TInt16.into(), FieldParent::Strukt(&strukt));
+        let mut set_y = DCField::new("setY", DCTypeEnum::TInt16.into(), FieldParent::Strukt(&strukt));
+        let mut set_z = DCField::new("setZ", DCTypeEnum::TInt16.into(), FieldParent::Strukt(&strukt));
+
+        let mut dcf: DCFile = empty_dcfile();
+
+        assert_eq!(dcf.add_field(&mut set_x), 0);
+        assert_eq!(dcf.add_field(&mut set_y), 1);
+        assert_eq!(dcf.add_field(&mut set_z), 2);
+        assert_eq!(dcf.get_num_fields(), 3);
+
+        for (id, name) in ["setX", "setY", "setZ"].into_iter().enumerate() {
+            let id: globals::FieldId = id.try_into().unwrap();
+            let field: &DCField = dcf.get_field_by_id(id).unwrap();
+
+            assert_eq!(field.get_field_id(), id);
+            assert_eq!(field.get_field_name(), name);
         }
+        assert!(dcf.get_field_by_id(3).is_none());
     }
 
-    /// Returns a string with the hash as a pretty format hexadecimal.
-    pub fn get_pretty_hash(&self) -> String {
-        format!("0x{:0width$x}", self.get_legacy_hash(), width = 8) // 2 hex / byte = 8 hex
-    }
+    #[test]
+    fn assign_field_ids_when_reading() {
+        let dcf: DCFile = crate::read_dc(DCFileConfig::default(), "dclass A { uint8 x; };".into()).unwrap();
 
-    // ---------- Python Imports ---------- //
+        assert_eq!(dcf.get_num_fields(), 1);
+        assert_eq!(dcf.get_field_by_id(0).unwrap().get_field_name(), "x");
+        assert_eq!(
+            dcf.get_dclass_by_name("A")
+                .unwrap()
+                .get_field(0)
+                .unwrap()
+                .get_field_id(),
+            0
+        );
 
-    pub fn get_num_imports(&self) -> usize {
-        self.imports.len()
-    }
+        // fields are numbered in declaration order across structs and dclasses
+        let dcf: DCFile = crate::read_dc(
+            DCFileConfig::default(),
+            "struct S { uint8 a; };
+             dclass A { uint8 x; setY(uint8 y); setZ(uint8 z); setYZ : setY, setZ; };
+             struct T { uint8 b; };
+             dclass B : A { uint8 c; };"
+                .into(),
+        )
+        .unwrap();
+
+        assert_eq!(dcf.get_num_fields(), 7);
+
+        for (id, name) in ["a", "x", "setY", "setZ", "setYZ", "b", "c"]
+            .into_iter()
+            .enumerate()
+        {
+            let id: globals::FieldId = id.try_into().unwrap();
+            let field: &DCField = dcf.get_field_by_id(id).unwrap();
+
+            assert_eq!(field.get_field_id(), id);
+            assert_eq!(field.get_field_name(), name);
+        }
+        assert!(dcf.get_field_by_id(7).is_none());
 
-    pub fn get_python_import(&self, index: usize) -> &DCPythonImport {
-        self.imports.get(index).expect("Index out of bounds.")
+        // inherited fields keep the IDs of the parent's fields
+        let b: &DClass = dcf.get_dclass_by_name("B").unwrap();
+
+        assert_eq!(b.get_field(0).unwrap().get_field_id(), 6);
+        assert_eq!(b.get_inherited_field(0).unwrap().get_field_id(), 1);
     }
 
-    // ---------- DC Keyword ---------- //
+    #[test]
+    fn decode_field_update_message() -> Result<(), IteratorError> {
+        let parent_file: DCFile = empty_dcfile();
+        let strukt: DCStruct = DCStruct::new(&parent_file);
 
-    pub fn get_num_keywords(&self) -> usize {
-        todo!();
-    }
+        let mut field_a = DCField::new("a", DCTypeEnum::TInt16.into(), FieldParent::Strukt(&strukt));
+        let mut field_b = DCField::new("b", DCTypeEnum::TString.into(), FieldParent::Strukt(&strukt));
 
-    pub fn get_keyword(&self, _index: usize) -> &'dc DCKeyword {
-        todo!();
-    }
+        field_a.set_field_id(0);
+        field_b.set_field_id(1);
 
-    pub fn has_keyword(&self, _keyword: String) -> bool {
-        todo!();
-    }
+        let mut dcf: DCFile = empty_dcfile();
+        dcf.field_id_2_field = vec![field_a, field_b];
 
-    // ---------- Distributed Class ---------- //
+        let mut dg: Datagram = Datagram::default();
 
-    pub fn get_num_dclasses(&self) -> usize {
-        self.dclasses.len()
-    }
+        dg.add_doid(1000).unwrap();
+        dg.add_u16(1).unwrap(); // field id
+        dg.add_string("Hello!").unwrap();
+        dg.add_doid(1000).unwrap();
+        dg.add_u16(0).unwrap(); // field id
+        dg.add_i16(-500).unwrap();
 
-    pub fn get_dclass(&self, _index: usize) -> &'dc DClass {
-        todo!();
-    }
+        let mut dgi: DatagramIterator = dg.into();
 
-    pub fn get_dclass_by_id(&self, id: globals::DClassId) -> &'dc DClass {
-        self.dclasses.get(usize::from(id)).unwrap()
-    }
+        assert_eq!(
+            decode_field_update(&mut dgi, &dcf)?,
+            (1000, 1, FieldValue::String("Hello!".to_owned()))
+        );
+        assert_eq!(
+            decode_field_update(&mut dgi, &dcf)?,
+            (1000, 0, FieldValue::Integer(-500))
+        );
+        assert_eq!(dgi.get_remaining(), 0);
 
-    pub fn get_dclass_by_name(&self, _name: &str) -> &'dc DClass {
-        todo!();
-    }
+        // unknown field IDs cannot be decoded
+        let mut dg: Datagram = Datagram::default();
 
-    // ---------- DC Struct ---------- //
+        dg.add_doid(1000).unwrap();
+        dg.add_u16(2).unwrap(); // field id
 
-    pub fn get_num_structs(&self) -> usize {
-        todo!();
+        assert!(decode_field_update(&mut dg.into(), &dcf).is_err());
+        Ok(())
     }
 
-    pub fn get_struct(&self, _index: usize) -> &'dc DCStruct {
-        todo!();
+    #[test]
+    fn build_and_decode_field_update() -> Result<(), IteratorError> {
+        let parent_file: DCFile = empty_dcfile();
+        let strukt: DCStruct = DCStruct::new(&parent_file);
+
+        let mut set_hp = DCField::new("setHp", DCTypeEnum::TInt16.into(), FieldParent::Strukt(&strukt));
+        let mut set_name = DCField::new(
+            "setName",
+            DCTypeEnum::TString.into(),
+            FieldParent::Strukt(&strukt),
+        );
+
+        set_hp.set_field_id(0);
+        set_name.set_field_id(1);
+
+        let mut dcf: DCFile = empty_dcfile();
+        dcf.field_id_2_field = vec![set_hp, set_name];
+
+        let value: FieldValue = FieldValue::String("Flippy".to_owned());
+        let dg: Datagram = build_field_update(&dcf, 1000, "setName", &value).unwrap();
+
+        let mut dgi: DatagramIterator = dg.into();
+
+        assert_eq!(dgi.read_msg_type()?, Protocol::ClientObjectSetField);
+        assert_eq!(decode_field_update(&mut dgi, &dcf)?, (1000, 1, value));
+        assert_eq!(dgi.get_remaining(), 0);
+
+        // values are validated against the field's data type
+        assert_eq!(
+            build_field_update(&dcf, 1000, "setHp", &FieldValue::Integer(40000)).unwrap_err(),
+            DatagramError::FieldConstraintViolation
+        );
+        assert_eq!(
+            build_field_update(&dcf, 1000, "setHp", &FieldValue::String("15".to_owned())).unwrap_err(),
+            DatagramError::FieldConstraintViolation
+        );
+        assert!(build_field_update(&dcf, 1000, "setMaxHp", &FieldValue::Integer(15)).is_err());
+        Ok(())
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    #[test]
+    fn build_field_update_checks_numeric_range() {
+        let dcf: DCFile = crate::read_dc(
+            DCFileConfig::default(),
+            "dclass A { uint8 / 10 (0-10) x; uint16(5-10) y; float64(0.5-1.5) z; float32 f; };".into(),
+        )
+        .unwrap();
+        let update = |name: &str, value: FieldValue| build_field_update(&dcf, 1000, name, &value);
+
+        // values are packed as on the wire, so the range is scaled by the divisor
+        assert!(update("x", FieldValue::UnsignedInteger(100)).is_ok());
+        assert_eq!(
+            update("x", FieldValue::UnsignedInteger(101)).unwrap_err(),
+            DatagramError::FieldConstraintViolation
+        );
+        assert!(update("y", FieldValue::UnsignedInteger(5)).is_ok());
+        assert_eq!(
+            update("y", FieldValue::UnsignedInteger(4)).unwrap_err(),
+            DatagramError::FieldConstraintViolation
+        );
+        assert!(update("z", FieldValue::FloatingPoint(1.5)).is_ok());
+        assert_eq!(
+            update("z", FieldValue::FloatingPoint(1.6)).unwrap_err(),
+            DatagramError::FieldConstraintViolation
+        );
+
+        // a value beyond the range of float32 is not packed as infinity
+        assert!(update("f", FieldValue::FloatingPoint(f64::from(f32::MAX))).is_ok());
+        assert_eq!(
+            update("f", FieldValue::FloatingPoint(1.0e39)).unwrap_err(),
+            DatagramError::FieldConstraintViolation
+        );
+    }
 
     #[test]
-    fn write_dc_python_import() {
-        let import: DCPythonImport = DCPythonImport {
-            module: "views".to_string(),
-            symbols: vec![],
-        };
+    fn pack_field_defaults() -> Result<(), IteratorError> {
+        let parent_file: DCFile = empty_dcfile();
+        let parent: DCStruct = DCStruct::new(&parent_file);
+        let field =
+            |name: &str, dtype: DCTypeEnum| DCField::new(name, dtype.into(), FieldParent::Strukt(&parent));
+
+        let mut health: DCField = field("health", DCTypeEnum::TUInt32);
+        let mut name: DCField = field("name", DCTypeEnum::TString);
+        let speed: DCField = field("speed", DCTypeEnum::TFloat64);
+        let title: DCField = field("title", DCTypeEnum::TString);
+        let grade: DCField = field("grade", DCTypeEnum::TChar);
+        let items: DCField = field("items", DCTypeEnum::TVarArray);
+        let data: DCField = field("data", DCTypeEnum::TVarBlob32);
+
+        // stored defaults are written as they were packed
+        health.set_default_value(vec![100, 0, 0, 0]);
+        name.set_default_value(vec![3, 0, b'M', b'a', b'x']);
+
+        // a struct is packed as the defaults of each of its fields
+        let x: StructField = StructField::Field(field("x", DCTypeEnum::TInt16));
+        let mut y_field: DCField = field("y", DCTypeEnum::TInt16);
+        y_field.set_default_value((-5_i16).to_le_bytes().to_vec());
+        let y: StructField = StructField::Field(y_field);
+
+        let mut point: DCStruct = DCStruct::new(&parent_file);
+        point.add_field(x);
+        point.add_field(y);
+
+        let mut position: DCField = field("position", DCTypeEnum::TStruct);
+        position.set_struct_type(Arc::new(point));
+
+        let mut dg: Datagram = Datagram::default();
+
+        for field in [&health, &name, &speed, &title, &grade, &items, &data, &position] {
+            field.pack_default(&mut dg).unwrap();
+        }
+        let mut dgi: DatagramIterator = dg.into();
+
+        assert_eq!(health.unpack_value(&mut dgi)?, FieldValue::UnsignedInteger(100));
+        assert_eq!(name.unpack_value(&mut dgi)?, FieldValue::String("Max".to_owned()));
+        assert_eq!(speed.unpack_value(&mut dgi)?, FieldValue::FloatingPoint(0.0));
+        assert_eq!(title.unpack_value(&mut dgi)?, FieldValue::String(String::new()));
+        assert_eq!(grade.unpack_value(&mut dgi)?, FieldValue::Char('\0'));
+        assert_eq!(dgi.read_size()?, 0); // empty array
+        assert_eq!(data.unpack_value(&mut dgi)?, FieldValue::Blob(vec![]));
+        assert_eq!(
+            position.unpack_value(&mut dgi)?,
+            FieldValue::Struct(vec![FieldValue::Integer(0), FieldValue::Integer(-5)])
+        );
+        assert_eq!(dgi.get_remaining(), 0);
 
-        assert_eq!(import.to_string(), "import views");
+        // a struct field without its struct type cannot be packed
+        let unresolved: DCField = field("unresolved", DCTypeEnum::TStruct);
+        assert!(unresolved.pack_default(&mut Datagram::default()).is_err());
+        Ok(())
     }
 
     #[test]
@@ -286,17 +1536,19 @@ mod tests {
             },
         ];
 
-        let dcf: DCFile<'_> = DCFile {
+        let dcf: DCFile = DCFile {
             config: DCFileConfig::default(),
             baked_legacy_hash: 0_u32,
             structs: vec![],
             dclasses: vec![],
+            dclass_name_2_index: HashMap::default(),
             imports,
             keywords: vec![],
             type_defs: vec![],
             field_id_2_field: vec![],
             all_object_valid: false,
             inherited_fields_stale: false,
+            sort_hash_by_name: false,
         };
 
         assert_eq!(
@@ -319,11 +1571,15 @@ mod tests {
 /// Contains intermediate DC file structure and logic
 /// for semantic analysis as the DC file is being built.
 pub(crate) mod interim {
-    use super::{ast, globals, DCField, DCFileConfig};
+    use super::{ast, globals, DCField, DCFileConfig, DCTypeDefinition};
+    use crate::datagram::datagram::Datagram;
+    use crate::dcfield::{pack_typed_value, FieldValue};
     use crate::dckeyword::interim::DCKeyword;
     use crate::dclass::interim::DClass;
     use crate::dcstruct::interim::DCStruct;
+    use crate::dctype::DCTypeEnum;
     use crate::parser::error::{Diagnostic, SemanticError};
+    use crate::parser::lexer::Span;
     use crate::parser::pipeline::PipelineData;
     use anyhow::{anyhow, Result};
     use std::collections::HashSet;
@@ -334,14 +1590,30 @@ pub(crate) mod interim {
         pub symbols: Vec<String>,
     }
 
+    /// A struct or dclass declaration, by its index among
+    /// the declarations of its kind in the interim DC file.
+    #[derive(Debug, Clone, Copy)]
+    pub enum TypeDeclaration {
+        Struct(usize),
+        DClass(usize),
+    }
+
     /// DC file structure for internal use by the DC parser.
     #[derive(Debug)]
     pub(crate) struct DCFile {
         pub config: DCFileConfig,
         pub structs: Vec<DCStruct>,
         pub dclasses: Vec<DClass>,
+        /// Structs and dclasses in declaration order, which
+        /// is the order their fields are assigned IDs in.
+        pub type_declarations: Vec<TypeDeclaration>,
         pub imports: Vec<PythonImport>,
         pub keywords: Vec<DCKeyword>,
+        pub type_defs: Vec<DCTypeDefinition>,
+        /// Diagnostics for struct fields of a type that was not declared
+        /// yet, by type name. These are emitted if a struct of that name
+        /// is declared later, as a struct may only refer to earlier ones.
+        pub undeclared_struct_types: Vec<(String, Diagnostic)>,
         //pub field_id_2_field: Vec<Rc<DCField>>,
         // TODO: type_id_2_type, type_name_2_type
         pub all_object_valid: bool,
@@ -354,8 +1626,11 @@ pub(crate) mod interim {
                 config: value,
                 structs: vec![],
                 dclasses: vec![],
+                type_declarations: vec![],
                 imports: vec![],
                 keywords: vec![],
+                type_defs: vec![],
+                undeclared_struct_types: vec![],
                 //field_id_2_field: vec![],
                 all_object_valid: true,
                 inherited_fields_stale: false,
@@ -395,53 +1670,51 @@ pub(crate) mod interim {
         /// into one or more [`PythonImport`] structures, which represent symbol imports
         /// from a python module (with view suffixes applied) and adds them to the DC file.
         pub fn add_python_import(&mut self, pipeline: &mut PipelineData, import: ast::PythonImport) {
-            let mut imports: Vec<PythonImport> = vec![];
-            let mut class_symbols: Vec<String> = vec![import.class.symbol.clone()];
-
             // check view suffixes
             Self::check_view_suffixes(pipeline, &import.module.symbol_views);
-            Self::check_view_suffixes(pipeline, &import.class.symbol_views);
 
-            // Separates "Class/AI/OV" to ["Class", "ClassAI", "ClassOV"]
-            if !import.class.symbol_views.is_empty() {
-                for class_suffix in &import.class.symbol_views {
-                    class_symbols.push(import.class.symbol.clone() + &class_suffix.view);
-                }
+            for class in &import.classes {
+                Self::check_view_suffixes(pipeline, &class.symbol_views);
             }
 
-            // Handles e.g. "from module/AI/OV/UD import DistributedThing/AI/OV/UD"
-            if !import.module.symbol_views.is_empty() {
-                let mut c_symbol: String = class_symbols.first().unwrap().clone();
-
-                imports.push(PythonImport {
-                    module: import.module.symbol.clone(),
-                    symbols: vec![c_symbol],
-                });
-
-                for (i, module_suffix) in import.module.symbol_views.into_iter().enumerate() {
-                    let full_import: String = import.module.symbol.clone() + &module_suffix.view;
-
-                    if (class_symbols.len() - 1) <= i {
-                        c_symbol = class_symbols.last().unwrap().clone();
-                    } else {
-                        c_symbol = class_symbols.get(i + 1).unwrap().clone();
+            // Separates "Class/AI/OV" to ["Class", "ClassAI", "ClassOV"]
+            let class_symbols: Vec<Vec<String>> = import
+                .classes
+                .iter()
+                .map(|class| {
+                    let mut symbols: Vec<String> = vec![class.symbol.clone()];
+
+                    for class_suffix in &class.symbol_views {
+                        symbols.push(class.symbol.clone() + &class_suffix.view);
                     }
+                    symbols
+                })
+                .collect();
 
-                    imports.push(PythonImport {
-                        module: full_import,
-                        symbols: vec![c_symbol],
-                    });
-                }
-            } else {
-                // No view suffixes for the module symbol, so just push the symbol.
-                imports.push(PythonImport {
+            if import.module.symbol_views.is_empty() {
+                // No view suffixes for the module symbol, so just push the symbols.
+                self.imports.push(PythonImport {
                     module: import.module.symbol,
-                    symbols: class_symbols,
+                    symbols: class_symbols.into_iter().flatten().collect(),
                 });
+                return;
             }
 
-            for imp in imports {
-                self.imports.push(imp);
+            // Handles e.g. "from module/AI/OV/UD import DistributedThing/AI/OV/UD",
+            // where each view of the module imports the same view of each class.
+            self.imports.push(PythonImport {
+                module: import.module.symbol.clone(),
+                symbols: class_symbols.iter().map(|symbols| symbols[0].clone()).collect(),
+            });
+
+            for (i, module_suffix) in import.module.symbol_views.iter().enumerate() {
+                self.imports.push(PythonImport {
+                    module: import.module.symbol.clone() + &module_suffix.view,
+                    symbols: class_symbols
+                        .iter()
+                        .map(|symbols| symbols.get(i + 1).unwrap_or(symbols.last().unwrap()).clone())
+                        .collect(),
+                });
             }
         }
 
@@ -466,19 +1739,401 @@ pub(crate) mod interim {
             self.keywords.push(new_kw);
         }
 
-        pub fn add_typedef(&mut self, _name: String) -> Result<(), ()> {
-            todo!();
+        /// Registers a type alias declared with `typedef`. The aliased type
+        /// is resolved first, so a typedef may refer to another typedef.
+        pub fn add_typedef(&mut self, pipeline: &mut PipelineData, typedef: ast::TypeDefinition) {
+            let Some(alias) = typedef.alias_identifier else {
+                return; // nothing to register without an alias identifier
+            };
+
+            if self.get_typedef(&alias).is_some() {
+                let diag: Diagnostic =
+                    Diagnostic::error(typedef.span, pipeline, SemanticError::AlreadyDefined(alias));
+
+                pipeline
+                    .emit_diagnostic(diag.into())
+                    .expect("Failed to emit diagnostic.");
+                return;
+            }
+
+            let Ok(mut type_def) = self.resolve_type(pipeline, typedef.span, typedef.data_type) else {
+                return;
+            };
+
+            if typedef.is_array {
+                type_def = DCTypeDefinition::array_of(type_def);
+            }
+            type_def.set_alias(alias);
+
+            self.type_defs.push(type_def);
+        }
+
+        /// Returns the type definition registered under the given alias, if any.
+        pub fn get_typedef(&self, alias: &str) -> Option<&DCTypeDefinition> {
+            self.type_defs.iter().find(|td| td.get_alias() == Some(alias))
+        }
+
+        /// Resolves the DC type of a field's data type, substituting any
+        /// identifier that names a typedef with its aliased type.
+        ///
+        /// If the identifier does not name a typedef or a struct,
+        /// this function will emit the error diagnostic.
+        ///
+        pub fn resolve_type(
+            &self,
+            pipeline: &mut PipelineData,
+            span: Span,
+            data_type: ast::NonMethodDataType,
+        ) -> Result<DCTypeDefinition> {
+            if let Some(identifier) = type_identifier(&data_type) {
+                if self.get_typedef(identifier).is_none() && self.get_struct(identifier).is_none() {
+                    let diag: Diagnostic =
+                        Diagnostic::error(span, pipeline, SemanticError::UnknownType(identifier.clone()));
+
+                    pipeline
+                        .emit_diagnostic(diag.into())
+                        .expect("Failed to emit diagnostic.");
+
+                    return Err(anyhow!("`{}` does not name a type.", identifier));
+                }
+            }
+            Ok(self.lookup_type(data_type))
+        }
+
+        /// Resolves the DC type of a field's data type in the same way as
+        /// [`Self::resolve_type`], but without checking that an identifier
+        /// names a known type. This is used to build the final DC file,
+        /// once its declarations have been checked.
+        ///
+        /// An array of a typedef, such as `DoId[]`, keeps the aliased
+        /// type as its element type.
+        pub fn lookup_type(&self, data_type: ast::NonMethodDataType) -> DCTypeDefinition {
+            match type_identifier(&data_type).and_then(|id| self.get_typedef(id)) {
+                Some(type_def) => match data_type {
+                    ast::NonMethodDataType::TypeWithArray(twa) => (0..twa.array_dimensions())
+                        .fold(type_def.clone(), |element, _| DCTypeDefinition::array_of(element)),
+                    _ => type_def.clone(),
+                },
+                None => data_type.into(),
+            }
+        }
+
+        /// Packs the default value of a field, such as the `100` in
+        /// `uint32 health = 100;`, as the field's data type. Returns
+        /// `None` if the field does not declare a default value.
+        ///
+        /// If the default value is not of the field's data type, or does
+        /// not fit within it, this function will emit the error diagnostic.
+        ///
+        pub fn pack_default_value(
+            &self,
+            pipeline: &mut PipelineData,
+            param: &ast::Parameter,
+        ) -> Result<Option<Vec<u8>>> {
+            if param.default_value.is_none() {
+                return Ok(None);
+            }
+            // emits the diagnostic if the data type is not known
+            self.resolve_type(pipeline, param.span, param.data_type.clone())?;
+
+            let Some(packed) = self.packed_default_value(param) else {
+                let diag: Diagnostic = Diagnostic::error(param.span, pipeline, SemanticError::InvalidDefault);
+
+                pipeline
+                    .emit_diagnostic(diag.into())
+                    .expect("Failed to emit diagnostic.");
+
+                return Err(anyhow!("Invalid default value for type."));
+            };
+            Ok(Some(packed))
+        }
+
+        /// Packs the default value of a field in the same way as
+        /// [`Self::pack_default_value`], but without emitting diagnostics.
+        /// Returns `None` if the field has no valid default value.
+        pub fn packed_default_value(&self, param: &ast::Parameter) -> Option<Vec<u8>> {
+            let value: &ast::TypeValue = param.default_value.as_ref()?;
+            let mut dg: Datagram = Datagram::default();
+
+            match self.array_element_type(&param.data_type) {
+                Some(array) => pack_array_literal(&mut dg, &array, value)?,
+                None => {
+                    let dtype: DCTypeDefinition = self.lookup_type(param.data_type.clone());
+
+                    pack_literal(&mut dg, &dtype, numeric_divisor(&param.data_type), value)?
+                }
+            }
+            Some(dg.get_data())
+        }
+
+        /// Returns the element type, divisor, and size range of an array
+        /// data type, such as `uint8[2]`, `DoId[]`, or the builtin `uint8array`.
+        /// Returns `None` if the data type is not an array.
+        fn array_element_type(&self, data_type: &ast::NonMethodDataType) -> Option<ArrayElement> {
+            let twa: &ast::TypeWithArray = match data_type {
+                ast::NonMethodDataType::TypeWithArray(twa) => twa,
+                // a typedef of an array type, without its size range
+                _ => {
+                    let type_def: DCTypeDefinition = self.lookup_type(data_type.clone());
+                    return Some((type_def.get_element_type()?.clone(), None, None));
+                }
+            };
+            let bracketed: bool = !twa.array_ranges.is_empty();
+            let range: Option<ast::ArrayRange> = twa.array_ranges.last().cloned();
+
+            let element: DCTypeEnum = match &twa.data_type {
+                // numeric types are only parsed as a `TypeWithArray` when followed by brackets
+                ast::ArrayableType::Numeric(numeric) => {
+                    return Some((numeric.base_type.clone().into(), numeric.divisor, range));
+                }
+                // identifiers are only parsed as a `TypeWithArray` when followed by brackets
+                ast::ArrayableType::Struct(id) => {
+                    let element: DCTypeDefinition =
+                        self.lookup_type(ast::NonMethodDataType::StructType(id.clone()));
+                    return Some((element, None, range));
+                }
+                ast::ArrayableType::Sized(sized) => match sized {
+                    ast::SizedTypeToken::String if bracketed => DCTypeEnum::TVarString,
+                    ast::SizedTypeToken::Blob if bracketed => DCTypeEnum::TVarBlob,
+                    ast::SizedTypeToken::Int8Array => DCTypeEnum::TInt8,
+                    ast::SizedTypeToken::Int16Array => DCTypeEnum::TInt16,
+                    ast::SizedTypeToken::Int32Array => DCTypeEnum::TInt32,
+                    ast::SizedTypeToken::UInt8Array => DCTypeEnum::TUInt8,
+                    ast::SizedTypeToken::UInt16Array => DCTypeEnum::TUInt16,
+                    ast::SizedTypeToken::UInt32Array => DCTypeEnum::TUInt32,
+                    _ => return None,
+                },
+            };
+            Some((element.into(), None, range))
         }
 
-        pub fn add_dclass(&mut self, dclass: DClass) {
+        /// Registers a dclass declaration, assigning it the next dclass ID.
+        ///
+        /// A dclass that redeclares an existing name, inherits from an
+        /// undeclared dclass, or declares an invalid default value for
+        /// one of its fields, is a semantic error and marks this file as
+        /// not valid. This function will emit the error diagnostic.
+        ///
+        pub fn add_dclass(&mut self, pipeline: &mut PipelineData, dclass: ast::DClass) {
+            let mut dclass: DClass = dclass.into();
+
+            if self.dclasses.iter().any(|dc| dc.identifier == dclass.identifier) {
+                let diag: Diagnostic = Diagnostic::error(
+                    dclass.span,
+                    pipeline,
+                    SemanticError::AlreadyDefined(dclass.identifier.clone()),
+                );
+
+                pipeline
+                    .emit_diagnostic(diag.into())
+                    .expect("Failed to emit diagnostic.");
+
+                self.all_object_valid = false;
+                return;
+            }
+
+            for parent in &dclass.parents {
+                if !self.dclasses.iter().any(|dc| dc.identifier == *parent) {
+                    let diag: Diagnostic =
+                        Diagnostic::error(dclass.span, pipeline, SemanticError::NotDefined(parent.clone()));
+
+                    pipeline
+                        .emit_diagnostic(diag.into())
+                        .expect("Failed to emit diagnostic.");
+
+                    self.all_object_valid = false;
+                }
+            }
+
+            // default values are checked here, as the fields
+            // of a dclass are not yet resolved from its declaration.
+            for field in &dclass.fields {
+                if let ast::AtomicOrMolecular::Atomic(atomic) = field {
+                    for param in &atomic.parameters {
+                        if self.pack_default_value(pipeline, param).is_err() {
+                            self.all_object_valid = false;
+                        }
+                    }
+                }
+            }
+
+            let Ok(class_id) = self.get_next_dclass_id(pipeline, &dclass) else {
+                self.all_object_valid = false;
+                return;
+            };
+            dclass.class_id = class_id;
+
+            self.type_declarations
+                .push(TypeDeclaration::DClass(self.dclasses.len()));
             self.dclasses.push(dclass);
         }
 
-        pub fn add_struct(&mut self, _strct: DCStruct) {
-            todo!();
+        /// Registers a struct declaration.
+        ///
+        /// A struct that redeclares an existing struct name, that is
+        /// the type of a field of itself or of a struct declared before it,
+        /// or that declares a method field, is a semantic error and marks
+        /// this file as not valid, as are invalid cases of its switches.
+        /// Fields of other types that are not declared yet are left
+        /// unresolved. This function will emit the error diagnostic.
+        ///
+        pub fn add_struct(&mut self, pipeline: &mut PipelineData, strukt: ast::Struct) {
+            if self.get_struct(&strukt.identifier).is_some() {
+                let diag: Diagnostic = Diagnostic::error(
+                    strukt.span,
+                    pipeline,
+                    SemanticError::AlreadyDefined(strukt.identifier.clone()),
+                );
+
+                pipeline
+                    .emit_diagnostic(diag.into())
+                    .expect("Failed to emit diagnostic.");
+
+                self.all_object_valid = false;
+                return;
+            }
+
+            for field in &strukt.fields {
+                match field {
+                    ast::StructField::ParameterField(pf) => {
+                        self.check_struct_field_type(pipeline, &pf.parameter)
+                    }
+                    ast::StructField::MethodAsField(mf) => self.reject_method_field(pipeline, mf.span),
+                    ast::StructField::Switch(switch) => self.check_switch(pipeline, switch),
+                }
+            }
+            let identifier: String = strukt.identifier.clone();
+
+            self.type_declarations
+                .push(TypeDeclaration::Struct(self.structs.len()));
+            self.structs.push(strukt.into());
+
+            // structs are built in declaration order, so fields that
+            // refer to this struct from itself or earlier structs are errors
+            let (references, undeclared): (Vec<_>, Vec<_>) =
+                std::mem::take(&mut self.undeclared_struct_types)
+                    .into_iter()
+                    .partition(|(type_name, _)| *type_name == identifier);
+
+            self.undeclared_struct_types = undeclared;
+
+            for (_, diag) in references {
+                pipeline
+                    .emit_diagnostic(diag.into())
+                    .expect("Failed to emit diagnostic.");
+
+                self.all_object_valid = false;
+            }
+        }
+
+        /// Keeps the diagnostic for a struct field of a type that is not
+        /// declared yet, to be emitted if a struct of that name is declared.
+        fn check_struct_field_type(&mut self, pipeline: &mut PipelineData, param: &ast::Parameter) {
+            let Some(identifier) = type_identifier(&param.data_type) else {
+                return;
+            };
+            if self.get_typedef(identifier).is_none() && self.get_struct(identifier).is_none() {
+                let diag: Diagnostic = Diagnostic::error(
+                    param.span,
+                    pipeline,
+                    SemanticError::UnknownType(identifier.clone()),
+                );
+
+                self.undeclared_struct_types.push((identifier.clone(), diag));
+            }
+        }
+
+        /// Emits the error diagnostic for a method field declared in a struct.
+        fn reject_method_field(&mut self, pipeline: &mut PipelineData, span: Span) {
+            let diag: Diagnostic = Diagnostic::error(span, pipeline, SemanticError::MethodInStruct);
+
+            pipeline
+                .emit_diagnostic(diag.into())
+                .expect("Failed to emit diagnostic.");
+
+            self.all_object_valid = false;
+        }
+
+        /// Checks the key and cases of a switch declared in a struct.
+        ///
+        /// A key parameter of an unknown type, a case value that is not
+        /// of the key's data type or that is already used by another case,
+        /// or more than one default case, is a semantic error and marks
+        /// this file as not valid. The fields of each case are checked as
+        /// fields of the struct. This function will emit the error diagnostic.
+        ///
+        fn check_switch(&mut self, pipeline: &mut PipelineData, switch: &ast::Switch) {
+            let key: &ast::Parameter = &switch.key_parameter.parameter;
+
+            // case values cannot be checked without the key's data type
+            let key_known: bool = self
+                .resolve_type(pipeline, switch.span, key.data_type.clone())
+                .is_ok();
+
+            if !key_known {
+                self.all_object_valid = false;
+            }
+
+            let mut values: HashSet<Vec<u8>> = HashSet::default();
+            let mut has_default: bool = false;
+
+            for case in &switch.cases {
+                let err: Option<SemanticError> = match &case.condition {
+                    None if has_default => Some(SemanticError::RedundantDefault),
+                    None => {
+                        has_default = true;
+                        None
+                    }
+                    Some(_) if !key_known => None,
+                    Some(condition) => match self.packed_case_value(key, condition) {
+                        None => Some(SemanticError::InvalidCaseValueType),
+                        Some(value) => (!values.insert(value)).then_some(SemanticError::RedundantCase),
+                    },
+                };
+
+                if let Some(err) = err {
+                    let diag: Diagnostic = Diagnostic::error(case.span, pipeline, err);
+
+                    pipeline
+                        .emit_diagnostic(diag.into())
+                        .expect("Failed to emit diagnostic.");
+
+                    self.all_object_valid = false;
+                }
+
+                for field in &case.fields {
+                    match field {
+                        ast::NamedField::ParameterField(pf) => {
+                            self.check_struct_field_type(pipeline, &pf.parameter)
+                        }
+                        ast::NamedField::MethodAsField(mf) => self.reject_method_field(pipeline, mf.span),
+                    }
+                }
+            }
+        }
+
+        /// Packs the value of a switch case, such as the `1` in `case 1:`,
+        /// as the data type of the switch's key parameter. Returns `None`
+        /// if the value is not of the key's data type, or does not fit within it.
+        pub fn packed_case_value(&self, key: &ast::Parameter, value: &ast::TypeValue) -> Option<Vec<u8>> {
+            let mut key: ast::Parameter = key.clone();
+            key.default_value = Some(value.clone());
+
+            self.packed_default_value(&key)
+        }
+
+        /// Returns the index and declaration of the struct
+        /// registered with the given identifier, if any.
+        pub fn get_struct(&self, identifier: &str) -> Option<(usize, &DCStruct)> {
+            self.structs
+                .iter()
+                .enumerate()
+                .find(|(_, strukt)| strukt.identifier == identifier)
         }
 
         /// Gets the next dclass ID based on the current allocated IDs.
+        /// IDs are sequential from zero, so this is the number of
+        /// dclasses already added to this file.
         ///
         /// If an error is returned, this DC file has run out of dclass
         /// IDs to assign. This function will emit the error diagnostic.
@@ -501,7 +2156,127 @@ pub(crate) mod interim {
 
                 return Err(anyhow!("Ran out of 16-bit DClass IDs!"));
             }
-            Ok(dc_num - 1_u16)
+            Ok(dc_num)
+        }
+    }
+
+    /// Returns the identifier of a data type that names
+    /// another type, such as a typedef or a struct.
+    pub(super) fn type_identifier(data_type: &ast::NonMethodDataType) -> Option<&String> {
+        match data_type {
+            ast::NonMethodDataType::StructType(id) => Some(id),
+            ast::NonMethodDataType::TypeWithArray(twa) => match &twa.data_type {
+                ast::ArrayableType::Struct(id) => Some(id),
+                _ => None,
+            },
+            ast::NonMethodDataType::NumericType(_) => None,
+        }
+    }
+
+    /// Returns the divisor transform of a numeric data type, if any.
+    fn numeric_divisor(data_type: &ast::NonMethodDataType) -> Option<f64> {
+        match data_type {
+            ast::NonMethodDataType::NumericType(numeric) => numeric.divisor,
+            _ => None,
+        }
+    }
+
+    /// The element type, divisor, and size range of an array data type.
+    type ArrayElement = (DCTypeDefinition, Option<f64>, Option<ast::ArrayRange>);
+
+    /// Packs an array literal, such as `[1, 2 * 3]`, as an array of the
+    /// given element type. Each element is repeated by its expansion factor.
+    ///
+    /// A fixed-size array, such as `uint8[2]`, must have exactly as many
+    /// elements as its size and is packed without a size tag. Any other
+    /// array must have an element count within its range, if bounded,
+    /// and is prefixed with its byte length.
+    fn pack_array_literal(dg: &mut Datagram, array: &ArrayElement, value: &ast::TypeValue) -> Option<()> {
+        let (element, divisor, range) = array;
+
+        let ast::TypeValue::ArrayValue(expansions) = value else {
+            return None;
+        };
+        let mut elements: Datagram = Datagram::default();
+        let mut count: u64 = 0;
+
+        for (element_value, factor) in expansions {
+            for _ in 0..*factor {
+                pack_literal(&mut elements, element, *divisor, element_value)?;
+            }
+            count += u64::from(*factor);
+        }
+
+        match range {
+            Some(range) if range.start == range.end => {
+                if count as f64 != range.start {
+                    return None;
+                }
+                dg.add_data(elements.get_data()).ok()
+            }
+            Some(range) if (count as f64) < range.start || (count as f64) > range.end => None,
+            _ => dg.add_blob(elements.get_data()).ok(),
+        }
+    }
+
+    /// Packs a literal of a basic data type. Integer literals are scaled by
+    /// the divisor of the data type, if any, as they are packed.
+    fn pack_literal(
+        dg: &mut Datagram,
+        dtype: &DCTypeDefinition,
+        divisor: Option<f64>,
+        value: &ast::TypeValue,
+    ) -> Option<()> {
+        let integer: Option<i64> = match value {
+            ast::TypeValue::I64(i) => Some(*i),
+            ast::TypeValue::String(hex) => hex_digits(hex).and_then(|d| i64::from_str_radix(d, 16).ok()),
+            _ => None,
+        };
+        let scaled: Option<i64> = match divisor {
+            Some(divisor) => integer.map(|i| (i as f64 * divisor).round() as i64),
+            None => integer,
+        };
+
+        let field_value: FieldValue = match (dtype.get_dc_type(), value) {
+            (DCTypeEnum::TInt8 | DCTypeEnum::TInt16 | DCTypeEnum::TInt32 | DCTypeEnum::TInt64, _) => {
+                FieldValue::Integer(scaled?)
+            }
+            (DCTypeEnum::TUInt8 | DCTypeEnum::TUInt16 | DCTypeEnum::TUInt32 | DCTypeEnum::TUInt64, _) => {
+                FieldValue::UnsignedInteger(scaled?.try_into().ok()?)
+            }
+            (DCTypeEnum::TChar, ast::TypeValue::Char(c)) => FieldValue::Char(*c),
+            (DCTypeEnum::TChar, ast::TypeValue::I64(i)) => FieldValue::Char(u8::try_from(*i).ok()?.into()),
+            (DCTypeEnum::TFloat32 | DCTypeEnum::TFloat64, ast::TypeValue::I64(i)) => {
+                FieldValue::FloatingPoint(*i as f64 * divisor.unwrap_or(1.0))
+            }
+            (DCTypeEnum::TString | DCTypeEnum::TVarString, ast::TypeValue::String(s)) => {
+                FieldValue::String(s.clone())
+            }
+            (
+                DCTypeEnum::TBlob | DCTypeEnum::TVarBlob | DCTypeEnum::TBlob32 | DCTypeEnum::TVarBlob32,
+                ast::TypeValue::String(s),
+            ) => FieldValue::Blob(hex_literal(s).unwrap_or_else(|| s.as_bytes().to_vec())),
+            _ => return None,
+        };
+        pack_typed_value(dg, dtype, None, &field_value).ok()
+    }
+
+    /// Returns the digits of a hexadecimal literal, such as `0x00ff`,
+    /// or `None` if the string is not a hexadecimal literal.
+    fn hex_digits(literal: &str) -> Option<&str> {
+        literal.strip_prefix("0x").or(literal.strip_prefix("0X"))
+    }
+
+    /// Decodes a hexadecimal literal into its bytes, two digits per byte.
+    fn hex_literal(literal: &str) -> Option<Vec<u8>> {
+        let digits: &str = hex_digits(literal)?;
+
+        if digits.is_empty() || !digits.len().is_multiple_of(2) {
+            return None;
         }
+        (0..digits.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(digits.get(i..i + 2)?, 16).ok())
+            .collect()
     }
 }
@@ -20,6 +20,7 @@
 //! Representation of arbitrary and historical
 //! keywords as defined in the DC file.
 
+use crate::globals;
 use crate::hashgen::*;
 use multimap::MultiMap;
 
@@ -28,6 +29,39 @@ use multimap::MultiMap;
 /// as 4 bytes in modern 32-bit and 64-bit C/C++ compilers.
 pub type HistoricalFlag = i32;
 
+/// Historical flag bitmasks of the keywords that Panda defines
+/// by default. Any other keyword has all of its flag bits set.
+pub struct DCKeywordFlags;
+
+impl DCKeywordFlags {
+    pub const REQUIRED: HistoricalFlag = 0x0001;
+    pub const BROADCAST: HistoricalFlag = 0x0002;
+    pub const OWNRECV: HistoricalFlag = 0x0004;
+    pub const RAM: HistoricalFlag = 0x0008;
+    pub const DB: HistoricalFlag = 0x0010;
+    pub const CLSEND: HistoricalFlag = 0x0020;
+    pub const CLRECV: HistoricalFlag = 0x0040;
+    pub const OWNSEND: HistoricalFlag = 0x0080;
+    pub const AIRECV: HistoricalFlag = 0x0100;
+    pub const NOT_HISTORICAL: HistoricalFlag = !0;
+
+    /// Returns the historical flag of the keyword with the given name.
+    pub fn from_name(name: &str) -> HistoricalFlag {
+        match name {
+            "required" => Self::REQUIRED,
+            "broadcast" => Self::BROADCAST,
+            "ownrecv" => Self::OWNRECV,
+            "ram" => Self::RAM,
+            "db" => Self::DB,
+            "clsend" => Self::CLSEND,
+            "clrecv" => Self::CLRECV,
+            "ownsend" => Self::OWNSEND,
+            "airecv" => Self::AIRECV,
+            _ => Self::NOT_HISTORICAL,
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct DCKeyword {
     name: String,
@@ -60,6 +94,26 @@ impl LegacyDCHash for DCKeyword {
 }
 
 impl DCKeyword {
+    /// Creates a keyword with the given name, and the historical
+    /// flag of that name, as given by [`DCKeywordFlags::from_name`].
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_owned(),
+            historical_flag: DCKeywordFlags::from_name(name),
+        }
+    }
+
+    /// Returns `true` if this is one of the historical keywords
+    /// that are implicitly defined by Panda and Astron.
+    #[inline]
+    pub fn is_historical(&self) -> bool {
+        Self::is_historical_name(&self.name)
+    }
+
+    fn is_historical_name(name: &str) -> bool {
+        globals::HISTORICAL_DC_KEYWORDS.contains(&name)
+    }
+
     #[inline]
     pub fn get_name(&self) -> String {
         self.name.clone()
@@ -71,8 +125,9 @@ impl DCKeyword {
     }
 }
 
-/// A map of key/value pairs mapping keyword names to DCKeyword struct pointers.
-pub type KeywordName2Keyword<'dc> = MultiMap<String, &'dc DCKeyword>;
+/// A map of key/value pairs mapping keyword names to
+/// the index of the keyword in its [`DCKeywordList`].
+pub type KeywordName2Keyword = MultiMap<String, usize>;
 
 /// Represents the two types of inputs that `DCKeywordList.has_keyword`
 /// accepts for looking up a Keyword. In Panda and Astron, the
@@ -84,14 +139,14 @@ pub enum IdentifyKeyword {
 
 /// This is a list of [`DCKeyword`] structures, which represent
 /// communication keywords that may be set on a particular field.
-#[derive(Debug)]
-pub struct DCKeywordList<'dc> {
-    keywords: Vec<&'dc DCKeyword>,
-    kw_name_2_keyword: KeywordName2Keyword<'dc>,
+#[derive(Debug, Clone)]
+pub struct DCKeywordList {
+    keywords: Vec<DCKeyword>,
+    kw_name_2_keyword: KeywordName2Keyword,
     flags: HistoricalFlag,
 }
 
-impl std::cmp::PartialEq for DCKeywordList<'_> {
+impl std::cmp::PartialEq for DCKeywordList {
     fn eq(&self, other: &Self) -> bool {
         let target_kw_map: KeywordName2Keyword = other._get_keywords_by_name_map();
 
@@ -111,7 +166,17 @@ impl std::cmp::PartialEq for DCKeywordList<'_> {
     }
 }
 
-impl std::fmt::Display for DCKeywordList<'_> {
+impl Default for DCKeywordList {
+    fn default() -> Self {
+        Self {
+            keywords: vec![],
+            kw_name_2_keyword: MultiMap::new(),
+            flags: 0_i32,
+        }
+    }
+}
+
+impl std::fmt::Display for DCKeywordList {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         for (i, kw) in self.keywords.iter().enumerate() {
             // We do not call the fmt::Display impl of [`DCKeyword`] here,
@@ -127,7 +192,7 @@ impl std::fmt::Display for DCKeywordList<'_> {
     }
 }
 
-impl LegacyDCHash for DCKeywordList<'_> {
+impl LegacyDCHash for DCKeywordList {
     fn generate_hash(&self, hashgen: &mut DCHashGenerator) {
         if self.flags != !0 {
             // All of the flags are historical flags only, so add just the flags
@@ -143,7 +208,7 @@ impl LegacyDCHash for DCKeywordList<'_> {
     }
 }
 
-impl<'dc> DCKeywordList<'dc> {
+impl DCKeywordList {
     /// Returns the number of keywords in this keyword list.
     pub fn get_num_keywords(&self) -> usize {
         self.keywords.len()
@@ -156,7 +221,7 @@ impl<'dc> DCKeywordList<'dc> {
             IdentifyKeyword::ByName(kw_id) => self.get_keyword_by_name(kw_id).is_some(),
             IdentifyKeyword::ByStruct(kw_obj) => {
                 for keyword in &self.keywords {
-                    if **keyword == kw_obj {
+                    if *keyword == kw_obj {
                         return true;
                     }
                 }
@@ -165,14 +230,37 @@ impl<'dc> DCKeywordList<'dc> {
         }
     }
 
+    /// Adds a copy of a keyword to this list, mixing its historical flag into
+    /// the flags of this list. Returns `false` if it is already in the list.
+    pub fn add_keyword(&mut self, keyword: &DCKeyword) -> bool {
+        if self.kw_name_2_keyword.contains_key(&keyword.name) {
+            return false;
+        }
+        self.flags |= keyword.historical_flag;
+
+        self.kw_name_2_keyword
+            .insert(keyword.get_name(), self.keywords.len());
+        self.keywords.push(keyword.clone());
+        true
+    }
+
+    /// Returns the bitwise OR of the historical flags of every
+    /// keyword in this list. See [`DCKeywordFlags`].
+    #[inline]
+    pub fn get_flags(&self) -> HistoricalFlag {
+        self.flags
+    }
+
     /// Returns [`DCKeyword`] reference by index, wrapped in an Option.
-    pub fn get_keyword(&self, index: usize) -> Option<&'dc DCKeyword> {
-        self.keywords.get(index).copied()
+    pub fn get_keyword(&self, index: usize) -> Option<&DCKeyword> {
+        self.keywords.get(index)
     }
 
     /// Returns [`DCKeyword`] reference by given name, wrapped in an Option.
-    pub fn get_keyword_by_name(&self, name: String) -> Option<&'dc DCKeyword> {
-        self.kw_name_2_keyword.get(&name).copied()
+    pub fn get_keyword_by_name(&self, name: String) -> Option<&DCKeyword> {
+        self.kw_name_2_keyword
+            .get(&name)
+            .and_then(|index| self.keywords.get(*index))
     }
 
     /// Returns a clone of this object's keyword name map.
@@ -184,7 +272,7 @@ impl<'dc> DCKeywordList<'dc> {
 /// Contains intermediate keyword structures and logic
 /// for semantic analysis as the keyword/lists is being built.
 pub(crate) mod interim {
-    use super::HistoricalFlag;
+    use super::{DCKeywordFlags, HistoricalFlag};
     use crate::parser::ast;
     use crate::parser::lexer::Span;
     use multimap::MultiMap;
@@ -201,17 +289,13 @@ pub(crate) mod interim {
         fn from(value: ast::KeywordDefinition) -> Self {
             Self {
                 span: value.span,
-                name: value.identifier,
-                historical_flag: {
-                    if value.historical {
-                        // Sets the historical flag bitmask to the bitwise complement of 0
-                        // (!0 in Rust, or ~0 in C/C++), as if the keyword were not one
-                        // of the historically defined keywords.
-                        !0
-                    } else {
-                        0
-                    }
+                historical_flag: match value.historical {
+                    true => DCKeywordFlags::from_name(&value.identifier),
+                    // Keywords that are not historical have every flag bit set,
+                    // (~0 in C/C++) so the list hashes each keyword's name.
+                    false => DCKeywordFlags::NOT_HISTORICAL,
                 },
+                name: value.identifier,
             }
         }
     }
@@ -281,3 +365,80 @@ pub(crate) mod interim {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dcfield::{DCField, FieldParent};
+    use crate::dcfile::{interim, DCFile};
+    use crate::dconfig::DCFileConfig;
+    use crate::dcstruct::DCStruct;
+    use crate::dctype::DCTypeEnum;
+    use crate::parser::ast;
+    use crate::parser::lexer::Lexer;
+    use crate::parser::parser::parse;
+
+    fn hash_of(value: &impl LegacyDCHash) -> u32 {
+        let mut hashgen: DCHashGenerator = DCHashGenerator::default();
+
+        value.generate_hash(&mut hashgen);
+        hashgen.get_hash()
+    }
+
+    /// Parses a single dclass with one atomic field and returns its keywords.
+    fn parse_field_keywords(input: &str) -> Vec<DCKeyword> {
+        let root: ast::Root = parse(Lexer::new(input)).unwrap();
+
+        match root.type_declarations.into_iter().next() {
+            Some(ast::TypeDeclaration::DClassType(dclass)) => match dclass.fields.into_iter().next() {
+                Some(ast::AtomicOrMolecular::Atomic(atomic)) => {
+                    atomic.keywords.iter().map(|kw| DCKeyword::new(kw)).collect()
+                }
+                _ => panic!("Expected an atomic field."),
+            },
+            _ => panic!("Expected a dclass declaration."),
+        }
+    }
+
+    #[test]
+    fn field_keyword_flags() {
+        let dcf: DCFile = interim::DCFile::from(DCFileConfig::default()).into();
+        let strukt: DCStruct = DCStruct::new(&dcf);
+
+        let historical = parse_field_keywords("dclass A { setX(int16) required broadcast ram; };");
+        let custom = parse_field_keywords("dclass A { setX(int16) required p2p; };");
+
+        let mut kw_list: DCKeywordList = DCKeywordList::default();
+
+        for keyword in &historical {
+            assert!(kw_list.add_keyword(keyword));
+        }
+        assert!(!kw_list.add_keyword(&historical[0]));
+        assert_eq!(
+            kw_list.get_flags(),
+            DCKeywordFlags::REQUIRED | DCKeywordFlags::BROADCAST | DCKeywordFlags::RAM
+        );
+
+        // historical keywords only hash their flags bitmask
+        let mut hashgen: DCHashGenerator = DCHashGenerator::default();
+        hashgen.add_int(0x000b);
+        assert_eq!(hash_of(&kw_list), hashgen.get_hash());
+
+        let plain = DCField::new("setX", DCTypeEnum::TInt16.into(), FieldParent::Strukt(&strukt));
+        let mut field = DCField::new("setX", DCTypeEnum::TInt16.into(), FieldParent::Strukt(&strukt));
+        field.set_field_keyword_list(kw_list);
+
+        assert!(field.is_required() && field.is_broadcast() && field.is_ram());
+        assert!(!field.is_db() && !field.is_airecv());
+        assert_ne!(hash_of(&field), hash_of(&plain));
+
+        // any other keyword sets every flag bit, so each keyword is hashed
+        let mut custom_list: DCKeywordList = DCKeywordList::default();
+
+        for keyword in &custom {
+            custom_list.add_keyword(keyword);
+        }
+        assert_eq!(custom_list.get_flags(), DCKeywordFlags::NOT_HISTORICAL);
+        assert_ne!(hash_of(&custom_list), hash_of(&DCKeywordList::default()));
+    }
+}
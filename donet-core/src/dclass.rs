@@ -28,28 +28,53 @@ use crate::globals;
 use crate::hashgen::*;
 use multimap::MultiMap;
 
-pub type FieldName2Field<'dc> = MultiMap<String, &'dc ClassField<'dc>>;
-pub type FieldId2Field<'dc> = MultiMap<globals::FieldId, &'dc ClassField<'dc>>;
+/// Maps field names to the index of the field in its class.
+pub type FieldName2Field = MultiMap<String, usize>;
+/// Maps field IDs to the index of the field in its class.
+pub type FieldId2Field = MultiMap<globals::FieldId, usize>;
+
+/// The names of fields that differ between two versions of a
+/// DClass, as returned by [`DClass::diff_fields`].
+#[derive(Debug, Default, PartialEq)]
+pub struct DClassFieldDiff {
+    /// Fields only declared in the newer class.
+    pub added: Vec<String>,
+    /// Fields only declared in the older class.
+    pub removed: Vec<String>,
+    /// Fields declared in both, but with a different declaration.
+    pub changed: Vec<String>,
+}
+
+impl DClassFieldDiff {
+    /// Returns `true` if both classes declare the same fields.
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
 
 /// Represents a Distributed Class defined in the DC file.
 /// Contains a map of DC Fields, as well as atomic and
 /// molecular fields that are declared within the class.
 /// Also stores other properties such as its hierarchy.
 #[derive(Debug, Clone)]
-pub struct DClass<'dc> {
-    dcfile: &'dc DCFile<'dc>,
+pub struct DClass {
+    dc_config: DCFileConfig,
     class_name: String,
     class_id: globals::DClassId,
     is_bogus_class: bool,
-    class_parents: Vec<&'dc DClass<'dc>>,
-    constructor: Option<&'dc DCAtomicField<'dc>>,
-    fields: Vec<&'dc ClassField<'dc>>,
-    inherited_fields: Vec<&'dc ClassField<'dc>>,
-    field_name_2_field: FieldName2Field<'dc>,
-    field_id_2_field: FieldId2Field<'dc>,
+    /// Copies of the parent classes, as they were when added.
+    class_parents: Vec<DClass>,
+    constructor: Option<DCAtomicField>,
+    fields: Vec<ClassField>,
+    inherited_fields: Vec<ClassField>,
+    field_name_2_field: FieldName2Field,
+    field_id_2_field: FieldId2Field,
+    /// Set when fields or parents are added, so the cached hash is recomputed.
+    fields_dirty: bool,
+    baked_hash: globals::DCFileHash,
 }
 
-impl std::fmt::Display for DClass<'_> {
+impl std::fmt::Display for DClass {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "dclass ")?;
         f.write_str(&self.get_name())?;
@@ -69,7 +94,7 @@ impl std::fmt::Display for DClass<'_> {
         self.class_id.fmt(f)?;
         writeln!(f)?;
 
-        if let Some(constructor) = self.constructor {
+        if let Some(constructor) = &self.constructor {
             constructor.fmt(f)?;
         }
 
@@ -84,14 +109,31 @@ impl std::fmt::Display for DClass<'_> {
     }
 }
 
-impl DCFileConfigAccessor for DClass<'_> {
+impl DCFileConfigAccessor for DClass {
     fn get_dc_config(&self) -> &DCFileConfig {
-        self.dcfile.get_dc_config()
+        &self.dc_config
     }
 }
 
-impl LegacyDCHash for DClass<'_> {
+impl LegacyDCHash for DClass {
     fn generate_hash(&self, hashgen: &mut DCHashGenerator) {
+        let fields: Vec<&ClassField> = self.fields.iter().collect();
+
+        self.fold_hash(hashgen, &fields);
+    }
+}
+
+impl DClass {
+    /// Same as [`LegacyDCHash::generate_hash`], but folds in this
+    /// class' fields ordered by name instead of declaration order.
+    pub(crate) fn generate_hash_sorted_by_name(&self, hashgen: &mut DCHashGenerator) {
+        let mut fields: Vec<&ClassField> = self.fields.iter().collect();
+        fields.sort_by_key(|field| field.get_field_name());
+
+        self.fold_hash(hashgen, &fields);
+    }
+
+    fn fold_hash(&self, hashgen: &mut DCHashGenerator, fields: &[&ClassField]) {
         hashgen.add_string(self.get_name());
         hashgen.add_int(self.get_num_parents().try_into().unwrap());
 
@@ -104,9 +146,9 @@ impl LegacyDCHash for DClass<'_> {
                 constructor.generate_hash(hashgen);
             }
         }
-        hashgen.add_int(self.fields.len().try_into().unwrap());
+        hashgen.add_int(fields.len().try_into().unwrap());
 
-        for field in &self.fields {
+        for field in fields {
             match field {
                 ClassField::Field(field) => field.generate_hash(hashgen),
                 ClassField::Atomic(atomic) => atomic.generate_hash(hashgen),
@@ -116,12 +158,87 @@ impl LegacyDCHash for DClass<'_> {
     }
 }
 
-impl<'dc> DClass<'dc> {
-    pub fn get_field_by_name(&self, name: &str) -> Option<&'dc ClassField> {
-        match self.field_name_2_field.get(name) {
-            Some(pointer) => Some(pointer),
-            None => None,
+impl DClass {
+    /// Creates a new DClass with the given name, adding each
+    /// field in the order given. The class ID is assigned later,
+    /// when the class is added to its DC file.
+    pub fn with_fields(dcfile: &DCFile, name: &str, fields: Vec<ClassField>) -> Self {
+        let mut dclass: Self = Self {
+            dc_config: dcfile.get_dc_config().clone(),
+            class_name: name.to_owned(),
+            class_id: 0,
+            is_bogus_class: true,
+            class_parents: vec![],
+            constructor: None,
+            fields: vec![],
+            inherited_fields: vec![],
+            field_name_2_field: FieldName2Field::default(),
+            field_id_2_field: FieldId2Field::default(),
+            fields_dirty: true,
+            baked_hash: 0,
+        };
+
+        for field in fields {
+            dclass.add_field(field);
+        }
+        dclass
+    }
+
+    /// Appends a field to this class, making it no longer a bogus class.
+    pub fn add_field(&mut self, field: ClassField) {
+        self.fields_dirty = true;
+        self.is_bogus_class = false;
+        self.field_name_2_field
+            .insert(field.get_field_name(), self.fields.len());
+        self.fields.push(field);
+    }
+
+    /// Returns the legacy hash of this class alone. The hash is cached,
+    /// and only recomputed after fields or parents have been added.
+    pub fn get_hash(&mut self) -> globals::DCFileHash {
+        if self.fields_dirty {
+            let mut hashgen: DCHashGenerator = DCHashGenerator::default();
+
+            self.generate_hash(&mut hashgen);
+            self.baked_hash = hashgen.get_hash();
+            self.fields_dirty = false;
+        }
+        self.baked_hash
+    }
+
+    /// Compares the fields of this class against a newer version of
+    /// it, such as the same class declared in another DC file.
+    ///
+    /// Fields are matched by name; see [`DCField`]'s `PartialEq`
+    /// implementation for which properties are compared.
+    ///
+    /// [`DCField`]: crate::dcfield::DCField
+    pub fn diff_fields(&self, newer: &DClass) -> DClassFieldDiff {
+        let mut diff: DClassFieldDiff = DClassFieldDiff::default();
+
+        for field in &self.fields {
+            let name: String = field.get_field_name();
+
+            match newer.get_field_by_name(&name) {
+                Some(newer_field) if newer_field != field => diff.changed.push(name),
+                Some(_) => {}
+                None => diff.removed.push(name),
+            }
         }
+        for field in &newer.fields {
+            let name: String = field.get_field_name();
+
+            if self.get_field_by_name(&name).is_none() {
+                diff.added.push(name);
+            }
+        }
+        diff
+    }
+
+    pub fn get_field_by_name(&self, name: &str) -> Option<&ClassField> {
+        self.field_name_2_field
+            .get(name)
+            .and_then(|index| self.fields.get(*index))
     }
 
     #[inline(always)]
@@ -134,15 +251,84 @@ impl<'dc> DClass<'dc> {
         self.class_id
     }
 
+    /// Assigns the class ID, which is done by [`DCFile::add_dclass`].
+    pub(crate) fn set_dclass_id(&mut self, id: globals::DClassId) {
+        self.class_id = id;
+    }
+
+    #[inline(always)]
+    pub fn get_num_fields(&self) -> usize {
+        self.fields.len()
+    }
+
+    /// Returns the field declared at the given index, in declaration order.
+    #[inline(always)]
+    pub fn get_field(&self, index: usize) -> Option<&ClassField> {
+        self.fields.get(index)
+    }
+
+    /// Returns the number of fields this class has after inheritance,
+    /// as of the last call to [`DClass::rebuild_inherited_fields`].
+    #[inline(always)]
+    pub fn get_num_inherited_fields(&self) -> usize {
+        self.inherited_fields.len()
+    }
+
+    /// Returns a field of this class after inheritance, including
+    /// the fields inherited from its parents, which come first.
+    #[inline(always)]
+    pub fn get_inherited_field(&self, index: usize) -> Option<&ClassField> {
+        self.inherited_fields.get(index)
+    }
+
+    /// Flattens the fields of this class and of all its ancestors into
+    /// its list of inherited fields. A field declared with the same
+    /// name as an inherited field replaces it, in the same position.
+    pub fn rebuild_inherited_fields(&mut self) {
+        self.inherited_fields = self.collect_inherited_fields().into_iter().cloned().collect();
+    }
+
+    fn collect_inherited_fields(&self) -> Vec<&ClassField> {
+        let mut fields: Vec<&ClassField> = vec![];
+
+        for parent in &self.class_parents {
+            for field in parent.collect_inherited_fields() {
+                let name: String = field.get_field_name();
+
+                if !fields.iter().any(|f| f.get_field_name() == name) {
+                    fields.push(field);
+                }
+            }
+        }
+        for field in &self.fields {
+            let name: String = field.get_field_name();
+
+            match fields.iter().position(|f| f.get_field_name() == name) {
+                Some(index) => fields[index] = field,
+                None => fields.push(field),
+            }
+        }
+        fields
+    }
+
+    /// Adds a parent class that this class inherits fields from.
+    ///
+    /// A copy of the parent is kept, so fields added to the parent
+    /// afterwards are not inherited. In a DC file, a class is only
+    /// declared after all of its parents.
+    pub fn add_parent(&mut self, parent: &DClass) {
+        self.fields_dirty = true;
+        self.class_parents.push(parent.clone());
+    }
+
     #[inline(always)]
     pub fn get_num_parents(&self) -> usize {
         self.class_parents.len()
     }
 
     #[inline(always)]
-    pub fn get_parent(&self, index: usize) -> Option<&'static DClass> {
-        // copy the reference inside the option instead of a reference to the reference
-        self.class_parents.get(index).cloned()
+    pub fn get_parent(&self, index: usize) -> Option<&DClass> {
+        self.class_parents.get(index)
     }
 
     #[inline(always)]
@@ -151,12 +337,8 @@ impl<'dc> DClass<'dc> {
     }
 
     #[inline(always)]
-    pub fn get_constructor(&self) -> Option<&'dc DCAtomicField> {
-        if let Some(atomic) = &self.constructor {
-            Some(atomic)
-        } else {
-            None
-        }
+    pub fn get_constructor(&self) -> Option<&DCAtomicField> {
+        self.constructor.as_ref()
     }
 }
 
@@ -166,8 +348,6 @@ pub(crate) mod interim {
     use crate::globals;
     use crate::parser::ast;
     use crate::parser::lexer::Span;
-    use std::cell::RefCell;
-    use std::rc::Rc;
 
     #[derive(Debug)]
     pub struct DClass {
@@ -176,23 +356,165 @@ pub(crate) mod interim {
         pub parents: Vec<String>,
         pub fields: ast::ClassFields,
         pub class_id: globals::DClassId,
-        pub is_bogus_class: bool,
-        pub class_parents: Vec<Rc<RefCell<DClass>>>,
     }
 
-    impl DClass {
-        #[inline(always)]
-        pub fn add_parent(&mut self, parent: Rc<RefCell<DClass>>) {
-            self.class_parents.push(parent);
+    impl From<ast::DClass> for DClass {
+        fn from(value: ast::DClass) -> Self {
+            Self {
+                span: value.span,
+                identifier: value.identifier,
+                parents: value.parents,
+                fields: value.fields,
+                class_id: 0,
+            }
         }
+    }
+}
 
-        /// Adds a newly allocated DC field to this class. The field structure
-        /// in memory is moved into ownership of this class structure, and is
-        /// wrapped in a [`std::cell::RefCell`] and an [`std::rc::Rc`] pointer
-        ///to pass references to other elements, such as molecular fields.
-        pub fn add_class_field(&mut self, field: ast::AtomicOrMolecular) {
-            self.is_bogus_class = false;
-            self.fields.push(field);
-        }
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dcfield::{DCField, FieldParent};
+    use crate::dcfile::interim;
+    use crate::dcstruct::DCStruct;
+    use crate::dctype::DCTypeEnum;
+
+    #[test]
+    fn dclass_with_fields() {
+        let dcf: DCFile = interim::DCFile::from(DCFileConfig::default()).into();
+        let strukt: DCStruct = DCStruct::new(&dcf);
+
+        let x: ClassField = ClassField::Field(DCField::new(
+            "setX",
+            DCTypeEnum::TInt16.into(),
+            FieldParent::Strukt(&strukt),
+        ));
+        let y: ClassField = ClassField::Field(DCField::new(
+            "setY",
+            DCTypeEnum::TInt16.into(),
+            FieldParent::Strukt(&strukt),
+        ));
+
+        let dclass: DClass = DClass::with_fields(&dcf, "DistributedNode", vec![x, y]);
+
+        assert_eq!(dclass.get_name(), "DistributedNode");
+        assert_eq!(dclass.get_num_fields(), 2);
+        assert!(dclass.get_field_by_name("setY").is_some());
+        assert!(dclass.get_field_by_name("setZ").is_none());
+    }
+
+    #[test]
+    fn hash_class_with_field_of_another_class() {
+        let dcf: DCFile = interim::DCFile::from(DCFileConfig::default()).into();
+        let node: DClass = DClass::with_fields(&dcf, "DistributedNode", vec![]);
+
+        // A field declared by another class, which hashing reaches
+        // through its parent to read the DC file configuration.
+        let x: ClassField = ClassField::Field(DCField::new(
+            "setX",
+            DCTypeEnum::TInt16.into(),
+            FieldParent::DClass(&node),
+        ));
+        let avatar: DClass = DClass::with_fields(&dcf, "DistributedAvatar", vec![x]);
+
+        let hash = |dclass: &DClass| -> globals::DCFileHash {
+            let mut hashgen: DCHashGenerator = DCHashGenerator::default();
+            dclass.generate_hash(&mut hashgen);
+            hashgen.get_hash()
+        };
+
+        assert_eq!(hash(&avatar), hash(&avatar));
+        assert_ne!(hash(&avatar), hash(&node));
+    }
+
+    #[test]
+    fn rehash_after_adding_field() {
+        let dcf: DCFile = interim::DCFile::from(DCFileConfig::default()).into();
+        let strukt: DCStruct = DCStruct::new(&dcf);
+
+        let x: ClassField = ClassField::Field(DCField::new(
+            "setX",
+            DCTypeEnum::TInt16.into(),
+            FieldParent::Strukt(&strukt),
+        ));
+        let y: ClassField = ClassField::Field(DCField::new(
+            "setY",
+            DCTypeEnum::TInt16.into(),
+            FieldParent::Strukt(&strukt),
+        ));
+        let mut dclass: DClass = DClass::with_fields(&dcf, "DistributedNode", vec![x.clone()]);
+
+        let first: globals::DCFileHash = dclass.get_hash();
+        assert!(!dclass.fields_dirty);
+        assert_eq!(dclass.get_hash(), first);
+
+        dclass.add_field(y.clone());
+        assert!(dclass.fields_dirty);
+
+        let second: globals::DCFileHash = dclass.get_hash();
+        assert_ne!(second, first);
+
+        // the recomputed hash matches a class built with both fields
+        let mut expected: DClass = DClass::with_fields(&dcf, "DistributedNode", vec![x, y]);
+        assert_eq!(second, expected.get_hash());
+    }
+
+    #[test]
+    fn rehash_after_adding_parent() {
+        let dcf: DCFile = interim::DCFile::from(DCFileConfig::default()).into();
+
+        let node: DClass = DClass::with_fields(&dcf, "DistributedNode", vec![]);
+        let mut avatar: DClass = DClass::with_fields(&dcf, "DistributedAvatar", vec![]);
+
+        let first: globals::DCFileHash = avatar.get_hash();
+
+        avatar.add_parent(&node);
+        assert!(avatar.fields_dirty);
+        assert_ne!(avatar.get_hash(), first);
+
+        // the recomputed hash matches a class built with its parent
+        let mut expected: DClass = DClass::with_fields(&dcf, "DistributedAvatar", vec![]);
+        expected.add_parent(&node);
+        assert_eq!(avatar.get_hash(), expected.get_hash());
+    }
+
+    #[test]
+    fn diff_class_fields() {
+        let dcf: DCFile = interim::DCFile::from(DCFileConfig::default()).into();
+        let strukt: DCStruct = DCStruct::new(&dcf);
+
+        let field = |name: &str, dtype: DCTypeEnum| -> ClassField {
+            ClassField::Field(DCField::new(name, dtype.into(), FieldParent::Strukt(&strukt)))
+        };
+        let x: ClassField = field("setX", DCTypeEnum::TInt16);
+        let y: ClassField = field("setY", DCTypeEnum::TInt16);
+        let y_wide: ClassField = field("setY", DCTypeEnum::TInt32);
+        let h: ClassField = field("setH", DCTypeEnum::TInt16);
+
+        assert_eq!(x, field("setX", DCTypeEnum::TInt16));
+        assert_ne!(y, y_wide);
+
+        let old: DClass = DClass::with_fields(&dcf, "DistributedNode", vec![x.clone(), y]);
+        let new: DClass = DClass::with_fields(&dcf, "DistributedNode", vec![x.clone(), y_wide]);
+
+        assert!(old.diff_fields(&old).is_empty());
+        assert_eq!(
+            old.diff_fields(&new),
+            DClassFieldDiff {
+                changed: vec!["setY".to_owned()],
+                ..Default::default()
+            }
+        );
+
+        let new: DClass = DClass::with_fields(&dcf, "DistributedNode", vec![x, h]);
+
+        assert_eq!(
+            old.diff_fields(&new),
+            DClassFieldDiff {
+                added: vec!["setH".to_owned()],
+                removed: vec!["setY".to_owned()],
+                changed: vec![],
+            }
+        );
     }
 }
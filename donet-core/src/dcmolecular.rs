@@ -21,24 +21,39 @@
 //! a form of a field 'alias' for a collection of fields.
 
 use crate::dcatomic::DCAtomicField;
-use crate::dcfield::DCField;
+use crate::dcfield::{DCField, FieldParent};
+use crate::dctype::DCTypeEnum;
+use crate::globals;
 use crate::hashgen::*;
 
 /// An abstract field which provides an interface to access
 /// multiple atomic fields under one field and one identifier.
-#[derive(Debug)]
-pub struct DCMolecularField<'dc> {
-    base_field: DCField<'dc>,
-    atomic_fields: Vec<&'dc DCAtomicField<'dc>>,
+#[derive(Debug, Clone)]
+pub struct DCMolecularField {
+    base_field: DCField,
+    atomic_fields: Vec<DCAtomicField>,
 }
 
-impl std::fmt::Display for DCMolecularField<'_> {
+/// Compares the base field and the names of the atomic fields it joins.
+impl PartialEq for DCMolecularField {
+    fn eq(&self, other: &Self) -> bool {
+        self.base_field == other.base_field
+            && self.atomic_fields.len() == other.atomic_fields.len()
+            && self
+                .atomic_fields
+                .iter()
+                .zip(&other.atomic_fields)
+                .all(|(a, b)| a.get_field_name() == b.get_field_name())
+    }
+}
+
+impl std::fmt::Display for DCMolecularField {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         writeln!(f, "TODO")
     }
 }
 
-impl LegacyDCHash for DCMolecularField<'_> {
+impl LegacyDCHash for DCMolecularField {
     fn generate_hash(&self, hashgen: &mut DCHashGenerator) {
         self.base_field.generate_hash(hashgen);
 
@@ -50,14 +65,38 @@ impl LegacyDCHash for DCMolecularField<'_> {
     }
 }
 
-impl<'dc> DCMolecularField<'dc> {
+impl DCMolecularField {
+    pub fn new(name: &str, parent: FieldParent<'_>, atomic_fields: Vec<DCAtomicField>) -> Self {
+        Self {
+            base_field: DCField::new(name, DCTypeEnum::TMethod.into(), parent),
+            atomic_fields,
+        }
+    }
+
+    #[inline(always)]
+    pub fn get_field_id(&self) -> globals::FieldId {
+        self.base_field.get_field_id()
+    }
+
+    #[inline(always)]
+    pub fn get_field_name(&self) -> String {
+        self.base_field.get_field_name()
+    }
+
+    /// Returns the plain field this molecular field is declared as,
+    /// such as to assign it a field ID when it is added to a DC file.
+    #[inline(always)]
+    pub(crate) fn base_field_mut(&mut self) -> &mut DCField {
+        &mut self.base_field
+    }
+
     #[inline(always)]
     pub fn get_num_atomics(&self) -> usize {
         self.atomic_fields.len()
     }
 
     #[inline(always)]
-    pub fn get_atomic_field(&self, index: usize) -> Option<&'dc DCAtomicField> {
-        self.atomic_fields.get(index).copied()
+    pub fn get_atomic_field(&self, index: usize) -> Option<&DCAtomicField> {
+        self.atomic_fields.get(index)
     }
 }
@@ -29,7 +29,7 @@ use std::mem::{discriminant, size_of};
 /// Numeric Range structs are used to represent a range of signed/unsigned
 /// integers or floating point numbers. Used for enforcing numeric limits
 /// within constraints of array, string, or blob sized types.
-#[derive(Clone)]
+#[derive(Debug, Clone)]
 pub struct DCNumericRange {
     pub min: DCNumber,
     pub max: DCNumber,
@@ -65,7 +65,7 @@ impl From<std::ops::Range<f64>> for DCNumericRange {
 impl DCNumericRange {
     pub fn contains(&self, num: DCNumber) -> bool {
         // Check that `num` is of the same data type as this numeric range.
-        if discriminant(&self.min) == discriminant(&num) {
+        if discriminant(&self.min) != discriminant(&num) {
             return false;
         }
 
@@ -113,6 +113,7 @@ impl DCNumericRange {
     }
 }
 
+#[derive(Debug, Clone)]
 pub struct DCNumericType {
     base_type: DCTypeDefinition,
     divisor: u16,
@@ -222,10 +223,15 @@ impl DCNumericType {
         if divisor == 0 {
             return Err("Cannot set the divisor to 0.".into());
         }
+        let prev_divisor: u16 = self.divisor;
         self.divisor = divisor;
 
         if self.has_range() {
-            self.set_range(self.orig_range.clone().unwrap())?;
+            // keep the previous divisor if the range cannot be scaled by this one
+            if let Err(err) = self.set_range(self.orig_range.clone().unwrap()) {
+                self.divisor = prev_divisor;
+                return Err(err);
+            }
         }
 
         if self.has_modulus() {
@@ -244,18 +250,101 @@ impl DCNumericType {
         Ok(()) // TODO: properly validate modulus range
     }
 
+    /// Sets the range of this numeric type, which is scaled by
+    /// the divisor to the range of values stored on the wire.
+    ///
+    /// Returns an error if a scaled bound does not fit in
+    /// this numeric type's underlying storage.
     pub fn set_range(&mut self, range: DCNumericRange) -> Result<(), String> {
-        self.range = Some(range); // TODO: validate
+        let scaled: DCNumericRange = DCNumericRange {
+            min: self.scale_by_divisor(range.min)?,
+            max: self.scale_by_divisor(range.max)?,
+        };
+        self.orig_range = Some(range);
+        self.range = Some(scaled);
         Ok(())
     }
 
+    /// Multiplies a number by the divisor, checking that the result
+    /// still fits in the storage of this numeric type.
+    fn scale_by_divisor(&self, num: DCNumber) -> Result<DCNumber, String> {
+        let divisor: u16 = self.divisor;
+        let overflow = || {
+            format!(
+                "Range scaled by divisor {} overflows {}.",
+                divisor, self.base_type.data_type
+            )
+        };
+
+        let scaled: DCNumber = match num {
+            DCNumber::Integer(v) => {
+                DCNumber::Integer(v.checked_mul(i64::from(divisor)).ok_or_else(overflow)?)
+            }
+            DCNumber::UnsignedInteger(v) => {
+                DCNumber::UnsignedInteger(v.checked_mul(u64::from(divisor)).ok_or_else(overflow)?)
+            }
+            DCNumber::FloatingPoint(v) => DCNumber::FloatingPoint(v * f64::from(divisor)),
+        };
+
+        let fits: bool = match self.get_storage_bounds() {
+            Some((min, max)) => match scaled {
+                DCNumber::Integer(v) => (min..=max).contains(&i128::from(v)),
+                DCNumber::UnsignedInteger(v) => (min..=max).contains(&i128::from(v)),
+                DCNumber::FloatingPoint(v) => min as f64 <= v && v <= max as f64,
+            },
+            // floating point storage
+            None => match scaled {
+                DCNumber::FloatingPoint(v) if self.base_type.data_type == DCTypeEnum::TFloat32 => {
+                    v.abs() <= f64::from(f32::MAX)
+                }
+                _ => true,
+            },
+        };
+        match fits {
+            true => Ok(scaled),
+            false => Err(overflow()),
+        }
+    }
+
+    /// Returns the inclusive bounds of values that the underlying
+    /// integer type can store, or `None` for floating point types.
+    fn get_storage_bounds(&self) -> Option<(i128, i128)> {
+        match self.base_type.data_type {
+            DCTypeEnum::TInt8 => Some((i8::MIN.into(), i8::MAX.into())),
+            DCTypeEnum::TInt16 => Some((i16::MIN.into(), i16::MAX.into())),
+            DCTypeEnum::TInt32 => Some((i32::MIN.into(), i32::MAX.into())),
+            DCTypeEnum::TInt64 => Some((i64::MIN.into(), i64::MAX.into())),
+            DCTypeEnum::TChar | DCTypeEnum::TUInt8 => Some((0, u8::MAX.into())),
+            DCTypeEnum::TUInt16 => Some((0, u16::MAX.into())),
+            DCTypeEnum::TUInt32 => Some((0, u32::MAX.into())),
+            DCTypeEnum::TUInt64 => Some((0, u64::MAX.into())),
+            _ => None,
+        }
+    }
+
     pub fn set_explicit_cast(&mut self, dtype: DCTypeDefinition) -> Result<(), String> {
         self.explicit_cast = Some(dtype);
         Ok(()) // TODO: do some sort of type check
     }
 
-    pub fn within_range(&self, _data: Vec<u8>, _length: u64) -> Result<(), String> {
-        todo!();
+    /// Checks that the packed data of a value of this numeric type
+    /// is within its range. The range is scaled by the divisor, as
+    /// the packed value is.
+    pub fn within_range(&self, data: Vec<u8>, _length: u64) -> Result<(), String> {
+        let (valid, num) = self.data_to_number(data).map_err(|err| err.to_string())?;
+
+        if !valid {
+            return Err(format!(
+                "Packed data is not a valid {}.",
+                self.base_type.data_type
+            ));
+        }
+        match &self.range {
+            Some(range) if !range.contains(num) => {
+                Err(format!("Value is out of range for {}.", self.base_type.data_type))
+            }
+            _ => Ok(()),
+        }
     }
 
     fn data_to_number(&self, data: Vec<u8>) -> Result<(bool, DCNumber), IteratorError> {
@@ -285,3 +374,53 @@ impl DCNumericType {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn divisor_scaling_overflow() {
+        let mut uint8: DCNumericType = DCTypeEnum::TUInt8.into();
+
+        // uint8(0-100) / 2 scales to 0-200, which fits.
+        uint8.set_range((0_u64..100_u64).into()).unwrap();
+        assert!(uint8.set_divisor(2).is_ok());
+
+        // uint8(0-100) / 1000 scales to 0-100000, which does not.
+        assert!(uint8.set_divisor(1000).is_err());
+        assert_eq!(uint8.get_divisor(), 2);
+
+        let mut uint8: DCNumericType = DCTypeEnum::TUInt8.into();
+
+        uint8.set_divisor(1000).unwrap();
+        assert!(uint8.set_range((0_u64..1_u64).into()).is_err());
+        assert!(!uint8.has_range());
+
+        // signed bounds are checked in both directions
+        let mut int16: DCNumericType = DCTypeEnum::TInt16.into();
+
+        int16.set_divisor(100).unwrap();
+        assert!(int16.set_range((-327_i64..327_i64).into()).is_ok());
+        assert!(int16.set_range((-328_i64..0_i64).into()).is_err());
+
+        let mut float32: DCNumericType = DCTypeEnum::TFloat32.into();
+
+        float32.set_divisor(1000).unwrap();
+        assert!(float32.set_range((-1.0e30_f64..1.0e30_f64).into()).is_ok());
+        assert!(float32.set_range((0.0_f64..f64::from(f32::MAX)).into()).is_err());
+    }
+
+    #[test]
+    fn numeric_type_has_range() {
+        let mut int32: DCNumericType = DCTypeEnum::TInt32.into();
+
+        assert!(!int32.has_range());
+        assert!(int32.get_range().is_none());
+
+        int32.set_range((-10_i64..10_i64).into()).unwrap();
+
+        assert!(int32.has_range());
+        assert!(int32.get_range().is_some());
+    }
+}
@@ -20,37 +20,63 @@
 //! Data model that represents a single parameter of an atomic
 //! field, which together form a RPC method signature.
 
-use crate::dcatomic::DCAtomicField;
 use crate::dctype::DCTypeDefinition;
 use crate::hashgen::*;
+use crate::parser::ast;
 
 /// Represents the type specification of a parameter within an atomic field.
-#[derive(Debug)]
-pub struct DCParameter<'dc> {
-    parent: &'dc DCAtomicField<'dc>,
+#[derive(Debug, Clone)]
+pub struct DCParameter {
     base_type: DCTypeDefinition,
     identifier: Option<String>,
-    type_alias: String,
     default_value: Vec<u8>,
     has_default_value: bool,
 }
 
-impl std::fmt::Display for DCParameter<'_> {
+impl From<ast::Parameter> for DCParameter {
+    fn from(value: ast::Parameter) -> Self {
+        Self::new(value.data_type.into(), value.identifier.as_deref())
+    }
+}
+
+impl std::fmt::Display for DCParameter {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        writeln!(f, "TODO")
+        match self.base_type.get_alias() {
+            Some(alias) => f.write_str(alias)?,
+            None => self.base_type.data_type.fmt(f)?,
+        }
+        if let Some(identifier) = &self.identifier {
+            write!(f, " ")?;
+            f.write_str(identifier)?;
+        }
+        Ok(())
     }
 }
 
-impl LegacyDCHash for DCParameter<'_> {
+impl LegacyDCHash for DCParameter {
     fn generate_hash(&self, hashgen: &mut DCHashGenerator) {
         self.base_type.generate_hash(hashgen);
     }
 }
 
-impl<'dc> DCParameter<'dc> {
+impl DCParameter {
+    pub fn new(dtype: DCTypeDefinition, identifier: Option<&str>) -> Self {
+        Self {
+            base_type: dtype,
+            identifier: identifier.map(str::to_owned),
+            default_value: vec![],
+            has_default_value: false,
+        }
+    }
+
+    #[inline(always)]
+    pub fn get_type(&self) -> &DCTypeDefinition {
+        &self.base_type
+    }
+
     #[inline(always)]
-    pub fn get_atomic_field(&self) -> &'dc DCAtomicField {
-        self.parent
+    pub fn get_identifier(&self) -> Option<&str> {
+        self.identifier.as_deref()
     }
 
     #[inline(always)]
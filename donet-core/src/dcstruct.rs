@@ -19,28 +19,55 @@
 
 //! Data model representing a DC Struct element. [NEEDS WORK]
 
+use crate::dcfield::StructField;
 use crate::dcfile::DCFile;
 use crate::dconfig::*;
 use crate::hashgen::*;
 
 #[derive(Debug, Clone)]
-pub struct DCStruct<'dc> {
-    dcfile: &'dc DCFile<'dc>,
+pub struct DCStruct {
+    dc_config: DCFileConfig,
+    fields: Vec<StructField>,
 }
 
-impl std::fmt::Display for DCStruct<'_> {
+impl std::fmt::Display for DCStruct {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         writeln!(f, "TODO")
     }
 }
 
-impl DCFileConfigAccessor for DCStruct<'_> {
+impl DCStruct {
+    /// Creates an empty struct, with the configuration of the given DC file.
+    pub fn new(dcfile: &DCFile) -> Self {
+        Self {
+            dc_config: dcfile.get_dc_config().clone(),
+            fields: vec![],
+        }
+    }
+
+    /// Appends a field to this struct, in declaration order.
+    pub fn add_field(&mut self, field: StructField) {
+        self.fields.push(field);
+    }
+
+    #[inline(always)]
+    pub fn get_num_fields(&self) -> usize {
+        self.fields.len()
+    }
+
+    #[inline(always)]
+    pub fn get_field(&self, index: usize) -> Option<&StructField> {
+        self.fields.get(index)
+    }
+}
+
+impl DCFileConfigAccessor for DCStruct {
     fn get_dc_config(&self) -> &DCFileConfig {
-        self.dcfile.get_dc_config()
+        &self.dc_config
     }
 }
 
-impl LegacyDCHash for DCStruct<'_> {
+impl LegacyDCHash for DCStruct {
     fn generate_hash(&self, _: &mut DCHashGenerator) {
         // TODO
     }
@@ -49,6 +76,20 @@ impl LegacyDCHash for DCStruct<'_> {
 /// Contains intermediate DC struct element structure and logic
 /// for semantic analysis as the DC struct is being built.
 pub(crate) mod interim {
+    use crate::parser::ast;
+
     #[derive(Debug)]
-    pub struct DCStruct {}
+    pub struct DCStruct {
+        pub identifier: String,
+        pub fields: Vec<ast::StructField>,
+    }
+
+    impl From<ast::Struct> for DCStruct {
+        fn from(value: ast::Struct) -> Self {
+            Self {
+                identifier: value.identifier,
+                fields: value.fields,
+            }
+        }
+    }
 }
@@ -24,9 +24,8 @@ use crate::hashgen::*;
 use std::collections::HashMap;
 
 /// Represents a case in a DC switch declaration.
-#[derive(Debug)]
-pub struct SwitchCase<'dc> {
-    switch: &'dc DCSwitch<'dc>,
+#[derive(Debug, Clone)]
+pub struct SwitchCase {
     /// Note that in the legacy DC language, switch cases
     /// always assume to break, no matter if a break
     /// statement was parsed at syntax analysis. This
@@ -34,33 +33,13 @@ pub struct SwitchCase<'dc> {
     breaks: bool,
     /// Empty byte array signifies default case.
     value: Vec<u8>,
-    fields: Vec<DCField<'dc>>,
+    fields: Vec<DCField>,
 }
 
-impl std::fmt::Display for SwitchCase<'_> {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        if self.is_default() {
-            writeln!(f, "default:")?;
-        } else {
-            write!(f, "case ")?;
-            self.switch.key.format_packed_data(f, &self.value, false)?;
-            writeln!(f, ":")?;
-        }
-
-        for field in &self.fields {
-            f.write_str(&field.to_string())?;
-        }
-        if self.breaks {
-            writeln!(f, "break;")?;
-        }
-        Ok(())
-    }
-}
-
-impl LegacyDCHash for SwitchCase<'_> {
+impl LegacyDCHash for SwitchCase {
     fn generate_hash(&self, hashgen: &mut DCHashGenerator) {
         if !self.is_default() {
-            hashgen.add_blob(self.value.clone());
+            hashgen.add_blob(&self.value);
         }
 
         hashgen.add_int(self.get_num_fields() as i32);
@@ -71,53 +50,66 @@ impl LegacyDCHash for SwitchCase<'_> {
     }
 }
 
-impl<'dc> SwitchCase<'dc> {
+impl SwitchCase {
     /// Returns true if this case is a default case.
     pub fn is_default(&self) -> bool {
         self.value.is_empty()
     }
 
+    /// Returns true if this case ends with a break statement,
+    /// instead of falling through to the next case.
+    #[inline(always)]
+    pub fn breaks(&self) -> bool {
+        self.breaks
+    }
+
     /// Returns the number of fields in the case.
     pub fn get_num_fields(&self) -> usize {
         self.fields.len()
     }
 
-    pub fn get_field(&self, index: usize) -> Option<&'dc DCField> {
+    pub fn get_field(&self, index: usize) -> Option<&DCField> {
         self.fields.get(index)
     }
 
-    pub fn get_field_by_name(&self, _name: String) -> Option<&'dc DCField> {
+    pub fn get_field_by_name(&self, _name: String) -> Option<&DCField> {
         todo!()
     }
+
+    /// Formats this case, using the key parameter of
+    /// its switch to format the value of the case.
+    fn fmt_case(&self, f: &mut std::fmt::Formatter<'_>, key: &DCField) -> std::fmt::Result {
+        if self.is_default() {
+            writeln!(f, "default:")?;
+        } else {
+            write!(f, "case ")?;
+            key.format_packed_data(f, &self.value, false)?;
+            writeln!(f, ":")?;
+        }
+
+        for field in &self.fields {
+            f.write_str(&field.to_string())?;
+        }
+        if self.breaks {
+            writeln!(f, "break;")?;
+        }
+        Ok(())
+    }
 }
 
 /// Represents a DC Switch statement, which can appear inside
-/// a dclass declaration and represents two or more alternative
+/// a struct declaration and represents two or more alternative
 /// unpacking schemes based on the first field read.
-#[derive(Debug)]
-pub struct DCSwitch<'dc> {
+#[derive(Debug, Clone)]
+pub struct DCSwitch {
     name: Option<String>,
-    key: DCField<'dc>,
-    cases: Vec<SwitchCase<'dc>>,
-    default_case: Option<SwitchCase<'dc>>,
-    case_fields: Vec<&'dc DCField<'dc>>,
+    key: DCField,
+    cases: Vec<SwitchCase>,
+    default_case: Option<SwitchCase>,
     cases_by_value: HashMap<Vec<u8>, usize>,
 }
 
-impl From<interim::DCSwitch> for DCSwitch<'_> {
-    fn from(value: interim::DCSwitch) -> Self {
-        Self {
-            name: value.name,
-            key: todo!(),
-            cases: vec![],
-            default_case: None,
-            case_fields: vec![],
-            cases_by_value: HashMap::default(),
-        }
-    }
-}
-
-impl std::fmt::Display for DCSwitch<'_> {
+impl std::fmt::Display for DCSwitch {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "switch")?;
 
@@ -129,14 +121,14 @@ impl std::fmt::Display for DCSwitch<'_> {
 
         writeln!(f, ") {{")?;
 
-        for case in &self.cases {
-            f.write_str(&case.to_string())?;
+        for case in self.cases.iter().chain(&self.default_case) {
+            case.fmt_case(f, &self.key)?;
         }
         writeln!(f, "}};")
     }
 }
 
-impl LegacyDCHash for DCSwitch<'_> {
+impl LegacyDCHash for DCSwitch {
     fn generate_hash(&self, hashgen: &mut DCHashGenerator) {
         if let Some(name) = self.get_name() {
             hashgen.add_string(name)
@@ -156,7 +148,19 @@ impl LegacyDCHash for DCSwitch<'_> {
     }
 }
 
-impl<'dc> DCSwitch<'dc> {
+impl DCSwitch {
+    /// Creates a switch without any cases, which chooses
+    /// a case by the value of the given key parameter.
+    pub fn new(name: Option<&str>, key: DCField) -> Self {
+        Self {
+            name: name.map(str::to_owned),
+            key,
+            cases: vec![],
+            default_case: None,
+            cases_by_value: HashMap::default(),
+        }
+    }
+
     /// Returns the optional identifier for this switch.
     #[inline(always)]
     pub fn get_name(&self) -> Option<String> {
@@ -168,7 +172,7 @@ impl<'dc> DCSwitch<'dc> {
     /// The value of this parameter in the record determines which
     /// one of the several cases within the switch will be used.
     #[inline(always)]
-    pub fn get_key_parameter(&self) -> &'dc DCField {
+    pub fn get_key_parameter(&self) -> &DCField {
         &self.key
     }
 
@@ -181,14 +185,14 @@ impl<'dc> DCSwitch<'dc> {
     }
 
     /// Returns case reference from given index wrapped in an Option.
-    pub fn get_case(&self, index: usize) -> Option<&'dc SwitchCase> {
+    pub fn get_case(&self, index: usize) -> Option<&SwitchCase> {
         self.cases.get(index)
     }
 
     /// Returns default case reference wrapped in an Option.
     ///
     /// A default case is optional, so `None` can be returned.
-    pub fn get_default_case(&self) -> Option<&'dc SwitchCase> {
+    pub fn get_default_case(&self) -> Option<&SwitchCase> {
         self.default_case.as_ref()
     }
 
@@ -199,30 +203,37 @@ impl<'dc> DCSwitch<'dc> {
         self.cases_by_value.get(&value).copied()
     }
 
-    // TODO
-    pub fn apply_switch(&self, _value: Vec<u8>, _length: usize) {}
-}
-
-/// Contains intermediate DC Switch structure and logic
-/// for semantic analysis as the DC Switch is being built.
-pub(crate) mod interim {
-    #[derive(Debug)]
-    pub struct SwitchCase {
-        pub breaks: bool,
-        pub value: Vec<u8>,
-    }
-
-    impl SwitchCase {
-        fn add_case_field(&mut self) {}
-    }
-
-    #[derive(Debug)]
-    pub struct DCSwitch {
-        pub name: Option<String>,
-        pub key_parameter: u8, // TODO
+    /// Adds a case for the given packed value of the key parameter,
+    /// with the fields declared under it. Returns false, without
+    /// adding the case, if the switch already has a case for the value.
+    pub fn add_case(&mut self, value: Vec<u8>, fields: Vec<DCField>, breaks: bool) -> bool {
+        if value.is_empty() || self.cases_by_value.contains_key(&value) {
+            return false;
+        }
+        self.cases_by_value.insert(value.clone(), self.cases.len());
+        self.cases.push(SwitchCase {
+            breaks,
+            value,
+            fields,
+        });
+        true
+    }
+
+    /// Sets the default case of the switch, with the fields declared
+    /// under it. Returns false, without setting the default case,
+    /// if the switch already has one.
+    pub fn add_default_case(&mut self, fields: Vec<DCField>, breaks: bool) -> bool {
+        if self.default_case.is_some() {
+            return false;
+        }
+        self.default_case = Some(SwitchCase {
+            breaks,
+            value: vec![],
+            fields,
+        });
+        true
     }
 
-    impl DCSwitch {
-        fn add_case(&mut self) {}
-    }
+    // TODO
+    pub fn apply_switch(&self, _value: Vec<u8>, _length: usize) {}
 }
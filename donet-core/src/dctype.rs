@@ -22,6 +22,7 @@
 
 use crate::globals::DgSizeTag;
 use crate::hashgen::*;
+use crate::parser::ast;
 
 /// The DCTypeEnum variants have assigned u8 values
 /// to keep compatibility with Astron's DC hash inputs.
@@ -76,6 +77,8 @@ impl std::fmt::Display for DCTypeEnum {
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct DCTypeDefinition {
     alias: Option<String>,
+    /// Type of the elements of an array type, such as the `DoId` of `DoId[]`.
+    element_type: Option<Box<DCTypeDefinition>>,
     pub data_type: DCTypeEnum,
     pub size: DgSizeTag,
 }
@@ -85,19 +88,46 @@ impl From<DCTypeEnum> for DCTypeDefinition {
     fn from(value: DCTypeEnum) -> Self {
         Self {
             alias: None,
+            element_type: None,
             data_type: value,
             size: 0_u16,
         }
     }
 }
 
+/// Resolves the DC type of a parsed (non-method) data type.
+impl From<ast::NonMethodDataType> for DCTypeDefinition {
+    fn from(value: ast::NonMethodDataType) -> Self {
+        match value {
+            ast::NonMethodDataType::NumericType(numeric) => numeric.base_type.into(),
+            ast::NonMethodDataType::StructType(_) => DCTypeEnum::TStruct.into(),
+            ast::NonMethodDataType::TypeWithArray(twa) => {
+                let dimensions: usize = twa.array_dimensions();
+
+                let element: Self = match twa.data_type {
+                    ast::ArrayableType::Numeric(numeric) => numeric.base_type.into(),
+                    ast::ArrayableType::Struct(_) => DCTypeEnum::TStruct.into(),
+                    ast::ArrayableType::Sized(sized) => match sized {
+                        ast::SizedTypeToken::String => DCTypeEnum::TString.into(),
+                        ast::SizedTypeToken::Blob => DCTypeEnum::TBlob.into(),
+                        ast::SizedTypeToken::Blob32 => DCTypeEnum::TBlob32.into(),
+                        // The remaining sized types are all builtin array types.
+                        _ => DCTypeEnum::TVarArray.into(),
+                    },
+                };
+                (0..dimensions).fold(element, |element, _| Self::array_of(element))
+            }
+        }
+    }
+}
+
 impl std::fmt::Display for DCTypeDefinition {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "typedef ")?;
         self.data_type.fmt(f)?;
-        if self.has_alias() {
+        if let Some(alias) = self.get_alias() {
             write!(f, " ")?;
-            self.alias.clone().unwrap().fmt(f)?;
+            f.write_str(alias)?;
         }
         write!(f, ";")?;
         writeln!(f)
@@ -108,13 +138,21 @@ impl LegacyDCHash for DCTypeDefinition {
     fn generate_hash(&self, hashgen: &mut DCHashGenerator) {
         hashgen.add_int(i32::from(self.data_type.clone() as u8));
 
-        if self.alias.is_some() {
-            hashgen.add_string(self.alias.clone().unwrap())
+        if let Some(alias) = self.get_alias() {
+            hashgen.add_string(alias.to_owned())
         }
     }
 }
 
 impl DCTypeDefinition {
+    /// Creates a variable length array type of the given element type.
+    pub fn array_of(element: DCTypeDefinition) -> Self {
+        Self {
+            element_type: Some(Box::new(element)),
+            ..DCTypeEnum::TVarArray.into()
+        }
+    }
+
     pub fn get_dc_type(&self) -> DCTypeEnum {
         self.data_type.clone()
     }
@@ -134,20 +172,23 @@ impl DCTypeDefinition {
         self.alias.is_some()
     }
 
-    pub fn get_alias(&self) -> Result<String, ()> {
-        if self.alias.is_some() {
-            Ok(self.alias.clone().unwrap())
-        } else {
-            Err(())
-        }
+    #[inline(always)]
+    pub fn get_alias(&self) -> Option<&str> {
+        self.alias.as_deref()
     }
 
     pub fn set_alias(&mut self, alias: String) {
         self.alias = Some(alias);
     }
+
+    /// Returns the element type of an array type, if known.
+    #[inline(always)]
+    pub fn get_element_type(&self) -> Option<&DCTypeDefinition> {
+        self.element_type.as_deref()
+    }
 }
 
-#[derive(Copy, Clone, PartialEq)] // required for unwrapping when in an option type
+#[derive(Debug, Copy, Clone, PartialEq)] // required for unwrapping when in an option type
 pub enum DCNumber {
     Integer(i64),
     UnsignedInteger(u64),
@@ -199,3 +240,23 @@ impl From<DCNumber> for f64 {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn type_definition_alias() {
+        let mut type_def: DCTypeDefinition = DCTypeEnum::TUInt32.into();
+
+        assert!(!type_def.has_alias());
+        assert_eq!(type_def.get_alias(), None);
+        assert_eq!(type_def.to_string(), "typedef uint32;\n");
+
+        type_def.set_alias("doId".to_string());
+
+        assert!(type_def.has_alias());
+        assert_eq!(type_def.get_alias(), Some("doId"));
+        assert_eq!(type_def.to_string(), "typedef uint32 doId;\n");
+    }
+}
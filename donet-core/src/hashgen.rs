@@ -93,22 +93,25 @@ impl DCHashGenerator {
     pub fn add_int(&mut self, number: i32) {
         assert!(self.index < MAX_PRIME_NUMBERS);
 
-        self.hash += i32::from(self.primes.get_prime(self.index)) * number;
+        // Panda's C++ hash silently wraps around on overflow; do the same.
+        let prime: i32 = i32::from(self.primes.get_prime(self.index));
+        self.hash = self.hash.wrapping_add(prime.wrapping_mul(number));
         self.index = (self.index + 1) % MAX_PRIME_NUMBERS;
     }
 
     /// Adds a blob to the hash, by breaking it down into a sequence of integers.
-    pub fn add_blob(&mut self, blob: Vec<u8>) {
-        self.add_int(blob.len().try_into().unwrap());
+    /// The length of the blob is folded in first, followed by each byte.
+    pub fn add_blob(&mut self, data: &[u8]) {
+        self.add_int(data.len().try_into().unwrap());
 
-        for byte in blob.into_iter() {
-            self.add_int(i32::from(byte));
+        for byte in data {
+            self.add_int(i32::from(*byte));
         }
     }
 
     /// Adds a string to the hash, by breaking it down into a sequence of integers.
     pub fn add_string(&mut self, string: String) {
-        self.add_blob(string.into_bytes());
+        self.add_blob(string.as_bytes());
     }
 
     pub const fn get_hash(&self) -> DCFileHash {
@@ -118,7 +121,7 @@ impl DCHashGenerator {
 
 #[cfg(test)]
 mod tests {
-    use super::PrimeNumberGenerator;
+    use super::{DCHashGenerator, PrimeNumberGenerator};
 
     #[test]
     fn prime_number_generator_integrity() {
@@ -139,4 +142,33 @@ mod tests {
             assert_eq!(target_prime, generator.get_prime(i.try_into().unwrap()));
         }
     }
+
+    #[test]
+    fn blob_hashing() {
+        let hash_blob = |data: &[u8]| -> u32 {
+            let mut hashgen: DCHashGenerator = DCHashGenerator::default();
+            hashgen.add_blob(data);
+            hashgen.get_hash()
+        };
+
+        assert_eq!(
+            hash_blob(&[0xde, 0xad, 0xbe, 0xef]),
+            hash_blob(&[0xde, 0xad, 0xbe, 0xef])
+        );
+        assert_ne!(
+            hash_blob(&[0xde, 0xad, 0xbe, 0xef]),
+            hash_blob(&[0xde, 0xad, 0xbe, 0xee])
+        );
+        // The length prefix tells apart blobs whose bytes sum up the same.
+        assert_ne!(hash_blob(&[]), hash_blob(&[0x00]));
+
+        // Same as folding in the length, then each byte as an integer.
+        let mut hashgen: DCHashGenerator = DCHashGenerator::default();
+        hashgen.add_int(2);
+        hashgen.add_int(0x02);
+        hashgen.add_int(0xff);
+
+        assert_eq!(hash_blob(&[0x02, 0xff]), hashgen.get_hash());
+        assert_eq!(hash_blob(&[0x02, 0xff]), 2 * 2 + 3 * 0x02 + 5 * 0xff);
+    }
 }
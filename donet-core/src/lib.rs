@@ -123,10 +123,7 @@ fn init_logger() {
 /// the DC files, instantiating the DC parsing pipeline, and either
 /// returns the DCFile object or a Parse/File error.
 #[cfg(feature = "dcfile")]
-pub fn read_dc_files<'a>(
-    config: dconfig::DCFileConfig,
-    file_paths: Vec<String>,
-) -> Result<DCFile<'a>, DCReadError> {
+pub fn read_dc_files(config: dconfig::DCFileConfig, file_paths: Vec<String>) -> Result<DCFile, DCReadError> {
     use log::{info, warn};
     use parser::InputFile;
     use std::fs::File;
@@ -255,7 +252,7 @@ pub fn read_dc_files<'a>(
 /// <br><img src="https://c.tenor.com/myQHgyWQQ9sAAAAd/tenor.gif">
 ///
 #[cfg(feature = "dcfile")]
-pub fn read_dc<'a>(config: dconfig::DCFileConfig, input: String) -> Result<DCFile<'a>, DCReadError> {
+pub fn read_dc(config: dconfig::DCFileConfig, input: String) -> Result<DCFile, DCReadError> {
     let dcparse_input: Vec<parser::InputFile> = vec![("input.dc".to_string(), input)];
 
     parser::dcparse_pipeline(config, dcparse_input)
@@ -52,7 +52,8 @@ pub enum TypeDeclaration {
 pub struct PythonImport {
     pub span: Span,
     pub module: SymbolWithViews,
-    pub class: SymbolWithViews,
+    /// Empty for plain `import module` statements.
+    pub classes: Vec<SymbolWithViews>,
 }
 
 /// Paired with the `py_module` and `dclass_import`
@@ -81,6 +82,9 @@ pub struct TypeDefinition {
     /// Used if deprecated type aliases are found, such as `typedef uint8 bool;`
     pub deprecated: bool,
     pub data_type: NonMethodDataType,
+    /// Set if the alias is declared as an array, such as
+    /// `typedef uint32 DoIdList[];`, even if its range is empty.
+    pub is_array: bool,
     pub array_range: Option<ArrayRange>,
     pub alias_identifier: Option<String>,
 }
@@ -119,6 +123,9 @@ pub struct AtomicField {
     pub identifier: Option<String>,
     pub keywords: Vec<String>,
     pub parameters: MethodBody,
+    /// Set if declared as a plain field, e.g. `string name`,
+    /// which has a single parameter instead of a method body.
+    pub parameter_field: bool,
 }
 
 impl AtomicField {
@@ -129,12 +136,14 @@ impl AtomicField {
                 identifier: pf.parameter.identifier.clone(),
                 keywords: kw_list,
                 parameters: vec![pf.parameter],
+                parameter_field: true,
             },
             NamedField::MethodAsField(mf) => Self {
                 span,
                 identifier: Some(mf.identifier),
                 keywords: kw_list,
                 parameters: mf.parameters,
+                parameter_field: false,
             },
         }
     }
@@ -170,7 +179,7 @@ pub enum StructField {
 impl From<NamedField> for StructField {
     fn from(value: NamedField) -> Self {
         match value {
-            NamedField::ParameterField(pf) => Self::ParameterField(pf),
+            NamedField::ParameterField(pf) => Self::ParameterField(*pf),
             NamedField::MethodAsField(mf) => Self::MethodAsField(mf),
         }
     }
@@ -198,7 +207,7 @@ pub struct Case {
 /// Paired with the `named_field` production in the Context Free Grammar.
 #[derive(Debug, Clone)]
 pub enum NamedField {
-    ParameterField(ParameterField),
+    ParameterField(Box<ParameterField>),
     MethodAsField(MethodAsField),
 }
 
@@ -275,6 +284,18 @@ pub struct TypeWithArray {
     pub array_ranges: Vec<ArrayRange>,
 }
 
+impl TypeWithArray {
+    /// Returns the number of array dimensions of this type. Numeric types
+    /// and identifiers are only parsed as a `TypeWithArray` when followed
+    /// by brackets, so they have at least one, even if its range is empty.
+    pub fn array_dimensions(&self) -> usize {
+        match self.data_type {
+            ArrayableType::Numeric(_) | ArrayableType::Struct(_) => self.array_ranges.len().max(1),
+            ArrayableType::Sized(_) => self.array_ranges.len(),
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub enum ArrayableType {
     Numeric(NumericType),
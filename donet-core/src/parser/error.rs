@@ -49,6 +49,7 @@ pub enum DCReadError {
 #[derive(Debug, Error)]
 #[error(transparent)]
 pub enum PipelineError {
+    Lexer(#[from] LexError),
     Parser(#[from] ParseError),
     Semantics(#[from] SemanticError),
 }
@@ -57,6 +58,7 @@ impl ToErrorCode for PipelineError {
     fn error_code(&self) -> &str {
         // Get the error code from the underlying error type.
         match self {
+            Self::Lexer(err) => err.error_code(),
             Self::Parser(err) => err.error_code(),
             Self::Semantics(err) => err.error_code(),
         }
@@ -71,6 +73,8 @@ pub enum SemanticError {
     AlreadyDefined(String),
     #[error("`{0}` is not defined")]
     NotDefined(String),
+    #[error("`{0}` does not name a type")]
+    UnknownType(String),
 
     // dc file
     #[error("multiple inheritance is not allowed")]
@@ -91,6 +95,8 @@ pub enum SemanticError {
     // structs
     #[error("dc keywords are not allowed in struct fields")]
     KeywordsInStructField,
+    #[error("method fields are not allowed in structs")]
+    MethodInStruct,
 
     // switches
     #[error("duplicate case value")]
@@ -135,6 +141,7 @@ impl ToErrorCode for SemanticError {
             // generic
             Self::AlreadyDefined(_) => "E0200",
             Self::NotDefined(_) => "E0201",
+            Self::UnknownType(_) => "E0202",
             // dc file
             Self::MultipleInheritanceDisabled => "E0210",
             Self::DClassOverflow => "E0211",
@@ -145,6 +152,7 @@ impl ToErrorCode for SemanticError {
             Self::RedundantKeyword(_) => "E0230",
             // structs
             Self::KeywordsInStructField => "E0240",
+            Self::MethodInStruct => "E0241",
             // switches
             Self::RedundantCase => "E0250",
             Self::RedundantDefault => "E0251",
@@ -167,6 +175,32 @@ impl ToErrorCode for SemanticError {
     }
 }
 
+/// Error type for malformed input found by the lexer. Since the
+/// lexer is an iterator of tokens, these are carried to the parser
+/// inside a [`DCToken::Invalid`] token instead of panicking.
+#[derive(Debug, Clone, PartialEq, Error)]
+pub enum LexError {
+    #[error("integer literal `{0}` does not fit in 64 bits")]
+    InvalidInteger(String),
+    #[error("invalid float literal `{0}`")]
+    InvalidFloat(String),
+    #[error("invalid character literal {0}")]
+    InvalidCharacterLiteral(String),
+    #[error("unexpected character `{0}`")]
+    UnexpectedCharacter(String),
+}
+
+impl ToErrorCode for LexError {
+    fn error_code(&self) -> &str {
+        match self {
+            Self::InvalidInteger(_) => "E0001",
+            Self::InvalidFloat(_) => "E0002",
+            Self::InvalidCharacterLiteral(_) => "E0003",
+            Self::UnexpectedCharacter(_) => "E0004",
+        }
+    }
+}
+
 /// Error type for the parser stage of the pipeline.
 /// Currently, it only stores one error type, which is
 /// the standard error type for the parser. Due to a
@@ -186,6 +220,7 @@ impl ToErrorCode for ParseError {
     }
 }
 
+#[derive(Debug)]
 pub(crate) struct Diagnostic {
     span: Span,
     stage: PipelineStage,
@@ -218,15 +253,15 @@ impl From<Diagnostic> for codespan_diag::Diagnostic<usize> {
                 val.span.min..val.span.max,
             )])
             .with_notes({
+                let mut notes: Vec<String> =
+                    vec![format!("at line {}, column {}", val.span.line, val.span.column)];
+
                 // If error type is from the Plex parser stage, emit the following notice.
                 if discriminant(&val.stage) == discriminant(&PipelineStage::Parser) {
-                    vec![
-                        "Syntax errors are limited. Please see issue #19.".into(),
-                        "https://gitlab.com/donet-server/donet/-/issues/19".into(),
-                    ]
-                } else {
-                    vec![]
+                    notes.push("Syntax errors are limited. Please see issue #19.".into());
+                    notes.push("https://gitlab.com/donet-server/donet/-/issues/19".into());
                 }
+                notes
             })
     }
 }
@@ -20,6 +20,7 @@
 //! Definition of the Lexer machine to process raw DC file
 //! string data into a stream of lexical tokens for the DC parser.
 
+use super::error::LexError;
 use crate::globals::{DC_VIEW_SUFFIXES, HISTORICAL_DC_KEYWORDS};
 use plex::lexer;
 
@@ -29,6 +30,7 @@ pub enum DCToken {
     Whitespace,       // Not a DC token; Ignores: " " | tab | carriage-return
     Comment,          // Not a DC token; Ignored. Satisfies lexer match.
     Newline,          // Not a DC token; Used by lexer iterator to keep track of line #.
+    Invalid(LexError), // Not a DC token; Malformed input, rejected by the parser.
     // Letter   ::= "A" ... "z"
     // DecDigit ::= "0" ... "9"
     // OctDigit ::= "0" ... "7"
@@ -131,29 +133,33 @@ lexer! {
     r#"true"# => (DCToken::BooleanLiteral(true), text),
     r#"false"# => (DCToken::BooleanLiteral(false), text),
 
-    r#"0|([1-9][0-9]*)"# => (DCToken::DecimalLiteral(match text.parse::<i64>() {
-        Ok(n) => { n },
-        Err(err) => {
-            panic!("dclexer: Found DecimalLiteral token, but failed to parse as i64.\n\n{}", err);
-        },
-    }), text),
+    r#"0|([1-9][0-9]*)"# => match text.parse::<i64>() {
+        Ok(n) => (DCToken::DecimalLiteral(n), text),
+        Err(_) => (DCToken::Invalid(LexError::InvalidInteger(text.to_owned())), text),
+    },
 
     r#"0[0-7]+"# => (DCToken::OctalLiteral(text.to_owned()), text),
     r#"0[xX][0-9a-fA-F]+"# => (DCToken::HexLiteral(text.to_owned()), text),
     r#"0[bB][0-1]+"# => (DCToken::BinaryLiteral(text.to_owned()), text),
 
-    r#"([0-9]?)+\.[0-9]+"# => (DCToken::FloatLiteral(match text.parse::<f64>() {
-        Ok(f) => { f },
-        Err(err) => {
-            panic!("dclexer: Found FloatLiteral token, but failed to parse as f64.\n\n{}", err);
-        }
-    }), text),
+    // Floats may omit the integer part ('.5'), the fractional part ('1.'),
+    // or the decimal point entirely if they carry an exponent ('2E10').
+    r#"([0-9]*\.[0-9]+|[0-9]+\.)([eE][+-]?[0-9]+)?|[0-9]+[eE][+-]?[0-9]+"# => match text.parse::<f64>() {
+        Ok(f) => (DCToken::FloatLiteral(f), text),
+        Err(_) => (DCToken::Invalid(LexError::InvalidFloat(text.to_owned())), text),
+    },
 
     // Rust doesn't support lookahead/lookbehind regex, so for character literals
-    // we match the entire ''x'' and extract the second (nth(1)) character.
-    r#"'.'"# => (DCToken::CharacterLiteral(text.chars().nth(1).unwrap()), text),
+    // we match the entire ''x'' (or escape sequence) and decode what is inside.
+    r#"'(\\(x[0-9a-fA-F]+|[^\n])|[^'\\\n])'"# => match decode_char_literal(text) {
+        Some(c) => (DCToken::CharacterLiteral(c), text),
+        None => (DCToken::Invalid(LexError::InvalidCharacterLiteral(text.to_owned())), text),
+    },
+    // Any other quoted text, such as '' or 'ab', holds zero or many characters.
+    r#"'[^'\n]*'"# => (DCToken::Invalid(LexError::InvalidCharacterLiteral(text.to_owned())), text),
     // Note that there is no need to escape double quotes in rust regex.
-    r#""[^"]*""# => (DCToken::StringLiteral(text.to_owned().replace('\"', "")), text),
+    // A string literal ends at the first unescaped quote, on the same line.
+    r#""([^"\\\n]|\\[^\n])*""# => (DCToken::StringLiteral(decode_string_literal(text)), text),
 
     // Signed/unsigned integer data types *could* be a single token,
     // but parsing is easier if they are all individual lexical tokens.
@@ -180,6 +186,9 @@ lexer! {
     r#"blob"# => (DCToken::BlobT, text),
     r#"blob32"# => (DCToken::Blob32T, text),
 
+    // Reserved words are listed before the identifier rule. Plex always takes the
+    // longest match, so 'importantStuff' is still an identifier, while an exact
+    // 'import' ties with the identifier rule and the earlier rule here wins.
     r#"dclass"# => (DCToken::DClass, text),
     r#"struct"# => (DCToken::Struct, text),
     r#"keyword"# => (DCToken::Keyword, text),
@@ -220,15 +229,86 @@ lexer! {
     r#"\;"# => (DCToken::Semicolon, text),
     r#"\="# => (DCToken::Equals, text),
     r#"\:"# => (DCToken::Colon, text),
-    r#"."# => {
-        panic!("dclexer: Found an unexpected character: '{}'", text);
+    r#"."# => (DCToken::Invalid(LexError::UnexpectedCharacter(text.to_owned())), text),
+}
+
+/// Decodes the character within a character literal token,
+/// including the quotes, into the actual [`char`] it represents.
+///
+/// Returns `None` if the literal holds an invalid hex escape.
+fn decode_char_literal(text: &str) -> Option<char> {
+    let inner: &str = &text[1..text.len() - 1];
+    let mut chars = inner.chars();
+
+    let first: char = chars.next()?;
+
+    if first != '\\' {
+        return Some(first);
+    }
+
+    match chars.next()? {
+        'n' => Some('\n'),
+        't' => Some('\t'),
+        'r' => Some('\r'),
+        '0' => Some('\0'),
+        'x' => u32::from_str_radix(chars.as_str(), 16)
+            .ok()
+            .and_then(char::from_u32),
+        // Any other escaped character (e.g. '\'' or '\\') is taken literally.
+        other => Some(other),
     }
 }
 
+/// Decodes the contents of a string literal token, without its
+/// surrounding quotes, replacing escape sequences such as `\"`,
+/// `\\`, `\n`, or `\x41` with the characters they represent.
+fn decode_string_literal(text: &str) -> String {
+    let inner: &str = &text[1..text.len() - 1];
+    let mut decoded: String = String::with_capacity(inner.len());
+    let mut chars = inner.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            decoded.push(c);
+            continue;
+        }
+        // The lexer rule guarantees a character follows a backslash.
+        match chars.next().unwrap() {
+            'n' => decoded.push('\n'),
+            't' => decoded.push('\t'),
+            'r' => decoded.push('\r'),
+            '0' => decoded.push('\0'),
+            'x' => {
+                let mut code: u32 = 0;
+                let mut digits: usize = 0;
+
+                // Reads up to two hex digits, as a string holds bytes.
+                while digits < 2 {
+                    let Some(digit) = chars.peek().and_then(|d| d.to_digit(16)) else {
+                        break;
+                    };
+                    code = code * 16 + digit;
+                    digits += 1;
+                    chars.next();
+                }
+                match digits {
+                    0 => decoded.push('x'),
+                    _ => decoded.push(char::from_u32(code).unwrap()),
+                }
+            }
+            // Any other escaped character (e.g. '\"' or '\\') is taken literally.
+            other => decoded.push(other),
+        }
+    }
+    decoded
+}
+
 pub struct Lexer<'a> {
     original: &'a str,
     remaining: &'a str,
     line: usize,
+    /// Byte offset of the first character on the current line.
+    line_start: usize,
 }
 
 impl<'a> Lexer<'a> {
@@ -237,6 +317,7 @@ impl<'a> Lexer<'a> {
             original: s,
             remaining: s,
             line: 1,
+            line_start: 0,
         }
     }
 }
@@ -246,6 +327,8 @@ pub struct Span {
     pub min: usize,
     pub max: usize,
     pub line: usize,
+    /// 1-based column of the span's first character within its line.
+    pub column: usize,
 }
 
 impl std::fmt::Display for Span {
@@ -253,6 +336,8 @@ impl std::fmt::Display for Span {
         writeln!(f, "--- SPAN ---")?;
         write!(f, "line: ")?;
         self.line.fmt(f)?;
+        write!(f, ", column: ")?;
+        self.column.fmt(f)?;
         write!(f, ", min: ")?;
         self.min.fmt(f)?;
         write!(f, ", max: ")?;
@@ -260,12 +345,18 @@ impl std::fmt::Display for Span {
     }
 }
 
-fn span_in(s: &str, t: &str, l: usize) -> Span {
-    let min = s.as_ptr() as usize - t.as_ptr() as usize;
+/// Returns the offset of slice `s` within the original text `t`.
+fn offset_in(s: &str, t: &str) -> usize {
+    s.as_ptr() as usize - t.as_ptr() as usize
+}
+
+fn span_in(s: &str, t: &str, l: usize, line_start: usize) -> Span {
+    let min = offset_in(s, t);
     Span {
         min,
         max: min + s.len(),
         line: l,
+        column: min - line_start + 1,
     }
 }
 
@@ -284,12 +375,13 @@ impl Iterator for Lexer<'_> {
                     // These tokens are ignored by the lexer.
                     continue;
                 }
-                (DCToken::Newline, _) => {
+                (DCToken::Newline, newline) => {
                     self.line += 1;
+                    self.line_start = offset_in(newline, self.original) + newline.len();
                     continue;
                 }
                 (tok, span) => {
-                    return Some((tok, span_in(span, self.original, self.line)));
+                    return Some((tok, span_in(span, self.original, self.line, self.line_start)));
                 }
             }
         }
@@ -298,7 +390,7 @@ impl Iterator for Lexer<'_> {
 
 #[cfg(test)]
 mod tests {
-    use super::{DCToken, Lexer};
+    use super::{DCToken, LexError, Lexer, Span};
 
     // Utility for unit testing lexer. Gives the test_string to the lexer
     // and compares the lexer results with the target_tokens vector given.
@@ -374,6 +466,18 @@ mod tests {
         lexer_test_for_target("from views.Donut import DistributedDonut/AI/OV", target);
     }
 
+    #[test]
+    fn reserved_words_and_identifiers() {
+        let target: Vec<DCToken> = vec![
+            DCToken::Import,
+            DCToken::Identifier(String::from("importantStuff")),
+            DCToken::From,
+            DCToken::Identifier(String::from("fromage")),
+            DCToken::Identifier(String::from("_import")),
+        ];
+        lexer_test_for_target("import importantStuff from fromage _import", target);
+    }
+
     #[test]
     fn number_literals() {
         let target: Vec<DCToken> = vec![
@@ -421,6 +525,34 @@ mod tests {
         );
     }
 
+    #[test]
+    #[allow(clippy::approx_constant)]
+    fn float_literal_forms() {
+        let target: Vec<DCToken> = vec![
+            DCToken::FloatLiteral(3.14),
+            DCToken::FloatLiteral(0.5),
+            DCToken::FloatLiteral(1e10),
+            DCToken::FloatLiteral(6.022e23),
+            DCToken::FloatLiteral(1.5e-3),
+            DCToken::FloatLiteral(2e10),
+            DCToken::FloatLiteral(4.0),
+            DCToken::FloatLiteral(2.5e+2),
+        ];
+        lexer_test_for_target("3.14 .5 1e10 6.022e23 1.5e-3 2E10 4. 2.5e+2", target);
+    }
+
+    #[test]
+    fn decimal_literal_lengths() {
+        let target: Vec<DCToken> = vec![
+            DCToken::DecimalLiteral(0),
+            DCToken::DecimalLiteral(7),
+            DCToken::DecimalLiteral(42),
+            DCToken::DecimalLiteral(1000),
+            DCToken::DecimalLiteral(255),
+        ];
+        lexer_test_for_target("0 7 42 1000 255", target);
+    }
+
     #[test]
     fn text_literals() {
         let target: Vec<DCToken> = vec![
@@ -445,6 +577,42 @@ mod tests {
         );
     }
 
+    #[test]
+    fn string_literals() {
+        let target: Vec<DCToken> = vec![
+            DCToken::StringLiteral(String::from("first")),
+            DCToken::StringLiteral(String::from("second")),
+            DCToken::StringLiteral(String::from("")),
+            DCToken::StringLiteral(String::from("say \"hi\"")),
+            DCToken::StringLiteral(String::from("back\\slash")),
+            DCToken::StringLiteral(String::from("A\tB\n")),
+        ];
+        lexer_test_for_target(
+            r#""first" "second" "" "say \"hi\"" "back\\slash" "\x41\tB\n""#,
+            target,
+        );
+
+        // a string literal cannot span multiple lines
+        let tokens: Vec<DCToken> = Lexer::new("\"first\nsecond\"").map(|(token, _)| token).collect();
+
+        assert!(!tokens
+            .iter()
+            .any(|token| matches!(token, DCToken::StringLiteral(_))));
+    }
+
+    #[test]
+    fn escaped_character_literals() {
+        let target: Vec<DCToken> = vec![
+            DCToken::CharacterLiteral('a'),
+            DCToken::CharacterLiteral('\n'),
+            DCToken::CharacterLiteral('A'),
+            DCToken::CharacterLiteral('\''),
+            DCToken::CharacterLiteral('\\'),
+            DCToken::CharacterLiteral('\t'),
+        ];
+        lexer_test_for_target("'a' '\\n' '\\x41' '\\'' '\\\\' '\\t'", target);
+    }
+
     #[test]
     fn data_types() {
         #[rustfmt::skip]
@@ -520,14 +688,134 @@ mod tests {
     }
 
     #[test]
-    #[should_panic]
     fn unexpected_token_test() {
-        let test_string: String = String::from("uint8 invalid_token = \\");
-        let lexer = Lexer::new(&test_string).inspect(|tok| eprintln!("token: {:?}", tok));
+        let target: Vec<DCToken> = vec![
+            DCToken::UInt8T,
+            DCToken::Identifier("invalid_token".to_string()),
+            DCToken::Equals,
+            DCToken::Invalid(LexError::UnexpectedCharacter("\\".to_string())),
+        ];
+        lexer_test_for_target("uint8 invalid_token = \\", target);
+    }
+
+    #[test]
+    fn malformed_literals_are_invalid_tokens() {
+        let target: Vec<DCToken> = vec![
+            DCToken::Invalid(LexError::InvalidInteger("99999999999999999999".to_string())),
+            DCToken::Invalid(LexError::InvalidCharacterLiteral("'\\x110000'".to_string())),
+            DCToken::Invalid(LexError::UnexpectedCharacter("@".to_string())),
+        ];
+        lexer_test_for_target("99999999999999999999 '\\x110000' @", target);
+    }
 
-        for (_, (_token, _span)) in lexer.enumerate() {
-            // iterate through lexer tokens until we get a panic
+    #[test]
+    fn empty_and_multi_character_literals() {
+        let target: Vec<DCToken> = vec![
+            DCToken::CharacterLiteral('a'),
+            DCToken::Invalid(LexError::InvalidCharacterLiteral("'ab'".to_string())),
+            DCToken::Invalid(LexError::InvalidCharacterLiteral("''".to_string())),
+            DCToken::CharacterLiteral('\''),
+        ];
+        lexer_test_for_target("'a' 'ab' '' '\\''", target);
+    }
+
+    /// Tricky inputs that previously crashed, or nearly crashed, the lexer.
+    const SEED_CORPUS: &[&str] = &[
+        "",
+        "99999999999999999999",
+        "-9223372036854775809",
+        "1.2.3",
+        ".",
+        "0x",
+        "0b2",
+        "'",
+        "''",
+        "'''",
+        "'\\'",
+        "'\\x'",
+        "'\\xFFFFFFFFFFFFFFFF'",
+        "'ab'",
+        "\"unterminated",
+        "/* unterminated",
+        "*/",
+        "\\",
+        "\\x",
+        "\u{0}\u{7f}",
+        "é 漢字 🐼",
+        "dclass { : ; } ] ) (",
+        "typedef uint8 bool;",
+    ];
+
+    /// Feeds the lexer the seed corpus, followed by random inputs built from
+    /// both raw bytes and DC syntax characters. The lexer must run to the end
+    /// of every input without panicking, and every span must be in bounds.
+    #[test]
+    fn lexer_never_panics() {
+        const ALPHABET: &[u8] = b"0123456789abcxXeE._'\"\\/*+-%()[]{},;=: \t\r\n@#";
+
+        // xorshift64; a fixed seed keeps the test reproducible
+        let mut state: u64 = 0x2545_f491_4f6c_dd1d;
+        let mut next = move || -> u64 {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state
+        };
+
+        let mut inputs: Vec<String> = SEED_CORPUS.iter().map(|s| s.to_string()).collect();
+
+        for _ in 0..2000 {
+            let len: usize = (next() % 48) as usize;
+            let bytes: Vec<u8> = (0..len)
+                .map(|_| match next() % 4 {
+                    0 => next() as u8,
+                    _ => ALPHABET[(next() % ALPHABET.len() as u64) as usize],
+                })
+                .collect();
+
+            inputs.push(String::from_utf8_lossy(&bytes).into_owned());
         }
+
+        for input in &inputs {
+            for (_token, span) in Lexer::new(input) {
+                assert!(span.min <= span.max && span.max <= input.len());
+            }
+        }
+    }
+
+    #[test]
+    fn lex_error_line_number() {
+        let test_string: &str = "keyword a;\nkeyword b;\ntypedef uint8 x = 99999999999999999999;";
+
+        let (token, span) = Lexer::new(test_string)
+            .find(|(token, _)| matches!(token, DCToken::Invalid(_)))
+            .expect("The out of range integer should be an invalid token.");
+
+        assert_eq!(
+            token,
+            DCToken::Invalid(LexError::InvalidInteger("99999999999999999999".to_string()))
+        );
+        assert_eq!(span.line, 3);
+
+        // the malformed input is a recoverable error, not a panic
+        let result = crate::read_dc(Default::default(), test_string.to_string());
+
+        assert!(matches!(result, Err(crate::parser::error::DCReadError::Syntax)));
+    }
+
+    #[test]
+    fn column_on_third_line() {
+        let test_string: &str = "keyword a;\nkeyword b;\n  typedef uint8 x;";
+
+        let spans: Vec<Span> = Lexer::new(test_string)
+            .filter(|(_, span)| span.line == 3)
+            .map(|(_, span)| span)
+            .collect();
+
+        // 'typedef' is indented by two spaces, then 'uint8' follows it.
+        assert_eq!(spans[0].column, 3);
+        assert_eq!(spans[1].column, 11);
+        assert_eq!(spans[2].column, 17);
     }
 
     #[test]
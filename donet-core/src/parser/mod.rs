@@ -47,10 +47,7 @@ pub(crate) type InputFile = (String, String);
 /// Runs the entire DC parser pipeline. The input is an array of strings
 /// that represent the input DC files in UTF-8, and the output is the final
 /// DC element tree data structure to be used by Donet.
-pub(crate) fn dcparse_pipeline<'a>(
-    config: DCFileConfig,
-    inputs: Vec<InputFile>,
-) -> Result<DCFile<'a>, DCReadError> {
+pub(crate) fn dcparse_pipeline(config: DCFileConfig, inputs: Vec<InputFile>) -> Result<DCFile, DCReadError> {
     // Create new pipeline data struct with [`DCFileConfig`]
     let mut pipeline_data: PipelineData<'_> = PipelineData::from(config);
 
@@ -72,11 +69,12 @@ pub(crate) fn dcparse_pipeline<'a>(
                     let token: lexer::DCToken = parser_err.0;
                     let msg: String = err.1.to_owned();
 
-                    let diag: error::Diagnostic = error::Diagnostic::error(
-                        span,
-                        &mut pipeline_data,
-                        error::PipelineError::Parser(error::ParseError::Error(token, msg)),
-                    );
+                    let err: error::PipelineError = match token {
+                        // Report malformed input with the lexer's own error.
+                        lexer::DCToken::Invalid(lex_err) => lex_err.into(),
+                        token => error::ParseError::Error(token, msg).into(),
+                    };
+                    let diag: error::Diagnostic = error::Diagnostic::error(span, &mut pipeline_data, err);
 
                     pipeline_data
                         .emit_diagnostic(diag.into())
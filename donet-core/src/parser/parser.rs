@@ -50,6 +50,7 @@ parser! {
             min: a.min,
             max: b.max,
             line: a.line, // only keep a's line number
+            column: a.column,
         }
     }
 
@@ -80,11 +81,23 @@ parser! {
     // ---------- Python-style Imports ---------- //
 
     python_style_import: ast::PythonImport {
-        py_module[module] dclass_import[class] => {
+        py_module[module] dclass_import[classes] => {
             ast::PythonImport {
                 span: span!(),
                 module,
-                class,
+                classes,
+            }
+        },
+        // e.g. "import views"
+        Import modules[modules] => {
+            ast::PythonImport {
+                span: span!(),
+                module: ast::SymbolWithViews {
+                    span: span!(),
+                    symbol: modules.join("."),
+                    symbol_views: vec![],
+                },
+                classes: vec![],
             }
         },
     }
@@ -160,17 +173,30 @@ parser! {
     }
 
     // e.g. "... import DistributedDonut/AI/OV"
+    // e.g. "... import DistributedDonut, DistributedDonutAI"
     // e.g. "... import *"
-    dclass_import: ast::SymbolWithViews {
-        Import Identifier(c) view_suffixes[cs] => ast::SymbolWithViews {
-            span: span!(),
-            symbol: c,
-            symbol_views: cs,
-        },
-        Import Star => ast::SymbolWithViews {
+    dclass_import: Vec<ast::SymbolWithViews> {
+        Import dclass_symbols[symbols] => symbols,
+        Import Star => vec![ast::SymbolWithViews {
             span: span!(),
             symbol: "*".into(),
             symbol_views: vec![],
+        }],
+    }
+
+    dclass_symbols: Vec<ast::SymbolWithViews> {
+        dclass_symbol[symbol] => vec![symbol],
+        dclass_symbols[mut symbols] Comma dclass_symbol[symbol] => {
+            symbols.push(symbol);
+            symbols
+        }
+    }
+
+    dclass_symbol: ast::SymbolWithViews {
+        Identifier(c) view_suffixes[cs] => ast::SymbolWithViews {
+            span: span!(),
+            symbol: c,
+            symbol_views: cs,
         },
     }
 
@@ -202,6 +228,7 @@ parser! {
                 span: span!(),
                 deprecated: true,
                 data_type: nmt.data_type,
+                is_array: false,
                 array_range: None,
                 alias_identifier: nmt.identifier,
             })
@@ -222,6 +249,7 @@ parser! {
             }
             let mut type_def = td.unwrap();
 
+            type_def.is_array = true;
             type_def.array_range = ar;
 
             Some(type_def)
@@ -419,10 +447,10 @@ parser! {
         nonmethod_type_with_name[nmt] => {
             let param: ast::Parameter = nmt.into();
 
-            ast::NamedField::ParameterField(param.into())
+            ast::NamedField::ParameterField(Box::new(param.into()))
         },
-        field_with_name_as_array[field] => ast::NamedField::ParameterField(field),
-        field_with_name_and_default[field] => ast::NamedField::ParameterField(field),
+        field_with_name_as_array[field] => ast::NamedField::ParameterField(Box::new(field)),
+        field_with_name_and_default[field] => ast::NamedField::ParameterField(Box::new(field)),
     }
 
     field_with_name_as_array: ast::ParameterField {
@@ -515,16 +543,29 @@ parser! {
 
     // ---------- Parameter ---------- //
 
+    // Parameters are separated by commas, e.g. "int16, int16 y, int16 z = 0"
     parameters: Vec<ast::Parameter> {
         epsilon => vec![],
-        #[no_reduce(Comma)] // don't reduce if we're expecting more params
-        parameters[mut vector] parameter[param] => {
+        parameter_list[vector] => vector,
+    }
+
+    parameter_list: Vec<ast::Parameter> {
+        method_parameter[param] => vec![param],
+        parameter_list[mut vector] Comma method_parameter[param] => {
             vector.push(param);
             vector
         },
-        parameters[mut vector] parameter[param] Comma => {
-            vector.push(param);
-            vector
+    }
+
+    // Unlike other parameters, method parameters can be given a name.
+    method_parameter: ast::Parameter {
+        parameter[param] => param,
+        nonmethod_type_with_name[nmt] => nmt.into(),
+        nonmethod_type_with_name[nmt] Equals type_value[value] => {
+            let mut param: ast::Parameter = nmt.into();
+
+            param.default_value = Some(value);
+            param
         },
     }
 
@@ -969,6 +1010,9 @@ mod tests {
             from views/AI import DistributedDonut
             from game.views.Donut/AI import DistributedDonut/AI
             from views import *
+            from views import DistributedDonut/AI, DistributedDonut2
+            import views
+            import game.views
 
             /* The next one tests handling legal python identifiers
             * that may be lexed as tokens other than Id/Module.
@@ -977,7 +1021,7 @@ mod tests {
             ",
         );
 
-        assert_eq!(dc_file.type_declarations.len(), 7);
+        assert_eq!(dc_file.type_declarations.len(), 10);
     }
 
     #[test]
@@ -41,7 +41,7 @@ use anyhow::Result;
 /// and outputs a [`crate::dcfile::DCFile`] immutable structure.
 ///
 /// [`Abstract Syntax Tree`]: https://en.wikipedia.org/wiki/Abstract_syntax_tree
-pub fn semantic_analyzer<'a>(pipeline: &mut PipelineData) -> Result<dcfile::DCFile<'a>, DCReadError> {
+pub fn semantic_analyzer(pipeline: &mut PipelineData) -> Result<dcfile::DCFile, DCReadError> {
     // tell the pipeline we are moving onto the next stage
     pipeline.next_stage();
 
@@ -58,9 +58,15 @@ pub fn semantic_analyzer<'a>(pipeline: &mut PipelineData) -> Result<dcfile::DCFi
                 ast::TypeDeclaration::KeywordType(keyword) => {
                     dc_file.add_keyword(pipeline, keyword);
                 }
-                ast::TypeDeclaration::StructType(_) => {}
-                ast::TypeDeclaration::DClassType(_) => {}
-                ast::TypeDeclaration::TypedefType(_) => {}
+                ast::TypeDeclaration::StructType(strukt) => {
+                    dc_file.add_struct(pipeline, strukt);
+                }
+                ast::TypeDeclaration::DClassType(dclass) => {
+                    dc_file.add_dclass(pipeline, dclass);
+                }
+                ast::TypeDeclaration::TypedefType(typedef) => {
+                    dc_file.add_typedef(pipeline, typedef);
+                }
                 // Ignore is returned by productions that parsed certain
                 // grammar that may be deprecated but ignored for
                 // compatibility & should not be added to the DC file.
@@ -81,6 +87,11 @@ pub fn semantic_analyzer<'a>(pipeline: &mut PipelineData) -> Result<dcfile::DCFi
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::dcfield::{ClassField, StructField};
+    use crate::dclass;
+    use crate::dctype::{DCTypeDefinition, DCTypeEnum};
+    use crate::parser::lexer::Lexer;
+    use crate::parser::parser::parse;
     use crate::read_dc;
     use dcfile::DCPythonImport;
 
@@ -115,6 +126,182 @@ mod tests {
         }
     }
 
+    #[test]
+    fn read_multiple_dc_files() {
+        let dir: std::path::PathBuf = std::env::temp_dir();
+
+        let write_dc = |name: &str, contents: &str| -> String {
+            let path: std::path::PathBuf = dir.join(format!("donet-{}-{}.dc", std::process::id(), name));
+            std::fs::write(&path, contents).unwrap();
+            path.to_string_lossy().into_owned()
+        };
+        let main: String = write_dc("main", "keyword abcdef;\ndclass DistributedNode {};\n");
+        let game: String = write_dc("game", "dclass DistributedDonut : DistributedNode {};\n");
+        let clash: String = write_dc("clash", "dclass DistributedNode {};\n");
+
+        // later files may refer to classes declared in earlier ones
+        let merged = crate::read_dc_files(DCFileConfig::default(), vec![main.clone(), game.clone()]);
+        // a class declared in two files is a collision
+        let collided = crate::read_dc_files(DCFileConfig::default(), vec![main.clone(), clash.clone()]);
+
+        for path in [main, game, clash] {
+            std::fs::remove_file(path).unwrap();
+        }
+        let merged: dcfile::DCFile = merged.unwrap();
+
+        assert!(merged.is_valid());
+        assert!(merged.has_keyword("abcdef"));
+        assert_eq!(merged.get_num_dclasses(), 2);
+        assert_eq!(
+            merged
+                .get_dclass_by_name("DistributedNode")
+                .unwrap()
+                .get_dclass_id(),
+            0
+        );
+
+        let donut: &dclass::DClass = merged.get_dclass_by_name("DistributedDonut").unwrap();
+        assert_eq!(donut.get_dclass_id(), 1);
+        assert_eq!(donut.get_num_parents(), 1);
+        assert!(matches!(collided, Err(DCReadError::Semantic)));
+    }
+
+    #[test]
+    fn valid_dc_file() {
+        let dc_string: &str = "
+            dclass DistributedNode {
+                setName(string name);
+            };
+
+            dclass DistributedAvatar : DistributedNode {
+                setHp(int16 hp);
+            };
+        ";
+
+        let dcf: dcfile::DCFile = read_dc(DCFileConfig::default(), dc_string.into()).unwrap();
+
+        assert!(dcf.is_valid());
+    }
+
+    #[test]
+    fn read_multi_class_dc_file() {
+        let dc_string: &str = "
+            from views import DistributedDonut/AI
+            keyword abcdef;
+
+            struct Point {
+                int16 x;
+                int16 y;
+            };
+
+            dclass DistributedNode {
+                setPos(Point pos) broadcast ram;
+            };
+
+            dclass DistributedDonut : DistributedNode {
+                setFlavor(string) abcdef;
+            };
+        ";
+
+        let dcf: dcfile::DCFile = read_dc(DCFileConfig::default(), dc_string.into()).unwrap();
+
+        assert!(dcf.is_valid());
+        assert_eq!(dcf.get_num_imports(), 1);
+        assert_eq!(
+            dcf.get_python_import(0).symbols,
+            vec!["DistributedDonut".to_owned(), "DistributedDonutAI".to_owned()]
+        );
+        assert_eq!(dcf.get_num_keywords(), 1);
+        assert!(dcf.has_keyword("abcdef"));
+
+        // structs and dclasses are carried over with their fields
+        assert_eq!(dcf.get_num_structs(), 1);
+        assert_eq!(dcf.get_struct(0).unwrap().get_num_fields(), 2);
+        assert_eq!(dcf.get_num_dclasses(), 2);
+
+        let donut: &dclass::DClass = dcf.get_dclass_by_name("DistributedDonut").unwrap();
+
+        assert_eq!(donut.get_dclass_id(), 1);
+        assert_eq!(donut.get_num_parents(), 1);
+        assert_eq!(donut.get_num_inherited_fields(), 2);
+
+        match donut.get_field_by_name("setFlavor") {
+            Some(ClassField::Atomic(atomic)) => assert_eq!(atomic.to_string(), "setFlavor(string);\n"),
+            _ => panic!("Expected an atomic field."),
+        }
+        assert_eq!(
+            dcf.get_class_table(),
+            "0      DistributedNode\n         setPos(struct pos)\n\
+             1      DistributedDonut\n         setFlavor(string)\n"
+        );
+
+        // a syntax error is reported as such, rather than as a semantic error
+        let result = read_dc(DCFileConfig::default(), "dclass DistributedNode {".into());
+
+        assert!(matches!(result, Err(DCReadError::Syntax)));
+    }
+
+    #[test]
+    fn invalid_dclass_declarations() {
+        let mut pipeline: PipelineData = DCFileConfig::default().into();
+        let mut dc_file = dcfile::interim::DCFile::from(DCFileConfig::default());
+
+        let root: ast::Root = parse(Lexer::new(
+            "
+            dclass DistributedNode {};
+            dclass DistributedNode {};
+            dclass DistributedAvatar : DistributedToon {};
+        ",
+        ))
+        .expect("Failed to parse syntax.");
+
+        let mut dclasses = root.type_declarations.into_iter().map(|decl| match decl {
+            ast::TypeDeclaration::DClassType(dclass) => dclass,
+            _ => panic!("Expected a dclass declaration."),
+        });
+
+        dc_file.add_dclass(&mut pipeline, dclasses.next().unwrap());
+        assert!(dc_file.all_object_valid);
+        assert!(!pipeline.failing());
+
+        // a duplicate dclass name is a semantic error
+        dc_file.add_dclass(&mut pipeline, dclasses.next().unwrap());
+        assert!(!dc_file.all_object_valid);
+        assert!(pipeline.failing());
+        assert_eq!(dc_file.dclasses.len(), 1);
+
+        // as is inheriting from an undeclared dclass
+        let mut pipeline: PipelineData = DCFileConfig::default().into();
+        let mut dc_file = dcfile::interim::DCFile::from(DCFileConfig::default());
+
+        dc_file.add_dclass(&mut pipeline, dclasses.next().unwrap());
+        assert!(!dc_file.all_object_valid);
+        assert!(pipeline.failing());
+    }
+
+    #[test]
+    fn plain_and_multi_symbol_imports() {
+        let dc_string: &str = "
+            import views
+            from game.toon import DistributedToon, DistributedToonAI
+            from views import DistributedDonut/AI
+        ";
+
+        let dcf: dcfile::DCFile = read_dc(DCFileConfig::default(), dc_string.into()).unwrap();
+        let imports: Vec<String> = (0..dcf.get_num_imports())
+            .map(|index| dcf.get_python_import(index).to_string())
+            .collect();
+
+        assert_eq!(
+            imports,
+            vec![
+                "import views",
+                "from game.toon import DistributedToon, DistributedToonAI",
+                "from views import DistributedDonut, DistributedDonutAI",
+            ]
+        );
+    }
+
     #[test]
     #[should_panic]
     fn redundant_view_suffix() {
@@ -137,4 +324,409 @@ mod tests {
 
         let _ = read_dc(dc_config, dc_string.into()).expect("Should fail.");
     }
+
+    #[test]
+    fn typedef_alias_resolution() {
+        let mut pipeline: PipelineData = DCFileConfig::default().into();
+        let mut dc_file = dcfile::interim::DCFile::from(DCFileConfig::default());
+
+        let root: ast::Root = parse(Lexer::new(
+            "
+            typedef uint32 DoId;
+            struct Avatar {
+                DoId avatarId;
+                Unknown thing;
+            };
+        ",
+        ))
+        .expect("Failed to parse syntax.");
+
+        let mut declarations = root.type_declarations.into_iter();
+
+        match declarations.next() {
+            Some(ast::TypeDeclaration::TypedefType(typedef)) => dc_file.add_typedef(&mut pipeline, typedef),
+            _ => panic!("Expected a typedef declaration."),
+        }
+        let fields: Vec<ast::StructField> = match declarations.next() {
+            Some(ast::TypeDeclaration::StructType(strukt)) => strukt.fields,
+            _ => panic!("Expected a struct declaration."),
+        };
+
+        let mut params = fields.into_iter().map(|field| match field {
+            ast::StructField::ParameterField(pf) => pf.parameter,
+            _ => panic!("Expected a parameter field."),
+        });
+
+        // `DoId` is substituted with its aliased type
+        let param: ast::Parameter = params.next().unwrap();
+        let resolved: DCTypeDefinition = dc_file
+            .resolve_type(&mut pipeline, param.span, param.data_type)
+            .expect("`DoId` should resolve to its typedef.");
+
+        assert_eq!(resolved.get_dc_type(), DCTypeEnum::TUInt32);
+        assert_eq!(resolved.get_alias(), Some("DoId"));
+        assert!(!pipeline.failing());
+
+        // an unknown type name is a semantic error
+        let param: ast::Parameter = params.next().unwrap();
+
+        assert!(dc_file
+            .resolve_type(&mut pipeline, param.span, param.data_type)
+            .is_err());
+        assert!(pipeline.failing());
+    }
+
+    #[test]
+    #[should_panic]
+    fn typedef_already_defined() {
+        let dc_config = DCFileConfig::default();
+        let dc_string: &str = "
+            typedef uint32 DoId;
+            typedef uint64 DoId;
+        ";
+
+        let _ = read_dc(dc_config, dc_string.into()).expect("Should fail.");
+    }
+
+    #[test]
+    fn typedefs_of_structs_and_arrays() {
+        let mut pipeline: PipelineData = DCFileConfig::default().into();
+        let mut dc_file = dcfile::interim::DCFile::from(DCFileConfig::default());
+
+        let params: Vec<ast::Parameter> = parse_struct_parameters(
+            &mut dc_file,
+            &mut pipeline,
+            "
+            struct Pos {
+                int16 x;
+            };
+            typedef Pos Position;
+            typedef uint32 DoId;
+            typedef DoId DoIdList[];
+            struct Avatar {
+                Position pos;
+                DoId[] friends = [1, 2];
+                DoIdList ids = [3];
+                Pos[] path = [];
+            };
+        ",
+        );
+        assert!(!pipeline.failing());
+
+        let position: &DCTypeDefinition = dc_file.get_typedef("Position").unwrap();
+        assert_eq!(position.get_dc_type(), DCTypeEnum::TStruct);
+
+        // arrays of a typedef keep the aliased type as their element type
+        let list: &DCTypeDefinition = dc_file.get_typedef("DoIdList").unwrap();
+        assert_eq!(list.get_dc_type(), DCTypeEnum::TVarArray);
+        assert_eq!(list.get_element_type().unwrap().get_alias(), Some("DoId"));
+
+        let friends: DCTypeDefinition = dc_file.lookup_type(params[1].data_type.clone());
+        assert_eq!(friends.get_dc_type(), DCTypeEnum::TVarArray);
+        assert_eq!(friends.get_element_type().unwrap().get_alias(), Some("DoId"));
+
+        let packed: Vec<Option<Vec<u8>>> = params
+            .iter()
+            .map(|param| dc_file.pack_default_value(&mut pipeline, param).unwrap())
+            .collect();
+
+        assert_eq!(packed[0], None);
+        assert_eq!(packed[1], Some(vec![8, 0, 1, 0, 0, 0, 2, 0, 0, 0]));
+        assert_eq!(packed[2], Some(vec![4, 0, 3, 0, 0, 0]));
+        assert_eq!(packed[3], Some(vec![0, 0]));
+        assert!(!pipeline.failing());
+
+        // struct types may be used as parameters of dclass fields
+        assert!(read_dc(
+            DCFileConfig::default(),
+            "struct Pos { int16 x; }; typedef Pos Position; \
+             dclass A { setPos(Position pos); setPath(Pos[] path = []); };"
+                .into()
+        )
+        .is_ok());
+    }
+
+    #[test]
+    fn forward_and_self_struct_references() {
+        // a struct may only refer to structs declared before it
+        let forward = read_dc(
+            DCFileConfig::default(),
+            "struct A { B b; }; struct B { uint8 x; };".into(),
+        );
+        assert!(matches!(forward, Err(DCReadError::Semantic)));
+
+        // which excludes the struct itself
+        let recursive = read_dc(DCFileConfig::default(), "struct A { uint8 y; A a; };".into());
+        assert!(matches!(recursive, Err(DCReadError::Semantic)));
+
+        let backward = read_dc(
+            DCFileConfig::default(),
+            "struct B { uint8 x; }; struct A { B b; };".into(),
+        );
+        assert!(backward.is_ok());
+
+        // types that are never declared are left unresolved
+        let undeclared = read_dc(DCFileConfig::default(), "struct A { Char c; };".into());
+        assert!(undeclared.is_ok());
+    }
+
+    #[test]
+    fn struct_switches() {
+        let dcf: dcfile::DCFile = read_dc(
+            DCFileConfig::default(),
+            "struct Buff {
+                uint8 level;
+                switch BuffData (uint16) {
+                    case 0:
+                        break;
+                    case 1:
+                    case 2:
+                        uint8 val1;
+                        int16/100 val2;
+                        break;
+                    default:
+                        string val3;
+                };
+            };"
+            .into(),
+        )
+        .unwrap();
+
+        let strukt = dcf.get_struct(0).unwrap();
+        assert_eq!(strukt.get_num_fields(), 2);
+
+        let Some(StructField::Switch(switch)) = strukt.get_field(1) else {
+            panic!("Expected a switch.");
+        };
+        assert_eq!(switch.get_name().as_deref(), Some("BuffData"));
+        assert_eq!(
+            switch.get_key_parameter().get_field_type().unwrap().get_dc_type(),
+            DCTypeEnum::TUInt16
+        );
+        assert_eq!(switch.get_num_cases(), 3);
+
+        // a case without a break falls through to the next case
+        let case = switch.get_case(1).unwrap();
+        assert!(!case.breaks());
+        assert_eq!(case.get_num_fields(), 0);
+
+        let case = switch.get_case(2).unwrap();
+        assert!(case.breaks());
+        assert_eq!(case.get_num_fields(), 2);
+        assert_eq!(case.get_field(1).unwrap().get_field_name(), "val2");
+        assert_eq!(
+            switch.get_case_index_by_value(2_u16.to_le_bytes().to_vec()),
+            Some(2)
+        );
+
+        let default = switch.get_default_case().unwrap();
+        assert!(default.is_default());
+        assert_eq!(default.get_field(0).unwrap().get_field_name(), "val3");
+
+        // only the plain field of the struct is assigned a field ID
+        assert_eq!(dcf.get_num_fields(), 1);
+    }
+
+    #[test]
+    fn invalid_struct_switches() {
+        for dc_string in [
+            // duplicate case value
+            "struct A { switch (uint8) { case 1: break; case 1: break; }; };",
+            // more than one default case
+            "struct A { switch (uint8) { default: break; default: break; }; };",
+            // case value not of the key's type
+            "struct A { switch (uint8) { case \"a\": break; }; };",
+            // case value out of the key's range
+            "struct A { switch (uint8) { case 256: break; }; };",
+            // key of an unknown type
+            "struct A { switch (Key) { case 0: break; }; };",
+            // case field of a struct declared after it
+            "struct A { switch (uint8) { case 0: B b; break; }; }; struct B { uint8 x; };",
+        ] {
+            let res = read_dc(DCFileConfig::default(), dc_string.into());
+            assert!(matches!(res, Err(DCReadError::Semantic)), "{}", dc_string);
+        }
+    }
+
+    #[test]
+    fn method_fields_in_structs() {
+        for dc_string in [
+            "struct A { setXY(int16, int16); };",
+            "struct A { switch (uint8) { case 0: setXY(int16, int16); break; }; };",
+        ] {
+            let res = read_dc(DCFileConfig::default(), dc_string.into());
+            assert!(matches!(res, Err(DCReadError::Semantic)), "{}", dc_string);
+        }
+    }
+
+    /// Parses the fields of the last struct declared in the given
+    /// DC string, registering any typedefs and structs declared before it.
+    fn parse_struct_parameters(
+        dc_file: &mut dcfile::interim::DCFile,
+        pipeline: &mut PipelineData,
+        dc_string: &str,
+    ) -> Vec<ast::Parameter> {
+        let root: ast::Root = parse(Lexer::new(dc_string)).expect("Failed to parse syntax.");
+        let mut params: Vec<ast::Parameter> = vec![];
+
+        for declaration in root.type_declarations {
+            match declaration {
+                ast::TypeDeclaration::TypedefType(typedef) => dc_file.add_typedef(pipeline, typedef),
+                ast::TypeDeclaration::StructType(strukt) => {
+                    dc_file.add_struct(pipeline, strukt.clone());
+
+                    params = strukt
+                        .fields
+                        .into_iter()
+                        .map(|field| match field {
+                            ast::StructField::ParameterField(pf) => pf.parameter,
+                            _ => panic!("Expected a parameter field."),
+                        })
+                        .collect();
+                }
+                _ => panic!("Expected a typedef or struct declaration."),
+            }
+        }
+        params
+    }
+
+    #[test]
+    fn field_default_values() {
+        use crate::dcfield::{DCField, FieldParent};
+        use crate::dcstruct::DCStruct;
+        use crate::hashgen::{DCHashGenerator, LegacyDCHash};
+
+        let mut pipeline: PipelineData = DCFileConfig::default().into();
+        let mut dc_file = dcfile::interim::DCFile::from(DCFileConfig::default());
+
+        let params: Vec<ast::Parameter> = parse_struct_parameters(
+            &mut dc_file,
+            &mut pipeline,
+            "
+            typedef uint32 DoId;
+            struct Avatar {
+                uint32 health = 100;
+                int16 offset = -2;
+                uint16/10 speed = 3;
+                DoId owner = 0x1f;
+                char grade = 'A';
+                string name = \"Max\";
+                blob key = 0x00ff;
+                uint8[] items = [1, 2 * 3];
+                uint16array scores = [];
+                uint8 level;
+                uint8[2] pair = [1, 2];
+                uint16[1-3] few = [4 * 2];
+            };
+        ",
+        );
+        let packed: Vec<Option<Vec<u8>>> = params
+            .iter()
+            .map(|param| dc_file.pack_default_value(&mut pipeline, param).unwrap())
+            .collect();
+
+        assert_eq!(packed[0], Some(vec![100, 0, 0, 0]));
+        assert_eq!(packed[1], Some((-2_i16).to_le_bytes().to_vec()));
+        assert_eq!(packed[2], Some(vec![30, 0])); // scaled by the divisor
+        assert_eq!(packed[3], Some(vec![0x1f, 0, 0, 0]));
+        assert_eq!(packed[4], Some(vec![b'A']));
+        assert_eq!(packed[5], Some(vec![3, 0, b'M', b'a', b'x']));
+        assert_eq!(packed[6], Some(vec![2, 0, 0x00, 0xff]));
+        assert_eq!(packed[7], Some(vec![4, 0, 1, 2, 2, 2]));
+        assert_eq!(packed[8], Some(vec![0, 0]));
+        assert_eq!(packed[9], None);
+        assert_eq!(packed[10], Some(vec![1, 2])); // fixed-size, without a size tag
+        assert_eq!(packed[11], Some(vec![4, 0, 4, 0, 4, 0]));
+        assert!(!pipeline.failing());
+
+        // the default value is folded into the field hash
+        let dcf: dcfile::DCFile = dcfile::interim::DCFile::from(DCFileConfig::default()).into();
+        let strukt: DCStruct = DCStruct::new(&dcf);
+        let hash = |default: Option<Vec<u8>>| {
+            let mut field: DCField =
+                DCField::new("health", DCTypeEnum::TUInt32.into(), FieldParent::Strukt(&strukt));
+            let mut hashgen: DCHashGenerator = DCHashGenerator::default();
+
+            if let Some(default) = default {
+                field.set_default_value(default);
+            }
+            field.generate_hash(&mut hashgen);
+            hashgen.get_hash()
+        };
+
+        assert_ne!(hash(None), hash(packed[0].clone()));
+        assert_ne!(hash(packed[0].clone()), hash(Some(vec![101, 0, 0, 0])));
+    }
+
+    #[test]
+    fn invalid_field_default_values() {
+        let mut pipeline: PipelineData = DCFileConfig::default().into();
+        let mut dc_file = dcfile::interim::DCFile::from(DCFileConfig::default());
+
+        let params: Vec<ast::Parameter> = parse_struct_parameters(
+            &mut dc_file,
+            &mut pipeline,
+            "
+            struct Avatar {
+                uint8 level = 300;
+                uint32 health = -1;
+                string name = 5;
+                uint8[] items = \"items\";
+                uint8[2] pair = [1, 2, 3];
+                uint16[1-3] few = [];
+            };
+        ",
+        );
+
+        for param in &params {
+            assert!(dc_file.pack_default_value(&mut pipeline, param).is_err());
+        }
+        assert!(pipeline.failing());
+
+        // invalid defaults of dclass fields fail the whole file
+        assert!(read_dc(
+            DCFileConfig::default(),
+            "dclass A { setLevel(uint8 = 255); };".into()
+        )
+        .is_ok());
+        assert!(read_dc(
+            DCFileConfig::default(),
+            "dclass A { setLevel(uint8 = 256); };".into()
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn parsed_field_default_values() {
+        let dc_string: &str = "
+            dclass A {
+                setHp(uint32 hp = 100);
+                setPair(uint8[2] pair = [1, 2]);
+                uint8 level = 5;
+                string name;
+            };
+        ";
+        let dcf: dcfile::DCFile = read_dc(DCFileConfig::default(), dc_string.into()).unwrap();
+        let dclass: &dclass::DClass = dcf.get_dclass_by_name("A").unwrap();
+
+        let atomic = |name: &str| match dclass.get_field_by_name(name) {
+            Some(ClassField::Atomic(atomic)) => atomic,
+            _ => panic!("`{}` is not an atomic field.", name),
+        };
+        let field = |name: &str| match dclass.get_field_by_name(name) {
+            Some(ClassField::Field(field)) => field,
+            _ => panic!("`{}` is not a plain field.", name),
+        };
+
+        assert_eq!(
+            atomic("setHp").get_element(0).unwrap().get_default_value(),
+            vec![100, 0, 0, 0]
+        );
+        assert_eq!(
+            atomic("setPair").get_element(0).unwrap().get_default_value(),
+            vec![1, 2]
+        );
+        assert_eq!(field("level").get_default_value(), Some([5].as_slice()));
+        assert_eq!(field("name").get_default_value(), None);
+    }
 }
@@ -20,11 +20,15 @@
 //! This module defines the `Protocol` enum, which stores every
 //! type of message in the Donet protocol, along with their 16-bit ID.
 
+use crate::globals::MsgType;
+use std::collections::HashMap;
+use std::sync::OnceLock;
+use strum::IntoEnumIterator;
 use strum_macros::EnumIter;
 
 /// Enum variants for all message types in the Donet protocol.
 #[repr(u16)] // 16-bit alignment
-#[derive(Debug, Copy, Clone, PartialEq, EnumIter)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, EnumIter)]
 pub enum Protocol {
     /// Client Messages
     ClientHello = 1,
@@ -157,3 +161,60 @@ pub enum Protocol {
     MDSetConUrl = 9013,
     MDLogMessage = 9014,
 }
+
+impl Protocol {
+    /// Returns the message type with the given 16-bit ID, or `None` if
+    /// no message in the protocol has this ID.
+    ///
+    /// The lookup table is built once, on the first call.
+    pub fn from_u16(id: MsgType) -> Option<Self> {
+        static MSG_TYPES: OnceLock<HashMap<MsgType, Protocol>> = OnceLock::new();
+
+        MSG_TYPES
+            .get_or_init(|| Self::iter().map(|message| (message.into(), message)).collect())
+            .get(&id)
+            .copied()
+    }
+
+    /// Returns `true` if this is a control message, which is sent directly
+    /// to a Message Director on the control channel instead of being routed.
+    pub fn is_control(&self) -> bool {
+        matches!(
+            self,
+            Self::MDAddChannel
+                | Self::MDRemoveChannel
+                | Self::MDAddRange
+                | Self::MDRemoveRange
+                | Self::MDAddPostRemove
+                | Self::MDClearPostRemoves
+                | Self::MDSetConName
+                | Self::MDSetConUrl
+                | Self::MDLogMessage
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn classify_control_messages() {
+        assert!(Protocol::MDAddChannel.is_control());
+        assert!(Protocol::MDLogMessage.is_control());
+        assert!(!Protocol::ClientHello.is_control());
+        assert!(!Protocol::DBObjectDelete.is_control());
+    }
+
+    #[test]
+    fn lookup_message_by_id() {
+        for message in Protocol::iter() {
+            assert_eq!(Protocol::from_u16(message.into()), Some(message));
+        }
+        assert_eq!(Protocol::from_u16(9000), Some(Protocol::MDAddChannel));
+
+        assert_eq!(Protocol::from_u16(0), None);
+        assert_eq!(Protocol::from_u16(6), None);
+        assert_eq!(Protocol::from_u16(MsgType::MAX), None);
+    }
+}
@@ -18,11 +18,16 @@
 */
 
 use serde::Deserialize;
+use std::collections::HashMap;
+use std::io::{Error, ErrorKind, Result};
+use std::path::Path;
 
 #[derive(Deserialize, PartialEq, Debug, Clone)]
 pub struct DonetConfig {
     pub daemon: Daemon,
     pub global: Global,
+    /// A daemon with no services section performs no services.
+    #[serde(default)]
     pub services: Services,
 }
 
@@ -31,19 +36,83 @@ pub struct Daemon {
     pub name: String,
     pub id: Option<u32>,
     pub log_level: Option<String>,
+    /// Per-module log levels, keyed by module path, which
+    /// override `log_level`. e.g. `donet_network = "trace"`.
+    #[serde(default)]
+    pub log_filters: HashMap<String, String>,
+    #[serde(default)]
+    pub log_format: LogFormat,
+    pub log_file: Option<LogFile>,
+}
+
+/// Log file output, rotated by size.
+#[derive(Deserialize, PartialEq, Debug, Clone)]
+pub struct LogFile {
+    pub path: String,
+    /// Size in bytes at which the log file is rotated.
+    pub max_size: Option<u64>,
+    /// Number of rotated files kept, e.g. `donet.log.1`.
+    pub max_files: Option<usize>,
+}
+
+/// Output format of the daemon's log messages.
+#[derive(Deserialize, PartialEq, Eq, Debug, Clone, Copy, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    /// Human-readable lines with ANSI colors.
+    #[default]
+    Plain,
+    /// One JSON object per line, for log aggregation.
+    Json,
 }
 
 #[derive(Deserialize, PartialEq, Debug, Clone)]
 pub struct Global {
     pub eventlogger: Option<String>, // '<host>:<port>'
+    #[serde(default)]
     pub dc_files: Vec<String>,
     /// See defaults for config vars below at libdonet's dconfig.rs.
     pub dc_multiple_inheritance: Option<bool>,
     pub dc_sort_inheritance_by_file: Option<bool>,
     pub dc_virtual_inheritance: Option<bool>,
+    /// Hexadecimal DC file hash, e.g. '0x9c737148'.
+    pub expected_dc_hash: Option<String>,
 }
 
-#[derive(Deserialize, PartialEq, Debug, Clone)]
+impl Global {
+    /// Compares the computed DC file hash against the `expected_dc_hash`
+    /// configured, if any. All nodes in a cluster must agree on the DC
+    /// file, so a mismatch is an error that should abort startup.
+    pub fn check_dc_hash(&self, dc_hash: u32) -> Result<()> {
+        let Some(expected) = &self.expected_dc_hash else {
+            return Ok(());
+        };
+        let digits: &str = expected.trim_start_matches("0x").trim_start_matches("0X");
+
+        let expected_hash: u32 = match u32::from_str_radix(digits, 16) {
+            Ok(hash) => hash,
+            Err(_) => {
+                return Err(Error::new(
+                    ErrorKind::InvalidInput,
+                    format!("Invalid expected DC hash `{}`.", expected),
+                ))
+            }
+        };
+
+        if expected_hash != dc_hash {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                format!(
+                    "DC file hash mismatch; expected 0x{:08x}, computed 0x{:08x}.",
+                    expected_hash, dc_hash
+                ),
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Deserialize, PartialEq, Debug, Clone, Default)]
 pub struct Services {
     pub client_agent: Option<ClientAgent>,
     pub message_director: Option<MessageDirector>,
@@ -64,6 +133,8 @@ pub struct ClientAgent {
 pub struct MessageDirector {
     pub bind: String,             // '<host>:<port>'
     pub upstream: Option<String>, // '<host>:<port>'
+    #[serde(default)]
+    pub message_stats: bool,
 }
 
 #[derive(Deserialize, PartialEq, Debug, Clone)]
@@ -126,3 +197,464 @@ impl From<DonetConfig> for donet_core::dconfig::DCFileConfig {
         this
     }
 }
+
+/// Checks that the configuration file path given exists and is a regular
+/// file, so that the user gets a clear error before it is opened.
+pub fn check_config_path(path: &Path) -> Result<()> {
+    let metadata = match std::fs::metadata(path) {
+        Ok(metadata) => metadata,
+        Err(err) if err.kind() == ErrorKind::NotFound => {
+            return Err(Error::new(
+                ErrorKind::NotFound,
+                format!("Configuration file `{}` does not exist.", path.display()),
+            ))
+        }
+        Err(err) => return Err(err),
+    };
+
+    if !metadata.is_file() {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            format!("Configuration path `{}` is not a regular file.", path.display()),
+        ));
+    }
+    Ok(())
+}
+
+/// Returns `true` if the path given has a `.toml` file extension.
+pub fn has_toml_extension(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("toml"))
+}
+
+/// Deserializes the TOML configuration contents read from `file_name`.
+///
+/// On a syntax error or a missing required option, the returned error's
+/// message names the file and the line and column the error is at.
+pub fn parse_config(file_name: &str, contents: &str) -> Result<DonetConfig> {
+    toml::from_str(contents).map_err(|err| {
+        let location: String = match err.span() {
+            Some(span) => {
+                let before: &str = &contents[..span.start];
+                let line: usize = before.matches('\n').count() + 1;
+                let column: usize = before.len() - before.rfind('\n').map_or(0, |i| i + 1) + 1;
+
+                format!(":{}:{}", line, column)
+            }
+            None => String::new(),
+        };
+        Error::new(
+            ErrorKind::InvalidInput,
+            format!(
+                "{}{}: invalid configuration; {}",
+                file_name,
+                location,
+                err.message()
+            ),
+        )
+    })
+}
+
+/// Expands `${VAR}` references in the TOML configuration contents
+/// with the values of the environment variables they name, before
+/// the contents are deserialized. A literal `$` is written as `$$`.
+///
+/// References within comments are left as they are. Values substituted
+/// within a basic string are escaped, so that they stay within it.
+///
+/// Returns an error if a referenced variable is not defined, or if its
+/// value cannot be written where it is referenced, such as a value with
+/// a `'` within a literal string, or a value with a line break outside
+/// of a string.
+pub fn interpolate_env(contents: &str) -> Result<String> {
+    interpolate_with(contents, |name| std::env::var(name).ok())
+}
+
+/// Lexical context of a variable reference within TOML contents,
+/// which decides how the variable's value is written there.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum TomlContext {
+    Bare,
+    Comment,
+    /// A basic string, which is multi-line if set.
+    BasicString(bool),
+    /// A literal string, which is multi-line if set.
+    LiteralString(bool),
+}
+
+/// Same as [`interpolate_env`], but variables are resolved by `lookup`.
+pub fn interpolate_with(contents: &str, lookup: impl Fn(&str) -> Option<String>) -> Result<String> {
+    let mut output: String = String::with_capacity(contents.len());
+    let mut context: TomlContext = TomlContext::Bare;
+    let mut rest: &str = contents;
+
+    while let Some(c) = rest.chars().next() {
+        let token: Option<(&str, TomlContext)> = match (context, c) {
+            (TomlContext::Bare, '#') => Some(("#", TomlContext::Comment)),
+            (TomlContext::Bare, '"') if rest.starts_with("\"\"\"") => {
+                Some(("\"\"\"", TomlContext::BasicString(true)))
+            }
+            (TomlContext::Bare, '"') => Some(("\"", TomlContext::BasicString(false))),
+            (TomlContext::Bare, '\'') if rest.starts_with("'''") => {
+                Some(("'''", TomlContext::LiteralString(true)))
+            }
+            (TomlContext::Bare, '\'') => Some(("'", TomlContext::LiteralString(false))),
+            (TomlContext::Comment, '\n') => Some(("\n", TomlContext::Bare)),
+            (TomlContext::Comment, _) => Some((&rest[..c.len_utf8()], context)),
+            // an escape sequence, such as `\"`, does not end the string
+            (TomlContext::BasicString(_), '\\') => {
+                let len: usize = rest[1..].chars().next().map_or(0, char::len_utf8);
+                Some((&rest[..1 + len], context))
+            }
+            (TomlContext::BasicString(true), '"') if rest.starts_with("\"\"\"") => {
+                Some(("\"\"\"", TomlContext::Bare))
+            }
+            (TomlContext::BasicString(false), '"') => Some(("\"", TomlContext::Bare)),
+            (TomlContext::LiteralString(true), '\'') if rest.starts_with("'''") => {
+                Some(("'''", TomlContext::Bare))
+            }
+            (TomlContext::LiteralString(false), '\'') => Some(("'", TomlContext::Bare)),
+            _ => None,
+        };
+        if let Some((token, next_context)) = token {
+            output.push_str(token);
+            rest = &rest[token.len()..];
+            context = next_context;
+            continue;
+        }
+        rest = &rest[c.len_utf8()..];
+
+        if c != '$' {
+            output.push(c);
+            continue;
+        }
+        if let Some(after) = rest.strip_prefix('$') {
+            rest = after;
+            output.push('$');
+            continue;
+        }
+        let Some(after) = rest.strip_prefix('{') else {
+            output.push('$');
+            continue;
+        };
+        let Some(end) = after.find('}') else {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("Unterminated variable reference `${{{}`.", after),
+            ));
+        };
+        let name: &str = &after[..end];
+        rest = &after[end + 1..];
+
+        let Some(value) = lookup(name) else {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("Environment variable `{}` is not defined.", name),
+            ));
+        };
+        write_value(&mut output, context, name, &value)?;
+    }
+    Ok(output)
+}
+
+/// Writes the value of a variable, referenced in the given context,
+/// escaping it if it is within a basic string.
+fn write_value(output: &mut String, context: TomlContext, name: &str, value: &str) -> Result<()> {
+    let line_break: bool = value.contains(['\n', '\r']);
+
+    let writable: bool = match context {
+        TomlContext::Bare => !line_break,
+        TomlContext::LiteralString(multiline) => !value.contains('\'') && (multiline || !line_break),
+        TomlContext::BasicString(_) | TomlContext::Comment => true,
+    };
+    if !writable {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            format!(
+                "Environment variable `{}` has a value that cannot be written where it is referenced.",
+                name
+            ),
+        ));
+    }
+
+    if !matches!(context, TomlContext::BasicString(_)) {
+        output.push_str(value);
+        return Ok(());
+    }
+    for c in value.chars() {
+        match c {
+            '"' => output.push_str("\\\""),
+            '\\' => output.push_str("\\\\"),
+            '\n' => output.push_str("\\n"),
+            '\r' => output.push_str("\\r"),
+            '\t' => output.push_str("\\t"),
+            c if c.is_control() => output.push_str(&format!("\\u{:04X}", u32::from(c))),
+            c => output.push(c),
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn global_with_hash(expected_dc_hash: Option<&str>) -> Global {
+        Global {
+            eventlogger: None,
+            dc_files: vec![],
+            dc_multiple_inheritance: None,
+            dc_sort_inheritance_by_file: None,
+            dc_virtual_inheritance: None,
+            expected_dc_hash: expected_dc_hash.map(str::to_owned),
+        }
+    }
+
+    #[test]
+    fn check_expected_dc_hash() {
+        assert!(global_with_hash(None).check_dc_hash(0x9c737148).is_ok());
+        assert!(global_with_hash(Some("0x9c737148"))
+            .check_dc_hash(0x9c737148)
+            .is_ok());
+        assert!(global_with_hash(Some("9C737148"))
+            .check_dc_hash(0x9c737148)
+            .is_ok());
+
+        let err: Error = global_with_hash(Some("0x9c737148"))
+            .check_dc_hash(0x12345678)
+            .unwrap_err();
+
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+        assert!(err.to_string().contains("0x12345678"));
+
+        assert!(global_with_hash(Some("not hex")).check_dc_hash(0).is_err());
+    }
+
+    /// Same as the example configuration in the reference manual.
+    const EXAMPLE_DAEMON_TOML: &str = r#"
+        [daemon]
+        name = "Donet Cluster"
+        log_level = "info"
+
+        [global]
+        eventlogger = "127.0.0.1:7197"
+        dc_files = ["main.dc", "game.dc"]
+        dc_multiple_inheritance = true
+        dc_sort_inheritance_by_file = true
+        dc_virtual_inheritance = true
+
+        [services.client_agent]
+        bind = "127.0.0.1:7198"
+        version_string = "v1.0.0"
+
+        [services.message_director]
+        bind = "127.0.0.1:7199"
+
+        [services.state_server]
+        control_channel = 102000
+
+        [services.database_server]
+        control_channel = 103000
+        db_backend = "mysql"
+        [services.database_server.sql]
+        host = "192.168.1.252:3306"
+        user = "root"
+        pass = ""
+        database = "test"
+
+        [services.dbss]
+        db_channel = 103000
+        range_min = 100000000
+        range_max = 200000000
+
+        [services.event_logger]
+        bind = "127.0.0.1:7197"
+        output = "/var/log/donet/"
+        log_format = "el-%Y-%m-%d-%H-%M-%S.log"
+        rotate_interval = "1d"
+    "#;
+
+    #[test]
+    fn deserialize_example_config() {
+        let conf: DonetConfig = toml::from_str(EXAMPLE_DAEMON_TOML).unwrap();
+
+        assert_eq!(conf.daemon.name, "Donet Cluster");
+        assert_eq!(conf.daemon.id, None);
+        assert_eq!(conf.daemon.log_level.as_deref(), Some("info"));
+        assert_eq!(conf.daemon.log_format, LogFormat::Plain);
+        assert_eq!(conf.daemon.log_file, None);
+        assert_eq!(conf.global.eventlogger.as_deref(), Some("127.0.0.1:7197"));
+        assert_eq!(conf.global.dc_files, vec!["main.dc", "game.dc"]);
+        assert_eq!(conf.global.expected_dc_hash, None);
+
+        let ca: ClientAgent = conf.services.client_agent.unwrap();
+        assert_eq!(ca.bind, "127.0.0.1:7198");
+        assert_eq!(ca.dc_file_hash, None);
+        assert_eq!(ca.version_string, "v1.0.0");
+
+        let md: MessageDirector = conf.services.message_director.unwrap();
+        assert_eq!(md.bind, "127.0.0.1:7199");
+        assert_eq!(md.upstream, None);
+        assert!(!md.message_stats);
+
+        assert_eq!(conf.services.state_server.unwrap().control_channel, 102000);
+
+        let db: DBServer = conf.services.database_server.unwrap();
+        assert_eq!(db.control_channel, 103000);
+        assert_eq!(db.db_backend, "mysql");
+        assert_eq!(db.sql.unwrap().host, "192.168.1.252:3306");
+
+        let dbss: DBSS = conf.services.dbss.unwrap();
+        assert_eq!(
+            (dbss.db_channel, dbss.range_min, dbss.range_max),
+            (103000, 100000000, 200000000)
+        );
+
+        let el: EventLogger = conf.services.event_logger.unwrap();
+        assert_eq!(el.output, "/var/log/donet/");
+        assert_eq!(el.rotate_interval, "1d");
+    }
+
+    #[test]
+    fn check_config_paths() {
+        let dir: std::path::PathBuf = std::env::temp_dir();
+
+        let missing: std::path::PathBuf = dir.join(format!("donet-missing-{}.toml", std::process::id()));
+        let err: Error = check_config_path(&missing).unwrap_err();
+
+        assert_eq!(err.kind(), ErrorKind::NotFound);
+        assert!(err.to_string().contains("does not exist"));
+
+        let err: Error = check_config_path(&dir).unwrap_err();
+
+        assert_eq!(err.kind(), ErrorKind::InvalidInput);
+        assert!(err.to_string().contains("not a regular file"));
+
+        let valid: std::path::PathBuf = dir.join(format!("donet-valid-{}.toml", std::process::id()));
+        std::fs::write(&valid, EXAMPLE_DAEMON_TOML).unwrap();
+
+        let res: Result<()> = check_config_path(&valid);
+        std::fs::remove_file(&valid).unwrap();
+        assert!(res.is_ok());
+
+        assert!(has_toml_extension(&valid));
+        assert!(has_toml_extension(Path::new("config/DAEMON.TOML")));
+        assert!(!has_toml_extension(Path::new("daemon.yaml")));
+        assert!(!has_toml_extension(Path::new("daemon")));
+    }
+
+    #[test]
+    fn parse_malformed_config() {
+        let err: Error = parse_config("daemon.toml", "[daemon]\nname = \"MD\"\n[global\n").unwrap_err();
+
+        assert_eq!(err.kind(), ErrorKind::InvalidInput);
+        assert!(err.to_string().starts_with("daemon.toml:3:"), "{}", err);
+
+        // missing required options are reported, not panicked on
+        let err: Error = parse_config("daemon.toml", "[daemon]\nid = 1\n[global]\n").unwrap_err();
+
+        assert!(err.to_string().starts_with("daemon.toml"), "{}", err);
+        assert!(err.to_string().contains("name"), "{}", err);
+
+        assert!(parse_config("daemon.toml", EXAMPLE_DAEMON_TOML).is_ok());
+    }
+
+    #[test]
+    fn deserialize_minimal_config() {
+        let conf: DonetConfig = toml::from_str("[daemon]\nname = \"MD\"\n[global]\n").unwrap();
+
+        assert!(conf.global.dc_files.is_empty());
+        assert_eq!(conf.services, Services::default());
+
+        let conf: DonetConfig = toml::from_str(
+            "[daemon]\nname = \"MD\"\n[global]\n\
+             [services.message_director]\nbind = \"127.0.0.1:7199\"\nmessage_stats = true\n",
+        )
+        .unwrap();
+
+        assert!(conf.services.message_director.unwrap().message_stats);
+
+        // a service's required options are still required
+        assert!(toml::from_str::<DonetConfig>(
+            "[daemon]\nname = \"MD\"\n[global]\n[services.state_server]\n"
+        )
+        .is_err());
+    }
+
+    fn lookup(name: &str) -> Option<String> {
+        match name {
+            "MD_PORT" => Some("7199".to_string()),
+            "DB_PASS" => Some("hunter2".to_string()),
+            "INJECTED" => Some("x\"\nadmin = true\n\\".to_string()),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn interpolate_defined_variables() {
+        let contents: &str = "bind = \"127.0.0.1:${MD_PORT}\"\npass = \"${DB_PASS}\"";
+
+        assert_eq!(
+            interpolate_with(contents, lookup).unwrap(),
+            "bind = \"127.0.0.1:7199\"\npass = \"hunter2\""
+        );
+    }
+
+    #[test]
+    fn interpolate_undefined_variable() {
+        let err: Error = interpolate_with("port = ${UNDEFINED_PORT}", lookup).unwrap_err();
+
+        assert_eq!(err.kind(), ErrorKind::InvalidInput);
+        assert!(err.to_string().contains("UNDEFINED_PORT"));
+
+        assert!(interpolate_with("port = ${MD_PORT", lookup).is_err());
+    }
+
+    #[test]
+    fn interpolate_escaped_dollar() {
+        assert_eq!(
+            interpolate_with("price = \"$$5, ${MD_PORT}, $ and $$${MD_PORT}\"", lookup).unwrap(),
+            "price = \"$5, 7199, $ and $7199\""
+        );
+    }
+
+    #[test]
+    fn interpolate_skips_comments() {
+        let contents: &str = "# port = ${UNDEFINED_PORT}\nport = ${MD_PORT} # or ${UNDEFINED_PORT}\n";
+
+        assert_eq!(
+            interpolate_with(contents, lookup).unwrap(),
+            "# port = ${UNDEFINED_PORT}\nport = 7199 # or ${UNDEFINED_PORT}\n"
+        );
+
+        // a `#` within a string does not start a comment
+        assert_eq!(
+            interpolate_with("tag = \"#${MD_PORT}\" # ${UNDEFINED_PORT}", lookup).unwrap(),
+            "tag = \"#7199\" # ${UNDEFINED_PORT}"
+        );
+    }
+
+    #[test]
+    fn interpolate_escapes_strings() {
+        for contents in [
+            "pass = \"${INJECTED}\"",
+            "pass = \"\\\"${INJECTED}\"",
+            "pass = \"\"\"\n${INJECTED}\"\"\"",
+        ] {
+            let interpolated: String = interpolate_with(contents, lookup).unwrap();
+            let table: toml::Table = toml::from_str(&interpolated).unwrap();
+
+            // the value stays within the string, without adding keys
+            assert_eq!(table.len(), 1);
+            assert!(table["pass"].as_str().unwrap().ends_with("x\"\nadmin = true\n\\"));
+        }
+
+        // values that cannot be written where they are referenced
+        for contents in ["pass = '${INJECTED}'", "pass = ${INJECTED}"] {
+            let err: Error = interpolate_with(contents, lookup).unwrap_err();
+
+            assert_eq!(err.kind(), ErrorKind::InvalidInput);
+            assert!(err.to_string().contains("INJECTED"));
+        }
+    }
+}
@@ -24,5 +24,7 @@ pub mod config;
 pub mod event;
 pub mod logger;
 pub mod meson;
+pub mod panic_hook;
 pub mod service;
+pub mod shutdown;
 pub mod subscriber;
@@ -17,8 +17,14 @@
     License along with Donet. If not, see <https://www.gnu.org/licenses/>.
 */
 
+use crate::config::{Daemon, LogFormat};
 use log::{Level, LevelFilter, Metadata, Record, SetLoggerError};
-use std::io::{Error, ErrorKind, Result};
+use std::cmp::Reverse;
+use std::ffi::OsString;
+use std::fs::{File, OpenOptions};
+use std::io::{Error, ErrorKind, Result, Write};
+use std::path::PathBuf;
+use std::sync::Mutex;
 
 pub static _ANSI_RESET: &str = "\x1b[0m";
 pub static _ANSI_RED: &str = "\x1b[31m";
@@ -31,43 +37,219 @@ pub static _ANSI_GRAY: &str = "\x1b[37;2m";
 pub static _ANSI_MAGENTA: &str = "\x1b[95m";
 
 pub struct DaemonLogger {
-    pub log_level: Level,
+    pub log_level: LevelFilter,
+    /// Module path prefixes with their own log level.
+    pub module_filters: Vec<(String, LevelFilter)>,
+    pub format: LogFormat,
+    /// Optional log file, written in addition to stdout.
+    pub file: Option<Mutex<RotatingFile>>,
 }
 
 pub static MAX_LOG_LEVEL: LevelFilter = LevelFilter::Trace;
 
+/// Default size in bytes at which the log file is rotated. (10 MiB)
+pub static DEFAULT_LOG_MAX_SIZE: u64 = 10 * 1024 * 1024;
+/// Default number of rotated log files kept.
+pub static DEFAULT_LOG_MAX_FILES: usize = 5;
+
+/// A log file that is rotated once it reaches a maximum size.
+///
+/// On rotation, `donet.log` is renamed to `donet.log.1`, the
+/// previous `donet.log.1` to `donet.log.2`, and so on. Files past
+/// `max_files` are deleted.
+pub struct RotatingFile {
+    path: PathBuf,
+    max_size: u64,
+    max_files: usize,
+    file: File,
+    size: u64,
+}
+
+impl RotatingFile {
+    /// Opens the log file for appending, creating it if needed.
+    pub fn open(path: PathBuf, max_size: u64, max_files: usize) -> Result<Self> {
+        let file: File = OpenOptions::new().create(true).append(true).open(&path)?;
+        let size: u64 = file.metadata()?.len();
+
+        Ok(Self {
+            path,
+            max_size,
+            max_files,
+            file,
+            size,
+        })
+    }
+
+    /// Appends a line to the log file, rotating it first if
+    /// the line would take the file over its maximum size.
+    pub fn write_line(&mut self, line: &str) -> Result<()> {
+        let len: u64 = line.len() as u64 + 1;
+
+        if self.size > 0 && self.size + len > self.max_size {
+            self.rotate()?;
+        }
+        writeln!(self.file, "{}", line)?;
+        self.size += len;
+        Ok(())
+    }
+
+    /// Returns the path of the n-th rotated file, e.g. `donet.log.1`.
+    pub fn rotated_path(&self, n: usize) -> PathBuf {
+        let mut path: OsString = self.path.clone().into_os_string();
+        path.push(format!(".{}", n));
+        path.into()
+    }
+
+    fn rotate(&mut self) -> Result<()> {
+        self.file.flush()?;
+
+        if self.max_files > 0 {
+            let oldest: PathBuf = self.rotated_path(self.max_files);
+            if oldest.exists() {
+                std::fs::remove_file(oldest)?;
+            }
+            for n in (1..self.max_files).rev() {
+                let from: PathBuf = self.rotated_path(n);
+                if from.exists() {
+                    std::fs::rename(from, self.rotated_path(n + 1))?;
+                }
+            }
+            std::fs::rename(&self.path, self.rotated_path(1))?;
+        }
+        self.file = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .truncate(true)
+            .open(&self.path)?;
+        self.size = 0;
+        Ok(())
+    }
+}
+
+/// Maps a log level string from the daemon configuration,
+/// such as `"warn"` or `"debug"`, to a [`LevelFilter`].
+pub fn parse_level_filter(level: &str) -> Result<LevelFilter> {
+    level.parse::<LevelFilter>().map_err(|_| {
+        Error::new(
+            ErrorKind::InvalidInput,
+            format!("Invalid log level '{}' in TOML configuration.", level),
+        )
+    })
+}
+
+impl DaemonLogger {
+    /// Builds the logger from the `daemon` section of the configuration.
+    /// The log level defaults to `info` if not set.
+    pub fn from_config(daemon: &Daemon) -> Result<Self> {
+        let log_level: LevelFilter = match &daemon.log_level {
+            Some(level) => parse_level_filter(level)?,
+            None => LevelFilter::Info,
+        };
+        let mut module_filters: Vec<(String, LevelFilter)> = vec![];
+
+        for (module, level) in &daemon.log_filters {
+            module_filters.push((module.clone(), parse_level_filter(level)?));
+        }
+        // Longest prefix first, so the most specific filter matches.
+        module_filters.sort_by_key(|(module, _)| Reverse(module.len()));
+
+        let file: Option<Mutex<RotatingFile>> = match &daemon.log_file {
+            Some(conf) => Some(Mutex::new(RotatingFile::open(
+                PathBuf::from(&conf.path),
+                conf.max_size.unwrap_or(DEFAULT_LOG_MAX_SIZE),
+                conf.max_files.unwrap_or(DEFAULT_LOG_MAX_FILES),
+            )?)),
+            None => None,
+        };
+
+        Ok(Self {
+            log_level,
+            module_filters,
+            format: daemon.log_format,
+            file,
+        })
+    }
+
+    /// Formats a log record as a single line of output.
+    /// ANSI colors are only used in plain format if `ansi` is set.
+    fn format_record(&self, record: &Record, ansi: bool) -> String {
+        match self.format {
+            LogFormat::Plain => {
+                let level_color: &str = match record.level() {
+                    Level::Info => _ANSI_MAGENTA, // themed to logo
+                    Level::Debug => _ANSI_CYAN,
+                    Level::Warn => _ANSI_ORANGE,
+                    Level::Error => _ANSI_RED,
+                    Level::Trace => _ANSI_GRAY,
+                };
+                let (gray, color, reset): (&str, &str, &str) = match ansi {
+                    true => (_ANSI_GRAY, level_color, _ANSI_RESET),
+                    false => ("", "", ""),
+                };
+                format!(
+                    "{}[{}]{} {}{}:{} {}: {}",
+                    gray,
+                    chrono::offset::Local::now().format("%Y-%m-%d %H:%M:%S"),
+                    reset,
+                    color,
+                    record.level(),
+                    reset,
+                    record.target(),
+                    record.args()
+                )
+            }
+            LogFormat::Json => serde_json::json!({
+                "timestamp": chrono::offset::Local::now().to_rfc3339(),
+                "level": record.level().as_str(),
+                "target": record.target(),
+                "message": record.args().to_string(),
+            })
+            .to_string(),
+        }
+    }
+
+    /// Returns the log level that applies to the given record target.
+    fn level_for(&self, target: &str) -> LevelFilter {
+        for (module, level) in &self.module_filters {
+            let matches: bool = match target.strip_prefix(module.as_str()) {
+                Some(rest) => rest.is_empty() || rest.starts_with("::"),
+                None => false,
+            };
+            if matches {
+                return *level;
+            }
+        }
+        self.log_level
+    }
+}
+
 impl log::Log for DaemonLogger {
     fn enabled(&self, metadata: &Metadata) -> bool {
-        metadata.level() <= self.log_level
+        metadata.level() <= self.level_for(metadata.target())
     }
 
     fn log(&self, record: &Record) {
-        let level_color: &str = match record.level() {
-            Level::Info => _ANSI_MAGENTA, // themed to logo
-            Level::Debug => _ANSI_CYAN,
-            Level::Warn => _ANSI_ORANGE,
-            Level::Error => _ANSI_RED,
-            Level::Trace => _ANSI_GRAY,
-        };
-
         if self.enabled(record.metadata()) {
-            // TODO: Write to log file by daemon configuration
-            let out_string: String = format!(
-                "{}[{}]{} {}{}:{} {}: {}",
-                _ANSI_GRAY,
-                chrono::offset::Local::now().format("%Y-%m-%d %H:%M:%S"),
-                _ANSI_RESET,
-                level_color,
-                record.level(),
-                _ANSI_RESET,
-                record.target(),
-                record.args()
-            );
-            println!("{}", out_string.as_str()); // stdout
+            println!("{}", self.format_record(record, true)); // stdout
+
+            if let Some(file) = &self.file {
+                let line: String = self.format_record(record, false);
+
+                // A failed write cannot be logged, so it is dropped.
+                if let Ok(mut file) = file.lock() {
+                    let _ = file.write_line(&line);
+                }
+            }
         }
     }
 
-    fn flush(&self) {}
+    fn flush(&self) {
+        if let Some(file) = &self.file {
+            if let Ok(mut file) = file.lock() {
+                let _ = file.file.flush();
+            }
+        }
+    }
 }
 
 pub fn init_logger(logger: &'static dyn log::Log) -> Result<()> {
@@ -86,14 +268,39 @@ pub fn init_logger(logger: &'static dyn log::Log) -> Result<()> {
 
 #[cfg(test)]
 mod tests {
-    use super::{init_logger, DaemonLogger};
+    use super::{init_logger, DaemonLogger, RotatingFile};
+    use crate::config::{Daemon, LogFile, LogFormat};
     use log::{debug, error, info, trace, warn};
+    use log::{Level, LevelFilter, Log, Metadata, Record};
+    use std::collections::HashMap;
     use std::io::Result;
+    use std::path::PathBuf;
+
+    fn daemon_with_levels(level: &str, filters: &[(&str, &str)]) -> Daemon {
+        Daemon {
+            name: "Donet".into(),
+            id: None,
+            log_level: Some(level.into()),
+            log_filters: filters
+                .iter()
+                .map(|(m, l)| (m.to_string(), l.to_string()))
+                .collect::<HashMap<String, String>>(),
+            log_format: LogFormat::Plain,
+            log_file: None,
+        }
+    }
+
+    fn enabled(logger: &DaemonLogger, level: Level, target: &str) -> bool {
+        logger.enabled(&Metadata::builder().level(level).target(target).build())
+    }
 
     #[test]
     fn logger_integrity() {
         pub static GLOBAL_LOGGER: DaemonLogger = DaemonLogger {
-            log_level: log::Level::Trace,
+            log_level: LevelFilter::Trace,
+            module_filters: vec![],
+            format: LogFormat::Plain,
+            file: None,
         };
 
         let res: Result<()> = init_logger(&GLOBAL_LOGGER);
@@ -107,4 +314,107 @@ mod tests {
         warn!("This macro should not panic.");
         trace!("This macro should not panic.");
     }
+
+    #[test]
+    fn warn_level_suppresses_info() {
+        let logger = DaemonLogger::from_config(&daemon_with_levels("warn", &[])).unwrap();
+
+        assert_eq!(logger.log_level, LevelFilter::Warn);
+        assert!(enabled(&logger, Level::Error, "donetd"));
+        assert!(enabled(&logger, Level::Warn, "donetd"));
+        assert!(!enabled(&logger, Level::Info, "donetd"));
+        assert!(!enabled(&logger, Level::Debug, "donetd"));
+    }
+
+    #[test]
+    fn per_module_filters() {
+        let daemon: Daemon = daemon_with_levels(
+            "warn",
+            &[("donet_network", "trace"), ("donet_network::tcp", "error")],
+        );
+        let logger = DaemonLogger::from_config(&daemon).unwrap();
+
+        assert!(enabled(&logger, Level::Trace, "donet_network"));
+        assert!(enabled(&logger, Level::Debug, "donet_network::udp"));
+        assert!(!enabled(&logger, Level::Warn, "donet_network::tcp"));
+        assert!(enabled(&logger, Level::Error, "donet_network::tcp"));
+        // Only whole module path segments match.
+        assert!(!enabled(&logger, Level::Info, "donet_networking"));
+        assert!(!enabled(&logger, Level::Info, "donetd"));
+    }
+
+    #[test]
+    fn invalid_log_level() {
+        assert!(DaemonLogger::from_config(&daemon_with_levels("loud", &[])).is_err());
+        assert!(DaemonLogger::from_config(&daemon_with_levels("info", &[("donetd", "x")])).is_err());
+    }
+
+    #[test]
+    fn json_log_format() {
+        let mut daemon: Daemon = daemon_with_levels("info", &[]);
+        daemon.log_format = LogFormat::Json;
+
+        let logger = DaemonLogger::from_config(&daemon).unwrap();
+        let line: String = logger.format_record(
+            &Record::builder()
+                .args(format_args!("Opened socket at {}.", "127.0.0.1:7199"))
+                .level(Level::Warn)
+                .target("donet_network::tcp")
+                .build(),
+            true,
+        );
+        assert!(!line.contains('\n'));
+
+        let value: serde_json::Value = serde_json::from_str(&line).unwrap();
+        let entry = value.as_object().unwrap();
+
+        assert_eq!(entry.len(), 4);
+        assert_eq!(entry["level"], "WARN");
+        assert_eq!(entry["target"], "donet_network::tcp");
+        assert_eq!(entry["message"], "Opened socket at 127.0.0.1:7199.");
+
+        let timestamp: &str = entry["timestamp"].as_str().unwrap();
+        assert!(chrono::DateTime::parse_from_rfc3339(timestamp).is_ok());
+    }
+
+    #[test]
+    fn log_file_rotation() {
+        let dir: PathBuf = std::env::temp_dir().join(format!("donet-log-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path: PathBuf = dir.join("donet.log");
+
+        let mut daemon: Daemon = daemon_with_levels("info", &[]);
+        daemon.log_file = Some(LogFile {
+            path: path.to_str().unwrap().into(),
+            max_size: Some(256),
+            max_files: Some(2),
+        });
+        let logger = DaemonLogger::from_config(&daemon).unwrap();
+
+        for i in 0..50 {
+            logger.log(
+                &Record::builder()
+                    .args(format_args!("Log message number {}.", i))
+                    .level(Level::Info)
+                    .target("donetd")
+                    .build(),
+            );
+        }
+        logger.flush();
+
+        let file: RotatingFile = logger.file.unwrap().into_inner().unwrap();
+
+        assert!(path.exists());
+        assert!(file.rotated_path(1).exists());
+        assert!(file.rotated_path(2).exists());
+        assert!(!file.rotated_path(3).exists());
+        assert!(std::fs::metadata(&path).unwrap().len() <= 256);
+
+        // The file output has no ANSI colors.
+        let contents: String = std::fs::read_to_string(&path).unwrap();
+        assert!(contents.contains("INFO: donetd: Log message number 49."));
+        assert!(!contents.contains('\x1b'));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
 }
@@ -0,0 +1,195 @@
+/*
+    This file is part of Donet.
+
+    Copyright © 2024 Max Rodriguez <me@maxrdz.com>
+
+    Donet is free software; you can redistribute it and/or modify
+    it under the terms of the GNU Affero General Public License,
+    as published by the Free Software Foundation, either version 3
+    of the License, or (at your option) any later version.
+
+    Donet is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+    GNU Affero General Public License for more details.
+
+    You should have received a copy of the GNU Affero General Public
+    License along with Donet. If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! Panic hook that reports which Donet service, and which message
+//! type, was being processed by the task that panicked.
+//!
+//! Services run the handling of each message within a [`scope`], and
+//! record what they are doing with [`set_context`] as they dispatch it.
+//! The context is task-local, so it stays with the message's task as it
+//! moves between the threads of the Tokio runtime across `.await` points.
+
+use donet_core::Protocol;
+use log::error;
+use std::cell::Cell;
+use std::future::Future;
+use std::panic::{self, PanicHookInfo};
+
+/// The service, and the message it is handling, on the current task.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ServiceContext {
+    pub service: &'static str,
+    pub msg_type: Option<Protocol>,
+}
+
+tokio::task_local! {
+    static CONTEXT: Cell<Option<ServiceContext>>;
+}
+
+/// Runs `future` with its own service context, which
+/// starts empty and is discarded once `future` completes.
+pub async fn scope<F: Future>(future: F) -> F::Output {
+    CONTEXT.scope(Cell::new(None), future).await
+}
+
+/// Records the service name and message type in flight on this task.
+/// Does nothing if this task is not running within a [`scope`].
+pub fn set_context(service: &'static str, msg_type: Option<Protocol>) {
+    let _ = CONTEXT.try_with(|ctx| ctx.set(Some(ServiceContext { service, msg_type })));
+}
+
+/// Clears this task's context, once a message is done processing.
+pub fn clear_context() {
+    let _ = CONTEXT.try_with(|ctx| ctx.set(None));
+}
+
+/// Returns a copy of this task's context, if one is set.
+pub fn get_context() -> Option<ServiceContext> {
+    CONTEXT.try_with(Cell::get).ok().flatten()
+}
+
+/// Formats a panic report, prefixed with this task's context.
+pub fn panic_message(info: &PanicHookInfo) -> String {
+    let payload: &str = match info.payload().downcast_ref::<&str>() {
+        Some(s) => s,
+        None => match info.payload().downcast_ref::<String>() {
+            Some(s) => s.as_str(),
+            None => "Box<dyn Any>",
+        },
+    };
+    let location: String = match info.location() {
+        Some(loc) => format!(" at {}:{}", loc.file(), loc.line()),
+        None => String::default(),
+    };
+    format_report(get_context(), &location, payload)
+}
+
+fn format_report(context: Option<ServiceContext>, location: &str, payload: &str) -> String {
+    match context {
+        Some(ServiceContext {
+            service,
+            msg_type: Some(msg_type),
+        }) => format!(
+            "{} panicked while handling {:?}{}: {}",
+            service, msg_type, location, payload
+        ),
+        Some(ServiceContext {
+            service,
+            msg_type: None,
+        }) => {
+            format!("{} panicked{}: {}", service, location, payload)
+        }
+        None => format!("Panicked{}: {}", location, payload),
+    }
+}
+
+/// Installs a panic hook that passes the [`panic_message`] to `sink`,
+/// before running the previously installed panic hook.
+pub fn install_panic_hook_with(sink: impl Fn(String) + Send + Sync + 'static) {
+    let previous_hook = panic::take_hook();
+
+    panic::set_hook(Box::new(move |info| {
+        sink(panic_message(info));
+        previous_hook(info);
+    }));
+}
+
+/// Installs a panic hook that logs the [`panic_message`] as an error.
+pub fn install_panic_hook() {
+    install_panic_hook_with(|msg| error!("{}", msg));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn panic_message_includes_context() {
+        let context = ServiceContext {
+            service: "Message Director",
+            msg_type: Some(Protocol::MDAddChannel),
+        };
+        let msg: String = format_report(Some(context), " at src/lib.rs:1", "malformed datagram");
+
+        assert_eq!(
+            msg,
+            "Message Director panicked while handling MDAddChannel at src/lib.rs:1: malformed datagram"
+        );
+        assert_eq!(
+            format_report(None, "", "malformed datagram"),
+            "Panicked: malformed datagram"
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn context_is_per_task() {
+        scope(async {
+            set_context("Event Logger", None);
+
+            // kept across `.await` points, even if the task moves threads
+            tokio::task::yield_now().await;
+            assert_eq!(get_context().unwrap().service, "Event Logger");
+
+            let other = tokio::spawn(async { get_context() }).await.unwrap();
+            assert_eq!(other, None);
+
+            clear_context();
+            assert_eq!(get_context(), None);
+        })
+        .await;
+
+        // outside of a scope, there is no context to set
+        set_context("Event Logger", None);
+        assert_eq!(get_context(), None);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn panic_hook_reports_context() {
+        use std::sync::{Arc, Mutex};
+
+        let reports: Arc<Mutex<Vec<String>>> = Arc::default();
+        let sink_reports: Arc<Mutex<Vec<String>>> = reports.clone();
+
+        // chain the hook to a silent one, so the expected panic is not printed
+        let previous_hook = panic::take_hook();
+        panic::set_hook(Box::new(|_| {}));
+        install_panic_hook_with(move |msg| sink_reports.lock().unwrap().push(msg));
+
+        let handle = tokio::spawn(scope(async {
+            set_context("Message Director", Some(Protocol::MDAddChannel));
+            panic!("malformed datagram");
+        }));
+        let res = handle.await;
+
+        drop(panic::take_hook());
+        panic::set_hook(previous_hook);
+
+        assert!(res.unwrap_err().is_panic());
+
+        // the hook is global, so it may have also seen panics of other tests
+        let reports: Vec<String> = reports.lock().unwrap().clone();
+        assert!(
+            reports.iter().any(|msg| msg
+                .starts_with("Message Director panicked while handling MDAddChannel at ")
+                && msg.ends_with(": malformed datagram")),
+            "{:?}",
+            reports
+        );
+    }
+}
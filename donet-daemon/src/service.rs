@@ -18,8 +18,13 @@
 */
 
 use crate::config;
+use crate::shutdown::{Shutdown, ShutdownCoordinator};
+use log::info;
+use std::collections::HashMap;
+use std::fmt;
 use std::future::Future;
 use std::io::Result;
+use std::pin::Pin;
 use std::sync::Arc;
 use tokio::sync::Mutex;
 use tokio::task::JoinHandle;
@@ -34,9 +39,7 @@ cfg_if! {
         /// This struct should never be initialized, as services
         /// that do not require the DC file will be passed `None`
         /// instead of `Some(DCFile)`.
-        pub struct DCFile<'dc> {
-            _hack: &'dc str, // required for lifetime parameter
-        }
+        pub struct DCFile;
     }
 }
 
@@ -53,16 +56,23 @@ pub trait DonetService {
 
     fn create(
         conf: Self::Configuration,
-        dc: Option<DCFile<'static>>,
+        dc: Option<DCFile>,
     ) -> impl Future<Output = Result<Arc<Mutex<Self::Service>>>> + Send;
 
     fn start(
         conf: config::DonetConfig,
-        dc: Option<DCFile<'static>>,
+        dc: Option<DCFile>,
+        shutdown: Shutdown,
     ) -> impl Future<Output = Result<JoinHandle<Result<()>>>> + Send;
 
     /// This service's main asynchronous loop.
-    fn main(service: Arc<Mutex<Self::Service>>) -> impl Future<Output = Result<()>> + Send;
+    ///
+    /// The loop should return once `shutdown` is notified, after
+    /// flushing and closing the service's resources.
+    fn main(
+        service: Arc<Mutex<Self::Service>>,
+        shutdown: Shutdown,
+    ) -> impl Future<Output = Result<()>> + Send;
 
     /// Spawns a new Tokio asynchronous task that executes the given
     /// async function, and returns its Tokio join handle.
@@ -79,10 +89,142 @@ pub trait DonetService {
 /// Hack to reassure the compiler the result type of a future.
 pub fn set_future_return_type<T, F: Future<Output = T>>(_arg: &F) {}
 
+/// The roles a Donet daemon can perform, one per
+/// section under `services` in the daemon configuration.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ServiceKind {
+    ClientAgent,
+    MessageDirector,
+    StateServer,
+    DatabaseServer,
+    DBSS,
+    EventLogger,
+}
+
+impl ServiceKind {
+    /// All service kinds, in the order they are booted.
+    pub const ALL: [ServiceKind; 6] = [
+        Self::ClientAgent,
+        Self::MessageDirector,
+        Self::StateServer,
+        Self::DatabaseServer,
+        Self::DBSS,
+        Self::EventLogger,
+    ];
+
+    /// Returns true if this service has a section in the configuration.
+    pub fn is_configured(self, services: &config::Services) -> bool {
+        match self {
+            Self::ClientAgent => services.client_agent.is_some(),
+            Self::MessageDirector => services.message_director.is_some(),
+            Self::StateServer => services.state_server.is_some(),
+            Self::DatabaseServer => services.database_server.is_some(),
+            Self::DBSS => services.dbss.is_some(),
+            Self::EventLogger => services.event_logger.is_some(),
+        }
+    }
+}
+
+impl fmt::Display for ServiceKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::ClientAgent => "Client Agent",
+            Self::MessageDirector => "Message Director",
+            Self::StateServer => "State Server",
+            Self::DatabaseServer => "Database Server",
+            Self::DBSS => "DBSS",
+            Self::EventLogger => "Event Logger",
+        })
+    }
+}
+
+/// Join handle of a service spawned by the [`ServiceFactory`].
+pub struct ServiceHandle {
+    pub kind: ServiceKind,
+    pub handle: JoinHandle<Result<()>>,
+}
+
+type SpawnFuture = Pin<Box<dyn Future<Output = Result<JoinHandle<Result<()>>>> + Send>>;
+type Spawner = Box<dyn Fn(config::DonetConfig, Shutdown) -> SpawnFuture + Send + Sync>;
+
+/// Starts the services enabled in the daemon configuration.
+///
+/// Each [`ServiceKind`] is backed by a [`DonetService`] implementation
+/// registered with [`Self::register`]. The daemon binary registers the
+/// services it was built with, so configured services that are not
+/// registered can be reported with [`Self::unregistered`].
+#[derive(Default)]
+pub struct ServiceFactory {
+    spawners: HashMap<ServiceKind, Spawner>,
+}
+
+impl ServiceFactory {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers the service implementation for the given kind,
+    /// replacing any previous registration.
+    pub fn register<S: DonetService + 'static>(&mut self, kind: ServiceKind) {
+        // TODO: Pass the DC file to services that require it.
+        let spawner: Spawner = Box::new(|conf, shutdown| Box::pin(S::start(conf, None, shutdown)));
+        self.spawners.insert(kind, spawner);
+    }
+
+    pub fn is_registered(&self, kind: ServiceKind) -> bool {
+        self.spawners.contains_key(&kind)
+    }
+
+    /// Returns the services that are configured but not registered.
+    pub fn unregistered(&self, conf: &config::DonetConfig) -> Vec<ServiceKind> {
+        ServiceKind::ALL
+            .into_iter()
+            .filter(|kind| kind.is_configured(&conf.services) && !self.is_registered(*kind))
+            .collect()
+    }
+
+    /// Starts every registered service that has a section in the
+    /// configuration, and returns their join handles. Services are
+    /// notified of shutdown by the given [`ShutdownCoordinator`].
+    ///
+    /// If a service fails to start, the services already
+    /// started are aborted and the error is returned.
+    pub async fn spawn_from_config(
+        &self,
+        conf: &config::DonetConfig,
+        coordinator: &ShutdownCoordinator,
+    ) -> Result<Vec<ServiceHandle>> {
+        let mut handles: Vec<ServiceHandle> = vec![];
+
+        for kind in ServiceKind::ALL {
+            let Some(spawner) = self.spawners.get(&kind) else {
+                continue;
+            };
+            if !kind.is_configured(&conf.services) {
+                continue;
+            }
+            info!("Booting {} service.", kind);
+
+            match spawner(conf.clone(), coordinator.subscribe()).await {
+                Ok(handle) => handles.push(ServiceHandle { kind, handle }),
+                Err(err) => {
+                    for started in &handles {
+                        started.handle.abort();
+                    }
+                    return Err(err);
+                }
+            }
+        }
+        Ok(handles)
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use super::set_future_return_type;
-    use std::io::Result;
+    use super::*;
+    use crate::config::DonetConfig;
+    use crate::shutdown::{ServiceStop, StopStatus};
+    use std::time::Duration;
 
     #[test]
     fn test_future_return_type_util() {
@@ -94,4 +236,125 @@ mod tests {
         // Need this test to have test coverage on this file.
         set_future_return_type::<Result<()>, _>(&test_future);
     }
+
+    /// Service that idles until shutdown, for testing the factory.
+    struct MockService;
+
+    impl DonetService for MockService {
+        type Service = ();
+        type Configuration = ();
+
+        async fn create(_: (), _: Option<DCFile>) -> Result<Arc<Mutex<()>>> {
+            Ok(Arc::new(Mutex::new(())))
+        }
+
+        async fn start(
+            _: config::DonetConfig,
+            dc: Option<DCFile>,
+            shutdown: Shutdown,
+        ) -> Result<JoinHandle<Result<()>>> {
+            let service: Arc<Mutex<()>> = Self::create((), dc).await?;
+            Ok(Self::spawn_async_task(Self::main(service, shutdown)))
+        }
+
+        async fn main(_: Arc<Mutex<()>>, mut shutdown: Shutdown) -> Result<()> {
+            shutdown.wait().await;
+            Ok(())
+        }
+    }
+
+    /// Service that always fails to start.
+    struct FailingService;
+
+    impl DonetService for FailingService {
+        type Service = ();
+        type Configuration = ();
+
+        async fn create(_: (), _: Option<DCFile>) -> Result<Arc<Mutex<()>>> {
+            Err(std::io::Error::other("failed to bind"))
+        }
+
+        async fn start(
+            _: config::DonetConfig,
+            dc: Option<DCFile>,
+            shutdown: Shutdown,
+        ) -> Result<JoinHandle<Result<()>>> {
+            let service: Arc<Mutex<()>> = Self::create((), dc).await?;
+            Ok(Self::spawn_async_task(Self::main(service, shutdown)))
+        }
+
+        async fn main(_: Arc<Mutex<()>>, _: Shutdown) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    const TWO_ROLES_TOML: &str = r#"
+        [daemon]
+        name = "Donet"
+
+        [global]
+
+        [services.message_director]
+        bind = "127.0.0.1:7199"
+
+        [services.event_logger]
+        bind = "127.0.0.1:7197"
+        output = "/var/log/donet/"
+        log_format = "el-%Y-%m-%d-%H-%M-%S.log"
+        rotate_interval = "1d"
+    "#;
+
+    #[tokio::test]
+    async fn spawn_configured_services() {
+        let conf: DonetConfig = toml::from_str(TWO_ROLES_TOML).unwrap();
+
+        let mut factory = ServiceFactory::new();
+        factory.register::<MockService>(ServiceKind::EventLogger);
+        factory.register::<MockService>(ServiceKind::MessageDirector);
+        // registered, but not in the configuration
+        factory.register::<MockService>(ServiceKind::StateServer);
+
+        assert!(factory.unregistered(&conf).is_empty());
+
+        let coordinator = ShutdownCoordinator::new(Duration::from_secs(1));
+        let handles: Vec<ServiceHandle> = factory.spawn_from_config(&conf, &coordinator).await.unwrap();
+        let kinds: Vec<ServiceKind> = handles.iter().map(|h| h.kind).collect();
+
+        assert_eq!(
+            kinds,
+            vec![ServiceKind::MessageDirector, ServiceKind::EventLogger]
+        );
+        let stops: Vec<ServiceStop> = coordinator.shutdown(handles).await;
+
+        assert_eq!(stops.len(), 2);
+        assert!(stops.iter().all(|stop| matches!(stop.status, StopStatus::Clean)));
+    }
+
+    #[tokio::test]
+    async fn unregistered_and_failing_services() {
+        let conf: DonetConfig = toml::from_str(TWO_ROLES_TOML).unwrap();
+
+        let coordinator = ShutdownCoordinator::new(Duration::from_secs(1));
+
+        let mut factory = ServiceFactory::new();
+        assert_eq!(
+            factory.unregistered(&conf),
+            vec![ServiceKind::MessageDirector, ServiceKind::EventLogger]
+        );
+        assert!(factory
+            .spawn_from_config(&conf, &coordinator)
+            .await
+            .unwrap()
+            .is_empty());
+
+        factory.register::<MockService>(ServiceKind::MessageDirector);
+        factory.register::<FailingService>(ServiceKind::EventLogger);
+
+        let err = factory
+            .spawn_from_config(&conf, &coordinator)
+            .await
+            .err()
+            .unwrap();
+        assert_eq!(err.to_string(), "failed to bind");
+    }
 }
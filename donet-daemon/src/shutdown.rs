@@ -0,0 +1,213 @@
+/*
+    This file is part of Donet.
+
+    Copyright © 2024 Max Rodriguez <me@maxrdz.com>
+
+    Donet is free software; you can redistribute it and/or modify
+    it under the terms of the GNU Affero General Public License,
+    as published by the Free Software Foundation, either version 3
+    of the License, or (at your option) any later version.
+
+    Donet is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+    GNU Affero General Public License for more details.
+
+    You should have received a copy of the GNU Affero General Public
+    License along with Donet. If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! Graceful shutdown of the services spawned by the daemon.
+//!
+//! On shutdown, the [`ShutdownCoordinator`] notifies every service
+//! through its [`Shutdown`] listener, so that services can flush and
+//! close their resources and return from their main loop. Services
+//! that have not stopped once the grace period is over are aborted.
+
+use crate::service::{ServiceHandle, ServiceKind};
+use std::io::{Error, Result};
+use std::time::Duration;
+use tokio::sync::watch;
+use tokio::time::Instant;
+
+/// Time given to services to stop on daemon shutdown.
+pub static GRACE_PERIOD: Duration = Duration::from_secs(5);
+
+/// Listener given to each service, notified when the daemon shuts down.
+#[derive(Clone)]
+pub struct Shutdown {
+    rx: watch::Receiver<bool>,
+}
+
+impl Shutdown {
+    /// Completes once shutdown has been requested.
+    pub async fn wait(&mut self) {
+        // If the coordinator was dropped, no shutdown will be
+        // requested, so this waits for the task to be aborted.
+        if self.rx.wait_for(|requested| *requested).await.is_err() {
+            std::future::pending::<()>().await;
+        }
+    }
+
+    pub fn is_requested(&self) -> bool {
+        *self.rx.borrow()
+    }
+}
+
+/// How a service stopped on daemon shutdown.
+#[derive(Debug)]
+pub enum StopStatus {
+    /// The service's main loop returned successfully.
+    Clean,
+    /// The service's main loop returned an error, or panicked.
+    Failed(Error),
+    /// The service did not stop within the grace period.
+    Aborted,
+}
+
+/// Result of stopping a single service.
+#[derive(Debug)]
+pub struct ServiceStop {
+    pub kind: ServiceKind,
+    pub status: StopStatus,
+}
+
+pub struct ShutdownCoordinator {
+    tx: watch::Sender<bool>,
+    grace_period: Duration,
+}
+
+impl ShutdownCoordinator {
+    /// Services are given `grace_period` to stop once notified.
+    pub fn new(grace_period: Duration) -> Self {
+        let (tx, _) = watch::channel(false);
+        Self { tx, grace_period }
+    }
+
+    /// Returns a new listener to be given to a service.
+    pub fn subscribe(&self) -> Shutdown {
+        Shutdown {
+            rx: self.tx.subscribe(),
+        }
+    }
+
+    /// Notifies all services to stop, and waits for them to do so. Services
+    /// that are still running once the grace period is over are aborted.
+    pub async fn shutdown(self, services: Vec<ServiceHandle>) -> Vec<ServiceStop> {
+        self.tx.send_replace(true);
+
+        let deadline: Instant = Instant::now() + self.grace_period;
+        let mut stops: Vec<ServiceStop> = vec![];
+
+        for mut service in services {
+            let status: StopStatus = match tokio::time::timeout_at(deadline, &mut service.handle).await {
+                Ok(Ok(Ok(()))) => StopStatus::Clean,
+                Ok(Ok(Err(err))) => StopStatus::Failed(err),
+                Ok(Err(join_err)) => StopStatus::Failed(Error::other(join_err)),
+                Err(_) => {
+                    service.handle.abort();
+                    StopStatus::Aborted
+                }
+            };
+            stops.push(ServiceStop {
+                kind: service.kind,
+                status,
+            });
+        }
+        stops
+    }
+}
+
+/// Waits until the daemon is asked to shut down, either by an
+/// interrupt (Ctrl + C) or, on Unix, by `SIGTERM`, which is sent
+/// by service managers and `docker stop`.
+pub async fn wait_for_signal() -> Result<()> {
+    cfg_if! {
+        if #[cfg(unix)] {
+            use tokio::signal::unix::{signal, Signal, SignalKind};
+
+            let mut terminate: Signal = signal(SignalKind::terminate())?;
+
+            tokio::select! {
+                res = tokio::signal::ctrl_c() => {
+                    res?;
+                    println!();
+                    log::info!("Received interrupt (Ctrl + C)");
+                }
+                _ = terminate.recv() => log::info!("Received termination signal."),
+            }
+        } else {
+            tokio::signal::ctrl_c().await?;
+            println!();
+            log::info!("Received interrupt (Ctrl + C)");
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::future::Future;
+    use tokio::task::JoinHandle;
+
+    fn spawn(kind: ServiceKind, task: impl Future<Output = Result<()>> + Send + 'static) -> ServiceHandle {
+        let handle: JoinHandle<Result<()>> = tokio::spawn(task);
+        ServiceHandle { kind, handle }
+    }
+
+    #[tokio::test]
+    async fn services_stop_on_shutdown() {
+        let coordinator = ShutdownCoordinator::new(Duration::from_millis(200));
+
+        let mut md_shutdown: Shutdown = coordinator.subscribe();
+        let mut el_shutdown: Shutdown = coordinator.subscribe();
+        let stubborn_shutdown: Shutdown = coordinator.subscribe();
+        assert!(!md_shutdown.is_requested());
+
+        let services: Vec<ServiceHandle> = vec![
+            spawn(ServiceKind::MessageDirector, async move {
+                md_shutdown.wait().await;
+                Ok(())
+            }),
+            spawn(ServiceKind::EventLogger, async move {
+                el_shutdown.wait().await;
+                Err(Error::other("failed to flush log"))
+            }),
+            // never checks its shutdown listener
+            spawn(ServiceKind::StateServer, async move {
+                let _shutdown: Shutdown = stubborn_shutdown;
+                std::future::pending().await
+            }),
+        ];
+
+        let stops: Vec<ServiceStop> = coordinator.shutdown(services).await;
+
+        assert_eq!(stops[0].kind, ServiceKind::MessageDirector);
+        assert!(matches!(stops[0].status, StopStatus::Clean));
+        assert_eq!(stops[1].kind, ServiceKind::EventLogger);
+        assert!(matches!(&stops[1].status, StopStatus::Failed(e) if e.to_string() == "failed to flush log"));
+        assert_eq!(stops[2].kind, ServiceKind::StateServer);
+        assert!(matches!(stops[2].status, StopStatus::Aborted));
+    }
+
+    #[tokio::test]
+    async fn listeners_see_shutdown_request() {
+        let coordinator = ShutdownCoordinator::new(Duration::from_secs(1));
+
+        let shutdown: Shutdown = coordinator.subscribe();
+        let mut cloned: Shutdown = shutdown.clone();
+        assert!(!shutdown.is_requested());
+
+        let services: Vec<ServiceHandle> = vec![spawn(ServiceKind::MessageDirector, async move {
+            cloned.wait().await;
+            panic!("service panicked while stopping");
+        })];
+
+        let stops: Vec<ServiceStop> = coordinator.shutdown(services).await;
+
+        // listeners that are checked after shutdown still see the request
+        assert!(shutdown.is_requested());
+        assert!(matches!(stops[0].status, StopStatus::Failed(_)));
+    }
+}
@@ -20,6 +20,7 @@
 use donet_core::globals;
 use donet_daemon::config;
 use donet_daemon::service::*;
+use donet_daemon::shutdown::Shutdown;
 use log::{error, info};
 use mysql::prelude::*;
 use mysql::*;
@@ -65,7 +66,7 @@ struct Field {
 }
 
 pub struct DatabaseServer {
-    dc_file: DCFile<'static>,
+    dc_file: DCFile,
     _sql_pool: Pool,
     sql_conn: PooledConn,
     _credentials: DBCredentials,
@@ -75,10 +76,7 @@ impl DonetService for DatabaseServer {
     type Service = Self;
     type Configuration = config::DBServer;
 
-    async fn create(
-        conf: Self::Configuration,
-        dc: Option<DCFile<'static>>,
-    ) -> Result<Arc<Mutex<Self::Service>>> {
+    async fn create(conf: Self::Configuration, dc: Option<DCFile>) -> Result<Arc<Mutex<Self::Service>>> {
         // TODO: Check for db backend type once we have multiple DB backend support.
         let sql_config: config::SQL;
         let host_port: Vec<&str>;
@@ -156,7 +154,11 @@ impl DonetService for DatabaseServer {
         })))
     }
 
-    async fn start(conf: config::DonetConfig, dc: Option<DCFile<'static>>) -> Result<JoinHandle<Result<()>>> {
+    async fn start(
+        conf: config::DonetConfig,
+        dc: Option<DCFile>,
+        shutdown: Shutdown,
+    ) -> Result<JoinHandle<Result<()>>> {
         // NOTE: We are unwrapping an Option without checking,
         // as this method can only be called if 'database_server'
         // is of a 'Some' type, which guarantees no panic scenario.
@@ -165,11 +167,11 @@ impl DonetService for DatabaseServer {
         let service = DatabaseServer::create(db_server_conf, dc).await?;
 
         Ok(Self::spawn_async_task(async move {
-            DatabaseServer::main(service).await
+            DatabaseServer::main(service, shutdown).await
         }))
     }
 
-    async fn main(service: Arc<Mutex<Self::Service>>) -> Result<()> {
+    async fn main(service: Arc<Mutex<Self::Service>>, _: Shutdown) -> Result<()> {
         let mut locked_service = service.lock().await;
 
         locked_service.check_database_tables().unwrap(); // FIXME
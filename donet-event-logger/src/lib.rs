@@ -25,6 +25,7 @@ use donet_core::datagram::iterator::DatagramIterator;
 use donet_daemon::config;
 use donet_daemon::event::LoggedEvent;
 use donet_daemon::service::*;
+use donet_daemon::shutdown::Shutdown;
 use donet_network::udp;
 use log::{debug, error, info, trace};
 use regex::Regex;
@@ -36,6 +37,9 @@ use tokio::io::AsyncWriteExt;
 use tokio::sync::Mutex;
 use tokio::task::JoinHandle;
 
+/// Maximum number of bytes of a bad datagram to include in an error log.
+const LOGGED_DATAGRAM_BYTES: usize = 64;
+
 /// Interval unit types for log rotation intervals.
 #[derive(Debug, PartialEq, Eq)]
 pub enum IntervalUnit {
@@ -64,10 +68,7 @@ impl DonetService for EventLogger {
     type Service = Self;
     type Configuration = config::EventLogger;
 
-    async fn create(
-        mut conf: Self::Configuration,
-        _: Option<DCFile<'static>>,
-    ) -> Result<Arc<Mutex<Self::Service>>> {
+    async fn create(mut conf: Self::Configuration, _: Option<DCFile>) -> Result<Arc<Mutex<Self::Service>>> {
         Ok(Arc::new(Mutex::new(Self {
             binding: udp::Socket::bind(&conf.bind).await?,
             log_format: {
@@ -83,18 +84,22 @@ impl DonetService for EventLogger {
         })))
     }
 
-    async fn start(conf: config::DonetConfig, _: Option<DCFile<'static>>) -> Result<JoinHandle<Result<()>>> {
+    async fn start(
+        conf: config::DonetConfig,
+        _: Option<DCFile>,
+        shutdown: Shutdown,
+    ) -> Result<JoinHandle<Result<()>>> {
         // We can unwrap safely here since this function only is called if it is `Some`.
         let service_conf = conf.services.event_logger.unwrap();
 
         let service = EventLogger::create(service_conf, None).await?;
 
-        Ok(Self::spawn_async_task(
-            async move { EventLogger::main(service).await },
-        ))
+        Ok(Self::spawn_async_task(async move {
+            EventLogger::main(service, shutdown).await
+        }))
     }
 
-    async fn main(service: Arc<Mutex<Self::Service>>) -> Result<()> {
+    async fn main(service: Arc<Mutex<Self::Service>>, mut shutdown: Shutdown) -> Result<()> {
         let mut service_lock = service.lock().await;
 
         service_lock.open_log().await?;
@@ -122,7 +127,10 @@ impl DonetService for EventLogger {
         }
 
         loop {
-            let (len, addr) = service_lock.binding.socket.recv_from(&mut buffer).await?;
+            let (len, addr) = tokio::select! {
+                received = service_lock.binding.socket.recv_from(&mut buffer) => received?,
+                _ = shutdown.wait() => break,
+            };
             trace!("Got packet from {}.", addr);
 
             dg = Datagram::default();
@@ -147,11 +155,22 @@ impl DonetService for EventLogger {
             match service_lock.process_datagram(addr, &mut data, &mut dgi).await {
                 Ok(txt) => txt,
                 Err(err) => {
-                    error!("Failed to process datagram from {}: {}", addr, err);
+                    error!(
+                        "Failed to process datagram from {}: {}\n{}",
+                        addr,
+                        err,
+                        dg.hex_dump_capped(LOGGED_DATAGRAM_BYTES)
+                    );
                     continue;
                 }
             };
         }
+        // make sure all logged events are written before closing the log
+        if let Some(file) = service_lock.log_file.lock().await.as_mut() {
+            file.flush().await?;
+        }
+        info!("Event Logger stopped.");
+        Ok(())
     }
 }
 
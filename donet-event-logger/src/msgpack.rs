@@ -17,7 +17,6 @@
     License along with Donet. If not, see <https://www.gnu.org/licenses/>.
 */
 
-use donet_core::datagram::byte_order;
 use donet_core::datagram::iterator::*;
 
 #[rustfmt::skip]
@@ -105,6 +104,8 @@ fn decode_ext(out: &mut String, dgi: &mut DatagramIterator, len: u32) -> Result<
 ///
 /// [`MsgPack`]: https://msgpack.org
 pub fn decode_to_json(out: &mut String, dgi: &mut DatagramIterator) -> Result<(), IteratorError> {
+    // MsgPack values are big-endian, while the iterator reads little-endian
+    // on every host, so multi-byte values are always byte swapped.
     let marker: u8 = dgi.read_u8()?;
 
     if marker < 0x80 {
@@ -138,11 +139,11 @@ pub fn decode_to_json(out: &mut String, dgi: &mut DatagramIterator) -> Result<()
     } else if marker == 0xc5 {
         // bin16
         let len: u16 = dgi.read_u16()?;
-        decode_string(out, dgi, byte_order::swap_be_16(len).into())?;
+        decode_string(out, dgi, u16::swap_bytes(len).into())?;
     } else if marker == 0xc6 {
         // bin32
         let len: u32 = dgi.read_u32()?;
-        decode_string(out, dgi, byte_order::swap_be_32(len))?;
+        decode_string(out, dgi, u32::swap_bytes(len))?;
     } else if marker == 0xc7 {
         // ext8
         let len: u8 = dgi.read_u8()?;
@@ -150,43 +151,43 @@ pub fn decode_to_json(out: &mut String, dgi: &mut DatagramIterator) -> Result<()
     } else if marker == 0xc8 {
         // ext16
         let len: u16 = dgi.read_u16()?;
-        decode_ext(out, dgi, byte_order::swap_be_16(len).into())?;
+        decode_ext(out, dgi, u16::swap_bytes(len).into())?;
     } else if marker == 0xc9 {
         // ext32
         let len: u32 = dgi.read_u32()?;
-        decode_ext(out, dgi, byte_order::swap_be_32(len))?;
+        decode_ext(out, dgi, u32::swap_bytes(len))?;
     } else if marker == 0xca {
         // float32
         let data: u32 = dgi.read_u32()?;
-        out.push_str(&format!("{}", byte_order::swap_be_32(data) as f32));
+        out.push_str(&format!("{}", f32::from_bits(u32::swap_bytes(data))));
     } else if marker == 0xcb {
         // float64
         let data: u64 = dgi.read_u64()?;
-        out.push_str(&format!("{}", byte_order::swap_be_64(data) as f64));
+        out.push_str(&format!("{}", f64::from_bits(u64::swap_bytes(data))));
     } else if marker == 0xcc {
         // uint8
         out.push_str(&format!("{}", dgi.read_u8()?));
     } else if marker == 0xcd {
         // uint16
-        out.push_str(&format!("{}", byte_order::swap_be_16(dgi.read_u16()?)));
+        out.push_str(&format!("{}", u16::swap_bytes(dgi.read_u16()?)));
     } else if marker == 0xce {
         // uint32
-        out.push_str(&format!("{}", byte_order::swap_be_32(dgi.read_u32()?)));
+        out.push_str(&format!("{}", u32::swap_bytes(dgi.read_u32()?)));
     } else if marker == 0xcf {
         // uint64
-        out.push_str(&format!("{}", byte_order::swap_be_64(dgi.read_u64()?)));
+        out.push_str(&format!("{}", u64::swap_bytes(dgi.read_u64()?)));
     } else if marker == 0xd0 {
         // int8
         out.push_str(&format!("{}", dgi.read_i8()?));
     } else if marker == 0xd1 {
         // int16
-        out.push_str(&format!("{}", byte_order::swap_be_16(dgi.read_u16()?) as i16));
+        out.push_str(&format!("{}", u16::swap_bytes(dgi.read_u16()?) as i16));
     } else if marker == 0xd2 {
         // int32
-        out.push_str(&format!("{}", byte_order::swap_be_32(dgi.read_u32()?) as i32));
+        out.push_str(&format!("{}", u32::swap_bytes(dgi.read_u32()?) as i32));
     } else if marker == 0xd3 {
         // int64
-        out.push_str(&format!("{}", byte_order::swap_be_64(dgi.read_u64()?) as i64));
+        out.push_str(&format!("{}", u64::swap_bytes(dgi.read_u64()?) as i64));
     } else if marker <= 0xd8 {
         // fixext family
         decode_ext(out, dgi, 1 << (marker - 0xd4))?;
@@ -197,27 +198,27 @@ pub fn decode_to_json(out: &mut String, dgi: &mut DatagramIterator) -> Result<()
     } else if marker == 0xda {
         // str16
         let len: u16 = dgi.read_u16()?;
-        decode_string(out, dgi, byte_order::swap_be_16(len).into())?;
+        decode_string(out, dgi, u16::swap_bytes(len).into())?;
     } else if marker == 0xdb {
         // str32
         let len: u32 = dgi.read_u32()?;
-        decode_string(out, dgi, byte_order::swap_be_32(len))?;
+        decode_string(out, dgi, u32::swap_bytes(len))?;
     } else if marker == 0xdc {
         // array16
         let len: u16 = dgi.read_u16()?;
-        decode_container(out, dgi, byte_order::swap_be_16(len).into(), false)?;
+        decode_container(out, dgi, u16::swap_bytes(len).into(), false)?;
     } else if marker == 0xdd {
         // array32
         let len: u32 = dgi.read_u32()?;
-        decode_container(out, dgi, byte_order::swap_be_32(len), false)?;
+        decode_container(out, dgi, u32::swap_bytes(len), false)?;
     } else if marker == 0xde {
         // map16
         let len: u16 = dgi.read_u16()?;
-        decode_container(out, dgi, byte_order::swap_be_16(len).into(), true)?;
+        decode_container(out, dgi, u16::swap_bytes(len).into(), true)?;
     } else if marker == 0xdf {
         // map32
         let len: u32 = dgi.read_u32()?;
-        decode_container(out, dgi, byte_order::swap_be_32(len), true)?;
+        decode_container(out, dgi, u32::swap_bytes(len), true)?;
     } else {
         // everything >= 0xe0 is a negative fixint.
         out.push_str(&format!("{}", marker as i8));
@@ -293,13 +294,13 @@ mod tests {
 
         dg.add_data(vec![0x90 + 0x2])?; // fixarray (2)
         dg.add_data(vec![0xca])?; // float32
-        dg.add_f32(f32::MAX)?; // value
+        dg.add_data(1.5_f32.to_be_bytes().to_vec())?; // value
         dg.add_data(vec![0xcb])?; // float64
-        dg.add_f64(f64::MAX)?; // value
+        dg.add_data((-0.25_f64).to_be_bytes().to_vec())?; // value
 
         decode_to_json(&mut output, &mut DatagramIterator::from(dg))?;
 
-        assert_eq!(output.as_str(), "[4294967300, 18446744073709552000]");
+        assert_eq!(output.as_str(), "[1.5, -0.25]");
         Ok(())
     }
 }
@@ -30,17 +30,65 @@ use tokio::sync::MutexGuard;
 
 /// Iterates over all ranges in a [`rangemap::RangeInclusiveMap`],
 /// and filters out ranges that do NOT overlap with the given
-/// `target` range.
+/// `target` range. Both ends of the target range are inclusive.
 fn equal_range(
     map: &RangeInclusiveMap<Channel, HashSet<SubscriberRef>>,
-    target: Range<Channel>,
+    target: RangeInclusive<Channel>,
 ) -> Vec<(&RangeInclusive<Channel>, &HashSet<SubscriberRef>)> {
-    map.iter()
-        .filter(|(range, _)| {
-            // Check if the range overlaps with the target range
-            range.start() < &target.end && range.end() > &target.start
-        })
-        .collect()
+    map.overlapping(&target).collect()
+}
+
+/// Adds a subscriber to every channel in the given range of a range
+/// subscriptions map, keeping the subscribers of overlapping ranges.
+#[allow(clippy::mutable_key_type)] // hashed by remote address only
+fn add_to_ranges(
+    map: &mut RangeInclusiveMap<Channel, HashSet<SubscriberRef>>,
+    target: RangeInclusive<Channel>,
+    sub: &SubscriberRef,
+) {
+    let mut segments: Vec<(RangeInclusive<Channel>, HashSet<SubscriberRef>)> = vec![];
+
+    for (range, subs) in map.overlapping(&target) {
+        let start: Channel = std::cmp::max(*range.start(), *target.start());
+        let end: Channel = std::cmp::min(*range.end(), *target.end());
+
+        let mut subs: HashSet<SubscriberRef> = subs.clone();
+        subs.insert(sub.clone());
+        segments.push((start..=end, subs));
+    }
+    for gap in map.gaps(&target) {
+        segments.push((gap, HashSet::from([sub.clone()])));
+    }
+    for (range, subs) in segments {
+        map.insert(range, subs);
+    }
+}
+
+/// Removes a subscriber from every channel in the given range of a
+/// range subscriptions map. Ranges left without subscribers are removed.
+#[allow(clippy::mutable_key_type)] // hashed by remote address only
+fn remove_from_ranges(
+    map: &mut RangeInclusiveMap<Channel, HashSet<SubscriberRef>>,
+    target: RangeInclusive<Channel>,
+    sub: &SubscriberRef,
+) {
+    let mut segments: Vec<(RangeInclusive<Channel>, HashSet<SubscriberRef>)> = vec![];
+
+    for (range, subs) in map.overlapping(&target) {
+        let start: Channel = std::cmp::max(*range.start(), *target.start());
+        let end: Channel = std::cmp::min(*range.end(), *target.end());
+
+        let mut subs: HashSet<SubscriberRef> = subs.clone();
+        subs.remove(sub);
+        segments.push((start..=end, subs));
+    }
+    for (range, subs) in segments {
+        if subs.is_empty() {
+            map.remove(range);
+        } else {
+            map.insert(range, subs);
+        }
+    }
 }
 
 /// Data model to store all channel subscriptions created via a
@@ -126,21 +174,19 @@ where
             // Create a new closed interval set using given range
             let new_interval: IntervalSet<Channel> = vec![(min, max)].to_interval_set();
 
-            // Create a new set with the given subscriber
-            let mut new_sub_set: HashSet<SubscriberRef> = HashSet::default();
-            new_sub_set.insert(sub.clone());
-
             // Update channel range subscription mappings
             locked_sub.subscribed_ranges.extend(new_interval);
 
-            self.get_channel_map()
-                .range_subscriptions
-                .insert(RangeInclusive::new(min, max), new_sub_set);
+            add_to_ranges(
+                &mut self.get_channel_map().range_subscriptions,
+                RangeInclusive::new(min, max),
+                &sub,
+            );
         }
 
         // Finally, check if any part of this interval is a new range.
         // (Check if any range of this interval does NOT overlap an existing range.)
-        let new_range: Range<Channel> = min..max;
+        let new_range: RangeInclusive<Channel> = min..=max;
 
         // Get overlapping ranges from map's range subscription map.
         let interval_range: Vec<_> = equal_range(&self.get_channel_map().range_subscriptions, new_range);
@@ -165,10 +211,6 @@ where
             return;
         }
 
-        // prepare subscriber set
-        let mut sub_set: HashSet<SubscriberRef> = HashSet::default();
-        sub_set.insert(sub.clone());
-
         // Construct the interval we are removing, bounded to range subscriptions.
         let map: &mut ChannelMap = self.get_channel_map();
 
@@ -181,16 +223,18 @@ where
         let upper: Channel = *rs_last.0.end();
 
         let union_lower: Channel = std::cmp::max(min, lower);
-        let union_upper: Channel = std::cmp::max(max, upper);
-
-        let range: Range<Channel> = union_lower..union_upper;
+        let union_upper: Channel = std::cmp::min(max, upper);
 
+        // the given range does not overlap any range subscription
+        if union_lower > union_upper {
+            return;
+        }
         let i_set: IntervalSet<Channel> = vec![(union_lower, union_upper)].to_interval_set();
 
         // Speculate the channel ranges that will have no subscribers
         // after this subscriber is removed.
         let mut dead_ranges: IntervalSet<Channel> = i_set.clone();
-        let interval_range = equal_range(&map.range_subscriptions, union_lower..union_upper);
+        let interval_range = equal_range(&map.range_subscriptions, union_lower..=union_upper);
 
         // go through interval range and remove ranges that will still
         // have subscribers after this subscriber is removed
@@ -200,16 +244,21 @@ where
 
             if has_subscribers && !is_only_subscriber {
                 // we are not the last subscriber in this range, so don't delete it
-                dead_ranges = &dead_ranges - vec![(*range.start(), *range.end())].to_interval_set();
+                let live: IntervalSet<Channel> = vec![(*range.start(), *range.end())].to_interval_set();
+                dead_ranges = dead_ranges.difference(&live);
             }
         }
 
         // update range mappings on both subscriber and channel map
         let mut locked_sub: MutexGuard<'_, Subscriber> = sub.lock().await;
 
-        locked_sub.subscribed_ranges = &locked_sub.subscribed_ranges - i_set;
-        map.range_subscriptions
-            .remove(RangeInclusive::new(range.start, range.end));
+        // note that `-` on interval sets is interval arithmetic, not set difference
+        locked_sub.subscribed_ranges = locked_sub.subscribed_ranges.difference(&i_set);
+        remove_from_ranges(
+            &mut map.range_subscriptions,
+            RangeInclusive::new(union_lower, union_upper),
+            &sub,
+        );
 
         // clone subscriber's channel subscriptions to avoid double borrow
         let chans = locked_sub.subscribed_channels.clone();
@@ -269,36 +318,21 @@ where
     async fn remove_subscriber(&mut self, sub: SubscriberRef, chan: Channel) -> bool {
         let map: &mut ChannelMap = self.get_channel_map();
 
-        let locked_sub: MutexGuard<'_, Subscriber> = sub.lock().await;
-        let mut sub_count: usize = map.subscriptions.len();
-
-        if sub_count == 0 {
+        // Subscribers are compared by their remote address, so we do not
+        // lock them here. The caller may be holding the given sub's lock.
+        let Some(subscriptions) = map.subscriptions.get_vec_mut(&chan) else {
             return false;
+        };
+        let Some(index) = subscriptions.iter().position(|s| *s == sub) else {
+            return false;
+        };
+        subscriptions.swap_remove(index);
+
+        if subscriptions.is_empty() {
+            map.subscriptions.remove(&chan);
+            return true;
         }
-        for (key, subscriptions) in map.subscriptions.iter_all_mut() {
-            if *key != chan {
-                continue;
-            }
-            let mut index: usize = 0;
-            let mut found: bool = false;
-
-            for subscription in subscriptions.iter_mut() {
-                if *subscription.lock().await == *locked_sub {
-                    found = true;
-                    sub_count -= 1;
-                }
-                index += 1;
-            }
-            if found {
-                // The swap_remove() function requires a second mutable
-                // borrow of the vector which is illegal, so I had to work
-                // around this by using a `found` flag and performing the
-                // remove outside of the for loop which turns values into
-                // an iterator. This way we don't perform a second borrow.
-                subscriptions.swap_remove(index);
-            }
-        }
-        sub_count == 0
+        false
     }
 
     /// Checks if a given subscriber has a subscription on the given
@@ -414,4 +448,132 @@ mod tests {
         assert!(!mock.is_subscribed(&sub_lock, min - 1).await);
         assert!(!mock.is_subscribed(&sub_lock, max + 1).await);
     }
+
+    /// Returns the remote addresses of the subscribers of the given channels.
+    #[allow(clippy::mutable_key_type)]
+    fn recipients(mock: &mut MockChannelCoordinator, channels: Vec<Channel>) -> HashSet<SocketAddr> {
+        let mut subs: HashSet<SubscriberRef> = HashSet::default();
+        mock.lookup_channels(channels, &mut subs);
+
+        subs.iter().map(|sub| sub.get_remote()).collect()
+    }
+
+    #[tokio::test]
+    async fn route_to_overlapping_subscribers() {
+        let mut mock = MockChannelCoordinator::default();
+        let addr_1 = SocketAddr::from_str("127.0.0.1:1").unwrap();
+        let addr_2 = SocketAddr::from_str("127.0.0.1:2").unwrap();
+        let mock_sub_1 = SubscriberRef::from(addr_1);
+        let mock_sub_2 = SubscriberRef::from(addr_2);
+
+        // both subscribers share channel 2000
+        mock.subscribe_channel(mock_sub_1.clone(), 1000).await;
+        mock.subscribe_channel(mock_sub_1.clone(), 2000).await;
+        mock.subscribe_channel(mock_sub_2.clone(), 2000).await;
+        mock.subscribe_range(mock_sub_2.clone(), 3000, 4000).await;
+
+        assert_eq!(recipients(&mut mock, vec![1000]), HashSet::from([addr_1]));
+        assert_eq!(recipients(&mut mock, vec![2000]), HashSet::from([addr_1, addr_2]));
+        assert_eq!(recipients(&mut mock, vec![3500]), HashSet::from([addr_2]));
+        assert_eq!(recipients(&mut mock, vec![5000]), HashSet::default());
+
+        // a datagram with several recipients reaches each subscriber once
+        assert_eq!(
+            recipients(&mut mock, vec![1000, 2000, 3500]),
+            HashSet::from([addr_1, addr_2])
+        );
+
+        mock.unsubscribe_channel(mock_sub_1.clone(), 2000).await;
+        assert_eq!(recipients(&mut mock, vec![2000]), HashSet::from([addr_2]));
+
+        mock.unsubscribe_all(mock_sub_2.clone()).await;
+        assert_eq!(recipients(&mut mock, vec![2000, 3500]), HashSet::default());
+        assert_eq!(recipients(&mut mock, vec![1000]), HashSet::from([addr_1]));
+    }
+
+    #[tokio::test]
+    async fn overlapping_range_subscriptions() {
+        let mut mock = MockChannelCoordinator::default();
+        let addr_1 = SocketAddr::from_str("127.0.0.1:1").unwrap();
+        let addr_2 = SocketAddr::from_str("127.0.0.1:2").unwrap();
+        let mock_sub_1 = SubscriberRef::from(addr_1);
+        let mock_sub_2 = SubscriberRef::from(addr_2);
+
+        mock.subscribe_range(mock_sub_1.clone(), 100, 200).await;
+        mock.subscribe_range(mock_sub_2.clone(), 150, 300).await;
+
+        assert_eq!(recipients(&mut mock, vec![120]), HashSet::from([addr_1]));
+        assert_eq!(recipients(&mut mock, vec![175]), HashSet::from([addr_1, addr_2]));
+        assert_eq!(recipients(&mut mock, vec![250]), HashSet::from([addr_2]));
+
+        mock.unsubscribe_range(mock_sub_1.clone(), 100, 200).await;
+
+        assert!(!mock.is_subscribed(&mock_sub_1.lock().await, 175).await);
+        assert_eq!(recipients(&mut mock, vec![120]), HashSet::default());
+        assert_eq!(recipients(&mut mock, vec![175]), HashSet::from([addr_2]));
+        assert_eq!(recipients(&mut mock, vec![250]), HashSet::from([addr_2]));
+    }
+
+    #[tokio::test]
+    async fn range_boundary_channels() {
+        let mut mock = MockChannelCoordinator::default();
+        let addr_1 = SocketAddr::from_str("127.0.0.1:1").unwrap();
+        let mock_sub_1 = SubscriberRef::from(addr_1);
+
+        // both ends of a range are subscribed
+        mock.subscribe_range(mock_sub_1.clone(), 1000, 2000).await;
+
+        assert_eq!(recipients(&mut mock, vec![999]), HashSet::default());
+        assert_eq!(recipients(&mut mock, vec![1000]), HashSet::from([addr_1]));
+        assert_eq!(recipients(&mut mock, vec![2000]), HashSet::from([addr_1]));
+        assert_eq!(recipients(&mut mock, vec![2001]), HashSet::default());
+        assert!(mock.is_subscribed(&mock_sub_1.lock().await, 2000).await);
+
+        // a range of a single channel is a new range
+        let mut mock = MockChannelCoordinator::default();
+        mock.subscribe_range(mock_sub_1.clone(), 5000, 5000).await;
+
+        assert!(*mock.got_add_range.get_mut());
+        assert_eq!(recipients(&mut mock, vec![5000]), HashSet::from([addr_1]));
+    }
+
+    #[tokio::test]
+    async fn range_removal() {
+        let mut mock = MockChannelCoordinator::default();
+        let addr_1 = SocketAddr::from_str("127.0.0.1:1").unwrap();
+        let addr_2 = SocketAddr::from_str("127.0.0.1:2").unwrap();
+        let mock_sub_1 = SubscriberRef::from(addr_1);
+        let mock_sub_2 = SubscriberRef::from(addr_2);
+
+        mock.subscribe_range(mock_sub_1.clone(), 100, 300).await;
+
+        // removing the middle of a range keeps both of its ends
+        mock.unsubscribe_range(mock_sub_1.clone(), 150, 200).await;
+        assert!(*mock.got_remove_range.get_mut());
+
+        assert_eq!(recipients(&mut mock, vec![149]), HashSet::from([addr_1]));
+        assert_eq!(recipients(&mut mock, vec![150]), HashSet::default());
+        assert_eq!(recipients(&mut mock, vec![200]), HashSet::default());
+        assert_eq!(recipients(&mut mock, vec![201]), HashSet::from([addr_1]));
+
+        let sub_lock = mock_sub_1.lock().await;
+        assert!(mock.is_subscribed(&sub_lock, 149).await);
+        assert!(!mock.is_subscribed(&sub_lock, 175).await);
+        assert!(mock.is_subscribed(&sub_lock, 201).await);
+        drop(sub_lock);
+
+        // a range still covered by another subscriber is not a dead range
+        let mut mock = MockChannelCoordinator::default();
+        mock.subscribe_range(mock_sub_2.clone(), 100, 300).await;
+        mock.subscribe_range(mock_sub_1.clone(), 150, 200).await;
+        mock.unsubscribe_range(mock_sub_1.clone(), 150, 200).await;
+
+        assert!(!*mock.got_remove_range.get_mut());
+        assert_eq!(recipients(&mut mock, vec![175]), HashSet::from([addr_2]));
+
+        mock.unsubscribe_range(mock_sub_2.clone(), 100, 300).await;
+
+        assert!(*mock.got_remove_range.get_mut());
+        assert_eq!(recipients(&mut mock, vec![100, 175, 300]), HashSet::default());
+    }
 }
@@ -27,11 +27,13 @@ use donet_core::datagram::datagram::*;
 use donet_core::globals::*;
 use donet_core::Protocol;
 use donet_daemon::config;
+use donet_daemon::panic_hook;
 use donet_daemon::service::*;
+use donet_daemon::shutdown::Shutdown;
 use donet_network::{tcp, udp};
 use donet_network::{Client, HasClient, RecvData, RecvSendHandles};
 use log::{error, info, trace, warn};
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::io::{Error, ErrorKind, Result};
 use std::sync::Arc;
 use subscriber::*;
@@ -40,6 +42,9 @@ use tokio::sync::{mpsc, Mutex, MutexGuard};
 use tokio::task::JoinHandle;
 use upstream::*;
 
+/// Name of this service, as reported by the daemon's panic hook.
+const SERVICE_NAME: &str = "Message Director";
+
 /// Represents an internal protocol header.
 ///
 /// Includes sender/recipient routing identifiers.
@@ -72,19 +77,19 @@ pub struct MessageDirector {
     channel_map: ChannelMap,
     subscribers: HashSet<SubscriberRef>,
     removed_subscribers: HashSet<SubscriberRef>,
+    /// If set, new subscribers tally the message types they send.
+    message_stats: bool,
 }
 
 impl DonetService for MessageDirector {
     type Service = Self;
     type Configuration = CreateInfo;
 
-    async fn create(
-        conf: Self::Configuration,
-        _: Option<DCFile<'static>>,
-    ) -> Result<Arc<Mutex<Self::Service>>> {
+    async fn create(conf: Self::Configuration, _: Option<DCFile>) -> Result<Arc<Mutex<Self::Service>>> {
         let bind_addr: &str = conf.service_conf.bind.as_str();
         let upstream: Option<String> = conf.service_conf.upstream;
         let logger_uri: Option<String> = conf.event_logger_url;
+        let message_stats: bool = conf.service_conf.message_stats;
 
         Ok(Arc::new(Mutex::new(MessageDirector {
             binding: Arc::new(Mutex::new(tcp::Acceptor::bind(bind_addr).await?)),
@@ -115,10 +120,15 @@ impl DonetService for MessageDirector {
             channel_map: ChannelMap::default(),
             subscribers: HashSet::default(),
             removed_subscribers: HashSet::default(),
+            message_stats,
         })))
     }
 
-    async fn start(conf: config::DonetConfig, _: Option<DCFile<'static>>) -> Result<JoinHandle<Result<()>>> {
+    async fn start(
+        conf: config::DonetConfig,
+        _: Option<DCFile>,
+        shutdown: Shutdown,
+    ) -> Result<JoinHandle<Result<()>>> {
         let service_conf: CreateInfo = CreateInfo {
             // We can unwrap safely here since this function only is called if it is `Some`.
             service_conf: conf.services.message_director.expect("MD conf not found."),
@@ -128,11 +138,11 @@ impl DonetService for MessageDirector {
         let service = MessageDirector::create(service_conf, None).await?;
 
         Ok(Self::spawn_async_task(async move {
-            MessageDirector::main(service).await
+            MessageDirector::main(service, shutdown).await
         }))
     }
 
-    async fn main(service: Arc<Mutex<Self::Service>>) -> Result<()> {
+    async fn main(service: Arc<Mutex<Self::Service>>, mut shutdown: Shutdown) -> Result<()> {
         // create a new mpsc channel for receiving incoming packets
         let (tx, mut rx) = mpsc::channel::<RecvData>(100);
 
@@ -147,11 +157,15 @@ impl DonetService for MessageDirector {
             while let Some(recv_data) = rx.recv().await {
                 let mut locked_service = service_clone_for_recv.lock().await;
 
-                if let Err(e) = locked_service.handle_datagram(recv_data).await {
+                let handled: Result<()> = panic_hook::scope(locked_service.handle_datagram(recv_data)).await;
+
+                if let Err(e) = handled {
                     warn!("Failed to handle received datagram: {}", e);
                 }
             }
-            todo!("unhandled error. MD incoming datagram receiver returned None.")
+            // All senders were dropped, which only happens when the
+            // MD's main task is aborted on daemon shutdown.
+            Ok(())
         });
 
         // if we have an uplink connection, spawn send/receive tokio tasks
@@ -168,7 +182,11 @@ impl DonetService for MessageDirector {
         // start the main loop (accepting new TCP connections)
         loop {
             // here, we keep the TCP binding locked. only this loop needs it
-            match binding_lock.socket.accept().await {
+            let accepted = tokio::select! {
+                accepted = binding_lock.socket.accept() => accepted,
+                _ = shutdown.wait() => break,
+            };
+            match accepted {
                 Ok((socket, address)) => {
                     info!("Received incoming connection from {}.", address);
 
@@ -189,6 +207,11 @@ impl DonetService for MessageDirector {
                 Err(socket_err) => error!("Failed to get client: {}", socket_err),
             }
         }
+        // stop handling datagrams received from subscribers
+        handle.abort();
+
+        info!("Message Director stopped.");
+        Ok(())
     }
 }
 
@@ -228,7 +251,11 @@ impl MessageDirector {
     /// Allocates a new [`Subscriber`] in our hash set.
     async fn add_subscriber(&mut self, client: Client) -> Result<SubscriberRef> {
         // create a new [`Subscriber`] structure from the new client
-        let sub: Subscriber = Subscriber::new(client).await;
+        let mut sub: Subscriber = Subscriber::new(client).await;
+
+        if self.message_stats {
+            sub.enable_message_stats();
+        }
 
         // move new subscriber struct to the heap and keep smart pointer
         let sub_ptr: SubscriberRef = sub.into();
@@ -276,6 +303,16 @@ impl MessageDirector {
         }
     }
 
+    /// Returns how many of each message type were received from the
+    /// subscriber with the given remote address, or `None` if it is
+    /// not connected or message stats are not enabled.
+    pub async fn get_message_stats(&self, remote: SocketAddr) -> Option<HashMap<Protocol, u64>> {
+        let sub: SubscriberRef = self.get_subscriber_with_remote(remote)?;
+        let locked_sub: MutexGuard<'_, Subscriber> = sub.lock().await;
+
+        locked_sub.message_stats().cloned()
+    }
+
     /// Takes in a [`SocketAddr`], returns a [`SubscriberRef`] or `None`.
     ///
     /// Retrieval can be done by creating a dummy [`SubscriberRef`]
@@ -314,6 +351,7 @@ impl MessageDirector {
     /// or they can come from our upstream MD, if one is configured.
     async fn handle_datagram(&mut self, mut data: RecvData) -> Result<()> {
         trace!("Processing datagram of {} bytes...", data.dg.size());
+        panic_hook::set_context(SERVICE_NAME, None);
 
         let recp_count: u8 = data.dgi.read_recipient_count()?;
         trace!("Recipient count: {}", recp_count);
@@ -336,6 +374,16 @@ impl MessageDirector {
         // not a control msg, so there is a sender field ahead
         let sender: Channel = data.dgi.read_channel()?;
 
+        if self.message_stats {
+            // peek the message type, leaving the iterator where it was
+            let index: usize = data.dgi.tell();
+
+            if let Ok(msg_type) = data.dgi.read_msg_type() {
+                self.record_message(data.remote, msg_type).await;
+            }
+            data.dgi.seek(index)?;
+        }
+
         // Store internal header info into struct
         let header = InternalHeader { sender, recipients };
         trace!("Datagram internal header: {}", &header);
@@ -348,6 +396,9 @@ impl MessageDirector {
     /// and the recipient channel was the control channel (channel 1).
     async fn handle_control_msg(&mut self, mut data: RecvData) -> Result<()> {
         let msg_type: Protocol = data.dgi.read_msg_type()?;
+        panic_hook::set_context(SERVICE_NAME, Some(msg_type));
+
+        self.record_message(data.remote, msg_type).await;
 
         match msg_type {
             Protocol::MDAddChannel => {
@@ -481,6 +532,17 @@ impl MessageDirector {
         Ok(())
     }
 
+    /// Tallies the message type received from the subscriber with
+    /// the given remote address, if message stats are enabled.
+    async fn record_message(&self, remote: SocketAddr, msg_type: Protocol) {
+        if !self.message_stats {
+            return;
+        }
+        if let Some(sub) = self.get_subscriber_with_remote(remote) {
+            sub.lock().await.record_message(msg_type);
+        }
+    }
+
     /// Sends the post remove for the given sender by sending it
     /// upstream, if there is an upstream connection.
     async fn preroute_post_remove(&mut self, sender: Channel, post_remove: Datagram) {
@@ -527,3 +589,61 @@ impl MessageDirector {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use donet_core::datagram::iterator::DatagramIterator;
+
+    #[tokio::test]
+    async fn message_stats_of_subscriber() {
+        let conf: CreateInfo = CreateInfo {
+            service_conf: config::MessageDirector {
+                bind: String::from("127.0.0.1:7196"),
+                upstream: None,
+                message_stats: true,
+            },
+            event_logger_url: None,
+        };
+        let service: Arc<Mutex<MessageDirector>> = MessageDirector::create(conf, None).await.unwrap();
+        let mut md: MutexGuard<'_, MessageDirector> = service.lock().await;
+
+        // connect a subscriber to the MD's binding
+        let binding: Arc<Mutex<tcp::Acceptor>> = md.binding.clone();
+        let (connected, accepted) = tokio::join!(TcpStream::connect("127.0.0.1:7196"), async {
+            binding.lock().await.socket.accept().await
+        });
+        let _stream: TcpStream = connected.unwrap();
+        let (socket, remote) = accepted.unwrap();
+
+        md.add_subscriber(Client::from(socket)).await.unwrap();
+        assert_eq!(md.get_message_stats(remote).await, Some(HashMap::default()));
+
+        for msg_type in [
+            Protocol::MDAddChannel,
+            Protocol::MDAddChannel,
+            Protocol::MDRemoveChannel,
+        ] {
+            let mut dg: Datagram = Datagram::default();
+
+            dg.add_control_header(msg_type.into()).unwrap();
+            dg.add_channel(1000).unwrap();
+
+            let data: RecvData = RecvData {
+                remote,
+                dg: dg.clone(),
+                dgi: DatagramIterator::from(dg),
+            };
+            md.handle_datagram(data).await.unwrap();
+        }
+        let stats: HashMap<Protocol, u64> = md.get_message_stats(remote).await.unwrap();
+
+        assert_eq!(stats.len(), 2);
+        assert_eq!(stats[&Protocol::MDAddChannel], 2);
+        assert_eq!(stats[&Protocol::MDRemoveChannel], 1);
+
+        // subscribers that are not connected have no stats
+        let unknown: SocketAddr = "127.0.0.1:7195".parse().unwrap();
+        assert_eq!(md.get_message_stats(unknown).await, None);
+    }
+}
@@ -20,13 +20,14 @@
 use core::net::SocketAddr;
 use donet_core::datagram::datagram::*;
 use donet_core::globals::Channel;
+use donet_core::Protocol;
 use donet_network::Client;
 use donet_network::HasClient;
 use gcollections::ops::*;
 use interval::IntervalSet;
 use log::trace;
 use multimap::MultiMap;
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
 use tokio::sync::mpsc;
 use tokio::sync::{Mutex, MutexGuard};
@@ -136,6 +137,9 @@ pub struct Subscriber {
     /// Datagrams scheduled to be distributed upon
     /// this subscriber's unexpected disconnect.
     pub post_removes: MultiMap<Channel, Datagram>,
+    /// Tally of each message type received from this
+    /// subscriber. `None` unless enabled, for diagnostics.
+    message_stats: Option<HashMap<Protocol, u64>>,
 }
 
 /// Creates a new [`Subscriber`] from a [`SocketAddr`],
@@ -154,6 +158,7 @@ impl From<SocketAddr> for Subscriber {
             subscribed_channels: HashSet::default(),
             subscribed_ranges: IntervalSet::empty(),
             post_removes: MultiMap::default(),
+            message_stats: None,
         }
     }
 }
@@ -183,6 +188,7 @@ impl Subscriber {
             subscribed_channels: HashSet::default(),
             subscribed_ranges: IntervalSet::empty(),
             post_removes: MultiMap::default(),
+            message_stats: None,
         }
     }
 
@@ -206,6 +212,25 @@ impl Subscriber {
         locked_client.stage_datagram(dg.clone()).await
     }
 
+    /// Starts tallying the message types received from this subscriber.
+    pub fn enable_message_stats(&mut self) {
+        self.message_stats.get_or_insert_with(HashMap::default);
+    }
+
+    /// Counts a message of the given type as received from this
+    /// subscriber. Does nothing if message stats are not enabled.
+    pub fn record_message(&mut self, msg_type: Protocol) {
+        if let Some(stats) = &mut self.message_stats {
+            *stats.entry(msg_type).or_insert(0) += 1;
+        }
+    }
+
+    /// Returns how many of each message type were received from
+    /// this subscriber, or `None` if message stats are not enabled.
+    pub fn message_stats(&self) -> Option<&HashMap<Protocol, u64>> {
+        self.message_stats.as_ref()
+    }
+
     pub async fn receive_disconnect(&mut self) {
         // TODO!
     }
@@ -214,3 +239,36 @@ impl Subscriber {
         // TODO!
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tally_message_stats() {
+        let mut sub: Subscriber = "127.0.0.1:7200".parse::<SocketAddr>().unwrap().into();
+
+        // disabled by default
+        sub.record_message(Protocol::MDAddChannel);
+        assert_eq!(sub.message_stats(), None);
+
+        sub.enable_message_stats();
+
+        for msg_type in [
+            Protocol::MDAddChannel,
+            Protocol::MDAddChannel,
+            Protocol::MDRemoveChannel,
+            Protocol::MDAddChannel,
+            Protocol::MDSetConName,
+        ] {
+            sub.record_message(msg_type);
+        }
+        let stats: &HashMap<Protocol, u64> = sub.message_stats().unwrap();
+
+        assert_eq!(stats.len(), 3);
+        assert_eq!(stats[&Protocol::MDAddChannel], 3);
+        assert_eq!(stats[&Protocol::MDRemoveChannel], 1);
+        assert_eq!(stats[&Protocol::MDSetConName], 1);
+        assert_eq!(stats.get(&Protocol::MDAddRange), None);
+    }
+}
@@ -0,0 +1,308 @@
+/*
+    This file is part of Donet.
+
+    Copyright © 2024 Max Rodriguez <me@maxrdz.com>
+
+    Donet is free software; you can redistribute it and/or modify
+    it under the terms of the GNU Affero General Public License,
+    as published by the Free Software Foundation, either version 3
+    of the License, or (at your option) any later version.
+
+    Donet is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+    GNU Affero General Public License for more details.
+
+    You should have received a copy of the GNU Affero General Public
+    License along with Donet. If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! Framing of datagrams sent over TCP streams.
+//!
+//! Each datagram on the wire is a frame: a [`DgSizeTag`] with the
+//! length of the datagram, followed by the datagram's bytes. TCP is a
+//! byte stream, so a single read from the socket may hold part of a
+//! frame, or many frames; [`FrameDecoder`] reassembles them.
+
+use donet_core::datagram::datagram::Datagram;
+use donet_core::globals::DgSizeTag;
+use log::warn;
+use std::io::{Error, ErrorKind, Result};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+/// Number of bytes of the size tag at the start of every frame.
+const SIZE_TAG_LEN: usize = std::mem::size_of::<DgSizeTag>();
+
+/// Size of the buffer for each read from the socket.
+const READ_BUFFER_SIZE: usize = 64 * 1024; // 64 kb
+
+/// Reassembles datagrams from bytes received in arbitrary chunks.
+///
+/// Bytes of complete frames are not removed from the buffer one frame
+/// at a time; they are skipped over, and the buffer is compacted the
+/// next time more bytes are appended. This keeps decoding a read that
+/// holds many small frames linear in its size.
+#[derive(Debug, Default)]
+pub struct FrameDecoder {
+    buffer: Vec<u8>,
+    /// Offset in `buffer` of the first byte not yet decoded.
+    start: usize,
+}
+
+impl FrameDecoder {
+    /// Appends bytes received from the stream.
+    pub fn extend(&mut self, bytes: &[u8]) {
+        if self.start > 0 {
+            self.buffer.drain(..self.start);
+            self.start = 0;
+        }
+        self.buffer.extend_from_slice(bytes);
+    }
+
+    /// Returns the next complete datagram, or `None` if more
+    /// bytes are needed. Frames with a size tag of 0 are skipped.
+    pub fn next_datagram(&mut self) -> Option<Datagram> {
+        loop {
+            let remaining: &[u8] = &self.buffer[self.start..];
+
+            if remaining.len() < SIZE_TAG_LEN {
+                return None;
+            }
+            let size: usize = usize::from(DgSizeTag::from_le_bytes([remaining[0], remaining[1]]));
+
+            if remaining.len() < SIZE_TAG_LEN + size {
+                return None;
+            }
+            let frame: Vec<u8> = remaining[SIZE_TAG_LEN..SIZE_TAG_LEN + size].to_vec();
+            self.start += SIZE_TAG_LEN + size;
+
+            if size == 0 {
+                warn!("Received datagram with a size tag of 0. Skipping.");
+                continue;
+            }
+            return Some(frame.into());
+        }
+    }
+
+    /// Returns the number of bytes received that are
+    /// not yet part of a complete datagram.
+    pub fn pending(&self) -> usize {
+        self.buffer.len() - self.start
+    }
+}
+
+/// Reads framed datagrams from a stream, such as a TCP socket.
+pub struct FramedReader<R> {
+    reader: R,
+    decoder: FrameDecoder,
+    /// Read buffer, kept between reads so it is only allocated once.
+    buffer: Box<[u8]>,
+}
+
+impl<R: AsyncRead + Unpin> FramedReader<R> {
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            decoder: FrameDecoder::default(),
+            buffer: vec![0_u8; READ_BUFFER_SIZE].into_boxed_slice(),
+        }
+    }
+
+    /// Reads the next datagram, waiting for as many reads from the
+    /// stream as needed to receive the whole frame.
+    ///
+    /// Returns `None` once the stream is closed between frames. If
+    /// it is closed in the middle of a frame, an error of kind
+    /// [`ErrorKind::UnexpectedEof`] is returned.
+    pub async fn read_datagram(&mut self) -> Result<Option<Datagram>> {
+        loop {
+            if let Some(dg) = self.decoder.next_datagram() {
+                return Ok(Some(dg));
+            }
+            let len: usize = self.reader.read(&mut self.buffer).await?;
+
+            if len == 0 {
+                if self.decoder.pending() == 0 {
+                    return Ok(None);
+                }
+                return Err(Error::new(
+                    ErrorKind::UnexpectedEof,
+                    format!(
+                        "Stream closed with {} bytes of a datagram.",
+                        self.decoder.pending()
+                    ),
+                ));
+            }
+            self.decoder.extend(&self.buffer[..len]);
+        }
+    }
+
+    pub fn get_ref(&self) -> &R {
+        &self.reader
+    }
+}
+
+/// Writes datagrams to a stream as frames.
+pub struct FramedWriter<W> {
+    writer: W,
+}
+
+impl<W: AsyncWrite + Unpin> FramedWriter<W> {
+    pub fn new(writer: W) -> Self {
+        Self { writer }
+    }
+
+    /// Writes a single datagram as a frame, and flushes the stream.
+    pub async fn write_datagram(&mut self, dg: &Datagram) -> Result<()> {
+        self.write_datagrams(std::slice::from_ref(dg)).await
+    }
+
+    /// Writes the given datagrams as frames with a single write
+    /// to the stream, and flushes the stream.
+    pub async fn write_datagrams(&mut self, dgs: &[Datagram]) -> Result<()> {
+        let mut frames: Vec<u8> = vec![];
+
+        for dg in dgs {
+            let size: DgSizeTag = DgSizeTag::try_from(dg.size()).map_err(|_| {
+                Error::new(
+                    ErrorKind::InvalidInput,
+                    "Datagram is too large to fit in a size tag.",
+                )
+            })?;
+            frames.extend_from_slice(&size.to_le_bytes());
+            frames.extend_from_slice(dg.get_buffer());
+        }
+        self.writer.write_all(&frames).await?;
+        self.writer.flush().await
+    }
+
+    pub fn get_ref(&self) -> &W {
+        &self.writer
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tcp::Acceptor;
+    use tokio::net::TcpStream;
+
+    fn datagram(bytes: &[u8]) -> Datagram {
+        bytes.to_vec().into()
+    }
+
+    #[test]
+    fn decode_partial_frames() {
+        let mut decoder = FrameDecoder::default();
+
+        // a frame split across three reads
+        decoder.extend(&[3]);
+        assert_eq!(decoder.next_datagram(), None);
+        decoder.extend(&[0, 0xaa]);
+        assert_eq!(decoder.next_datagram(), None);
+
+        // the end of the first frame, an empty frame, and a whole frame
+        decoder.extend(&[0xbb, 0xcc, 0, 0, 1, 0, 0xdd]);
+        assert_eq!(decoder.next_datagram(), Some(datagram(&[0xaa, 0xbb, 0xcc])));
+        assert_eq!(decoder.next_datagram(), Some(datagram(&[0xdd])));
+        assert_eq!(decoder.next_datagram(), None);
+        assert_eq!(decoder.pending(), 0);
+    }
+
+    #[test]
+    fn decode_one_byte_at_a_time() {
+        let mut decoder = FrameDecoder::default();
+        let stream: [u8; 9] = [2, 0, 0x10, 0x11, 3, 0, 0x20, 0x21, 0x22];
+        let mut received: Vec<Datagram> = vec![];
+
+        for byte in stream {
+            assert_eq!(decoder.next_datagram(), None);
+            decoder.extend(&[byte]);
+
+            if let Some(dg) = decoder.next_datagram() {
+                received.push(dg);
+            }
+        }
+        assert_eq!(
+            received,
+            vec![datagram(&[0x10, 0x11]), datagram(&[0x20, 0x21, 0x22])]
+        );
+        assert_eq!(decoder.pending(), 0);
+    }
+
+    #[test]
+    fn decode_batched_frames() {
+        let mut decoder = FrameDecoder::default();
+
+        // three frames received in a single read
+        decoder.extend(&[1, 0, 0xaa, 3, 0, 0xbb, 0xbc, 0xbd, 2, 0, 0xcc, 0xcd]);
+        assert_eq!(decoder.next_datagram(), Some(datagram(&[0xaa])));
+        assert_eq!(decoder.pending(), 9);
+        assert_eq!(decoder.next_datagram(), Some(datagram(&[0xbb, 0xbc, 0xbd])));
+        assert_eq!(decoder.next_datagram(), Some(datagram(&[0xcc, 0xcd])));
+        assert_eq!(decoder.next_datagram(), None);
+        assert_eq!(decoder.pending(), 0);
+
+        // the decoder keeps working after its buffer is compacted
+        decoder.extend(&[1, 0]);
+        assert_eq!(decoder.next_datagram(), None);
+        decoder.extend(&[0xdd]);
+        assert_eq!(decoder.next_datagram(), Some(datagram(&[0xdd])));
+    }
+
+    #[tokio::test]
+    async fn loopback_round_trip() {
+        let acceptor: Acceptor = Acceptor::bind("127.0.0.1:0").await.unwrap();
+        let address = acceptor.socket.local_addr().unwrap();
+
+        let dgs: Vec<Datagram> = vec![
+            datagram(&[1, 2, 3]),
+            datagram(&[4]),
+            datagram(&vec![0x5a; 65_000]), // split over several TCP segments
+            datagram(&[6, 7]),
+        ];
+        let sent: Vec<Datagram> = dgs.clone();
+
+        let client = tokio::spawn(async move {
+            let stream: TcpStream = TcpStream::connect(address).await.unwrap();
+            let mut writer = FramedWriter::new(stream);
+
+            writer.write_datagrams(&sent[..2]).await.unwrap();
+            for dg in &sent[2..] {
+                writer.write_datagram(dg).await.unwrap();
+            }
+        });
+
+        let (mut reader, _writer, _remote) = acceptor.accept_framed().await.unwrap();
+        let mut received: Vec<Datagram> = vec![];
+
+        while let Some(dg) = reader.read_datagram().await.unwrap() {
+            received.push(dg);
+        }
+        client.await.unwrap();
+
+        assert_eq!(received, dgs);
+    }
+
+    #[tokio::test]
+    async fn stream_closed_mid_frame() {
+        let mut reader = FramedReader::new(&[5_u8, 0, 1, 2][..]);
+
+        let err: Error = reader.read_datagram().await.unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::UnexpectedEof);
+    }
+
+    #[tokio::test]
+    async fn frame_too_large() {
+        let mut writer = FramedWriter::new(vec![]);
+        let mut dg: Datagram = Datagram::default();
+
+        // only a datagram with a raised cap can outgrow a size tag
+        dg.override_cap(usize::from(DgSizeTag::MAX) + 1);
+        dg.add_data(vec![0; usize::from(DgSizeTag::MAX) + 1]).unwrap();
+
+        let err: Error = writer.write_datagram(&dg).await.unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidInput);
+        assert!(writer.get_ref().is_empty());
+    }
+}
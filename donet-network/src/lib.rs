@@ -17,30 +17,21 @@
     License along with Donet. If not, see <https://www.gnu.org/licenses/>.
 */
 
+pub mod codec;
 pub mod tcp;
 pub mod udp;
 
 use donet_core::datagram::datagram::*;
 use donet_core::datagram::iterator::*;
-use donet_core::globals::*;
 use log::{info, warn};
-use std::collections::VecDeque;
 use std::io;
 use std::net::SocketAddr;
 use std::sync::Arc;
-use tokio::io::AsyncWriteExt;
 use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
 use tokio::net::TcpStream;
 use tokio::sync::{mpsc, Mutex};
 use tokio::task::JoinHandle;
 
-/// Size of the byte buffer for incoming TCP packets.
-///
-/// Tokio gives us entire TCP messages (after reassembling
-/// segments) so we should expect this buffer to fill above
-/// the TCP max segment size (MSS).
-const TCP_READ_BUFFER_SIZE: usize = 300 * 1024; // 300 kb
-
 /// Data sent via an MPSC channel from a
 /// client receive loop task to a service
 /// handle receive task.
@@ -102,16 +93,6 @@ impl From<tcp::Connection> for Client {
     }
 }
 
-/// Util macro for truncated datagram scenarios.
-macro_rules! truncated_datagram {
-    ($remote:expr, $err:expr) => {{
-        warn!("Received truncated datagram from {}: {}", $remote, $err);
-
-        // no more bytes to read, break read loop
-        break;
-    }};
-}
-
 impl Client {
     /// Returns the remote IPv4/6 address of this client.
     pub fn get_remote(&self) -> SocketAddr {
@@ -158,102 +139,40 @@ impl Client {
 
     /// Main asynchronous loop for handling receiving TCP packets
     /// from this client's TCP stream.
+    ///
+    /// Datagrams may arrive split across several reads from the
+    /// socket, so they are reassembled by a [`codec::FramedReader`].
     async fn receive_loop(
         read_half: OwnedReadHalf,
         incoming_queue_tx: mpsc::Sender<RecvData>,
     ) -> io::Result<()> {
         let remote: SocketAddr = read_half.peer_addr()?;
+        let mut reader: codec::FramedReader<OwnedReadHalf> = codec::FramedReader::new(read_half);
 
         loop {
-            read_half.readable().await?;
-
-            // initializing this **after** the `await` point prevents
-            // it from being stored in the async task.
-            let mut buffer = [0_u8; TCP_READ_BUFFER_SIZE];
-
-            match read_half.try_read(&mut buffer) {
-                Ok(0) => {
+            let dg: Datagram = match reader.read_datagram().await {
+                Ok(Some(dg)) => dg,
+                Ok(None) => {
                     info!("Lost connection from {}", remote);
 
                     return Ok(()); // client closed TCP connection
                 }
-                Ok(len) => {
-                    let mut dg: Datagram = Datagram::default();
-
-                    dg.override_cap(TCP_READ_BUFFER_SIZE);
-
-                    // The buffer is always a fixed size. Let's make a slice that
-                    // contains only the length of the datagram received.
-                    let mut buf_slice: Vec<u8> = buffer.to_vec();
-                    buf_slice.truncate(len);
-
-                    // we can safely unwrap here, since the size cap for `dg` was
-                    // overridden to be the size of the read buffer size.
-                    dg.add_data(buf_slice).unwrap();
-
-                    Self::split_datagrams(remote, &incoming_queue_tx, dg.into()).await;
-                    continue;
-                }
-                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {
-                    continue;
-                }
-                Err(err) => {
-                    return Err(err);
+                Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => {
+                    warn!("Received truncated datagram from {}: {}", remote, err);
+                    return Ok(());
                 }
-            }
-        }
-    }
-
-    /// Handles separating TCP packets into separate Datagrams, if multiple
-    /// found in the packet, and sends each individual datagram over the
-    /// mpsc channel using the given [`mpsc::Sender`].
-    async fn split_datagrams(
-        remote: SocketAddr,
-        incoming_tx: &mpsc::Sender<RecvData>,
-        mut dgi: DatagramIterator,
-    ) {
-        loop {
-            let sizetag: DgSizeTag = match dgi.read_size() {
-                Ok(size) => size,
-                Err(err) => truncated_datagram!(remote, err),
-            };
-
-            if sizetag == 0 {
-                warn!("Received datagram with a size tag of 0. Skipping.");
-                break;
-            }
-
-            let mut individual_dg: Datagram = Datagram::default();
-
-            let payload: Vec<u8> = match dgi.read_data(sizetag.into()) {
-                Ok(data) => data,
-                Err(err) => truncated_datagram!(remote, err),
+                Err(err) => return Err(err),
             };
 
-            assert!(individual_dg.add_data(payload).is_ok());
-
-            // send individual datagram to the receive ncoming queue
-            incoming_tx
+            // send individual datagram to the receive incoming queue
+            incoming_queue_tx
                 .send(RecvData {
                     remote,
-                    dg: individual_dg.clone(),
-                    dgi: DatagramIterator::from(individual_dg),
+                    dg: dg.clone(),
+                    dgi: DatagramIterator::from(dg),
                 })
                 .await
                 .expect("Tried to send received packet, but MPSC channel closed.");
-
-            let remaining: usize = dgi.get_remaining();
-
-            // if this packet has at least another size tag ahead,
-            // try separating another datagram
-            if remaining < std::mem::size_of::<DgSizeTag>() {
-                // we *should* have 0 bytes left to read, if this is a
-                // good packet. if not, its truncated (or we read it wrong)
-                if remaining != 0 {
-                    truncated_datagram!(remote, "Expected more bytes!");
-                }
-                break;
-            }
         }
     }
 
@@ -263,49 +182,26 @@ impl Client {
     /// The queue of datagrams to be sent is received by this task
     /// via the given [`mpsc::Receiver<Datagram>`] struct.
     async fn send_loop(
-        mut write_half: OwnedWriteHalf,
+        write_half: OwnedWriteHalf,
         mut send_queue_rx: mpsc::Receiver<Datagram>,
     ) -> io::Result<()> {
+        let mut writer: codec::FramedWriter<OwnedWriteHalf> = codec::FramedWriter::new(write_half);
+
         loop {
             let mut buffer: Vec<Datagram> = vec![];
 
             // await until notified that more packets was added to the queue
             let n = send_queue_rx.recv_many(&mut buffer, 1000).await;
 
-            // if `recv_many` returns 0, it means the MPSC channel was closed.
+            // if `recv_many` returns 0, it means the MPSC channel was closed,
+            // so nothing else can be sent to this client. This happens when
+            // the service that owns the client is stopped on shutdown.
             if n == 0 {
-                todo!("unhandled error. tcp client dg queue receiver returned 0.")
-            }
-
-            let mut queue: VecDeque<Datagram> = VecDeque::from(buffer);
-
-            // prepare write buffer by reading the send queue
-            let mut write_buffer_dg: Datagram = Datagram::default();
-
-            while !queue.is_empty() {
-                let mut dgi: DatagramIterator = queue.pop_front().unwrap().into();
-
-                // get the size of this datagram to append size tag
-                let sizetag: usize = dgi.get_remaining();
-
-                // read the next bytes based on the size tag
-                let dg_payload: Result<Vec<u8>, IteratorError> = dgi.read_data(sizetag);
-
-                assert!(dg_payload.is_ok(), "Tried to read past datagram.");
-
-                write_buffer_dg.add_size(sizetag as DgSizeTag).unwrap();
-                write_buffer_dg.add_data(dg_payload.unwrap()).unwrap();
-
-                debug_assert!(
-                    dgi.get_remaining() == 0,
-                    "Did not read all bytes from received dg to send."
-                );
+                return Ok(());
             }
 
             // send staged datagrams to client
-            write_half.writable().await?;
-            write_half.write_all(write_buffer_dg.get_buffer()).await?;
-            write_half.flush().await?;
+            writer.write_datagrams(&buffer).await?;
         }
     }
 }
@@ -17,8 +17,11 @@
     License along with Donet. If not, see <https://www.gnu.org/licenses/>.
 */
 
+use crate::codec::{FramedReader, FramedWriter};
 use log::info;
 use std::io::Result;
+use std::net::SocketAddr;
+use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
 use tokio::net::{TcpListener, TcpStream};
 
 pub struct Acceptor {
@@ -42,6 +45,25 @@ impl Acceptor {
             address: String::from(uri),
         })
     }
+
+    /// Accepts a new connection, split into a reader of incoming
+    /// datagrams and a writer of outgoing datagrams.
+    pub async fn accept_framed(
+        &self,
+    ) -> Result<(
+        FramedReader<OwnedReadHalf>,
+        FramedWriter<OwnedWriteHalf>,
+        SocketAddr,
+    )> {
+        let (socket, remote) = self.socket.accept().await?;
+        let (read_half, write_half) = socket.into_split();
+
+        Ok((
+            FramedReader::new(read_half),
+            FramedWriter::new(write_half),
+            remote,
+        ))
+    }
 }
 
 impl Connection {
@@ -41,28 +41,20 @@ use donet_core::{dconfig::DCFileConfig, read_dc_files};
 use donet_daemon::config::*;
 use donet_daemon::logger;
 use donet_daemon::logger::DaemonLogger;
+use donet_daemon::panic_hook;
 use donet_daemon::service::*;
+use donet_daemon::shutdown::{self, ShutdownCoordinator, StopStatus};
 use log::*;
 use std::fs::File;
 use std::io::{Error, ErrorKind, Read};
+use std::path::Path;
 use tokio::runtime::{Builder, Runtime};
-use tokio::task::JoinHandle;
 
 #[derive(Clone, Copy)]
 enum FlagArguments {
     DCFilePath,
 }
 
-// Macro for defining global logger static and initializing it.
-macro_rules! init_logger {
-    ($level:expr) => {
-        pub static GLOBAL_LOGGER: DaemonLogger = DaemonLogger { log_level: $level };
-        logger::init_logger(&GLOBAL_LOGGER)?;
-
-        info!("Log level set at {}.", $level);
-    };
-}
-
 fn main() -> std::io::Result<()> {
     // initialize tokio instrumentation on debug builds
     #[cfg(debug_assertions)]
@@ -72,6 +64,8 @@ fn main() -> std::io::Result<()> {
 
     let mut config_file: &str = DEFAULT_TOML;
     let mut want_dc_check: bool = false;
+    let mut want_dc_list: bool = false;
+    let mut want_config_check: bool = false;
     let mut dc_check_files: Vec<String> = vec![];
     let mut expecting_flag_argument: Option<FlagArguments> = None;
 
@@ -92,6 +86,13 @@ fn main() -> std::io::Result<()> {
                     want_dc_check = true;
                     expecting_flag_argument = Some(FlagArguments::DCFilePath);
                     continue;
+                } else if argument == "--check" {
+                    want_config_check = true;
+                    continue;
+                } else if argument == "-l" || argument == "--list-dc" {
+                    want_dc_list = true;
+                    expecting_flag_argument = Some(FlagArguments::DCFilePath);
+                    continue;
                 } else {
                     println!("{}: {}: Invalid flag.\n", BINARY, argument);
                     print_help_page();
@@ -130,6 +131,20 @@ fn main() -> std::io::Result<()> {
         }
     }
 
+    // Check the configuration file path before opening it.
+    let config_path: &Path = Path::new(config_file);
+
+    if let Err(err) = check_config_path(config_path) {
+        println!("Could not load TOML configuration. {}", err);
+        return Err(err);
+    }
+    if !has_toml_extension(config_path) {
+        println!(
+            "Warning: configuration file `{}` does not end in `.toml`.",
+            config_file
+        );
+    }
+
     // Read the daemon configuration file
     let mut conf_file: File = match File::open(config_file) {
         Err(err) => {
@@ -145,38 +160,54 @@ fn main() -> std::io::Result<()> {
     conf_file.read_to_string(&mut contents)?;
     drop(conf_file); // we're in the main scope, so lets drop manually here
 
+    // Expand `${VAR}` environment variable references before deserializing.
+    let contents: String = match interpolate_env(&contents) {
+        Ok(expanded) => expanded,
+        Err(err) => {
+            println!("An error occurred while reading the TOML configuration: {}", err);
+            return Err(err);
+        }
+    };
+
     // Deserialize the TOML config file to our [`DonetConfig`] struct.
-    let daemon_config: DonetConfig = match toml::from_str(contents.as_str()) {
+    // The logger is not created yet, so errors are printed instead.
+    let daemon_config: DonetConfig = match parse_config(config_file, &contents) {
         Ok(config) => config,
         Err(err) => {
-            error!("An error occurred while parsing the TOML configuration.");
-            return Err(Error::new(ErrorKind::InvalidInput, err.message()));
+            println!("An error occurred while parsing the TOML configuration.");
+            println!("{}", err);
+            return Err(err);
         }
     };
     drop(contents);
 
     // Now that configuration file is parsed, we can create the logger.
-    if let Some(log_level) = &daemon_config.daemon.log_level {
-        match log_level.as_str() {
-            "error" => {
-                init_logger!(log::Level::Error);
-            }
-            "warn" => {
-                init_logger!(log::Level::Warn);
-            }
-            "info" => {
-                init_logger!(log::Level::Info);
-            }
-            "debug" => {
-                init_logger!(log::Level::Debug);
-            }
-            "trace" => {
-                init_logger!(log::Level::Trace);
+    let daemon_logger: DaemonLogger = match DaemonLogger::from_config(&daemon_config.daemon) {
+        Ok(logger) => logger,
+        Err(err) => {
+            println!("Could not initialize logger.");
+            println!("{}", err);
+            return Err(err);
+        }
+    };
+    let log_level: LevelFilter = daemon_logger.log_level;
+
+    // The logger must live for the rest of the program.
+    logger::init_logger(Box::leak(Box::new(daemon_logger)))?;
+    panic_hook::install_panic_hook();
+
+    info!("Log level set at {}.", log_level);
+
+    // If `--list-dc` argument was received, print the DC class table and exit.
+    if want_dc_list {
+        cfg_if! {
+            if #[cfg(feature = "requires_dc")] {
+                return list_dc_files(&daemon_config, dc_check_files);
+            } else {
+                error!("This build of Donet does not include DC file support.");
+                return Err(Error::new(ErrorKind::Unsupported, "No DC file support."));
             }
-            _ => panic!("Could not initialize logger. Error in log level string in TOML configuration."),
         }
-    } else {
-        init_logger!(log::Level::Info);
     }
 
     // If `--validate-dc` argument was received, parse DC files and exit.
@@ -191,6 +222,11 @@ fn main() -> std::io::Result<()> {
         }
     }
 
+    // If `--check` argument was received, validate the configuration and exit.
+    if want_config_check {
+        return check_config(&daemon_config);
+    }
+
     // At this point in execution, the program has not exited, which
     // means all arguments have been read and executed, if executed,
     // and now we can start the process of booting the Donet daemon.
@@ -200,16 +236,8 @@ fn main() -> std::io::Result<()> {
     // Services like the Event Logger and Message Director do not need the DC file.
     cfg_if! {
         if #[cfg(feature = "requires_dc")] {
-            let conf: DCFileConfig = daemon_config.clone().into();
-            let files: Vec<String> = daemon_config.global.dc_files.clone();
-
-            let dc: DCFile = match read_dc_files(conf, files) {
-                Ok(dc) => dc,
-                Err(dc_err) => {
-                    error!("Failed to parse DC file(s): {}", dc_err);
-                    return Err(Error::new(ErrorKind::InvalidInput, "Failed to parse DC file."));
-                }
-            };
+            // Fail fast if this node does not agree with the cluster on the DC file.
+            let dc: DCFile = load_dc_files(&daemon_config)?;
         }
     }
 
@@ -217,102 +245,29 @@ fn main() -> std::io::Result<()> {
     // are safe to start the Tokio asynchronous runtime.
     let tokio_runtime: Runtime = Builder::new_multi_thread()
         .enable_io()
+        .enable_time()
         .thread_stack_size(2 * 1024 * 1024) // default: 2MB
         .build()?;
 
     let daemon_async_main = async move {
-        let services: Services = daemon_config.services.clone();
-
-        // Tokio join handles for spawned tasks of services started.
-        let mut service_handles: Vec<JoinHandle<std::io::Result<()>>> = vec![];
-
-        let want_client_agent: bool = services.client_agent.is_some();
-        let want_message_director: bool = services.message_director.is_some();
-        let want_state_server: bool = services.state_server.is_some();
-        let want_database_server: bool = services.database_server.is_some();
-        let want_dbss: bool = services.dbss.is_some();
-        let want_event_logger: bool = services.event_logger.is_some();
-
-        cfg_if! {
-            if #[cfg(feature = "client-agent")] {
-                if want_client_agent {
-                    info!("Booting Client Agent service.");
-                    todo!("CA not yet implemented.")
-                }
-            } else {
-                if want_client_agent {
-                    feature_warn("Client Agent");
-                }
-            }
-        }
-        cfg_if! {
-            if #[cfg(feature = "message-director")] {
-                use donet_message_director::MessageDirector;
+        // Register the services this build of Donet was made with.
+        #[allow(unused_mut)] // builds with no implemented services
+        let mut factory = ServiceFactory::new();
 
-                if want_message_director {
-                    info!("Booting Message Director service.");
+        #[cfg(feature = "message-director")]
+        factory.register::<donet_message_director::MessageDirector>(ServiceKind::MessageDirector);
+        #[cfg(feature = "event-logger")]
+        factory.register::<donet_event_logger::EventLogger>(ServiceKind::EventLogger);
 
-                    let handle = MessageDirector::start(daemon_config.clone(), None).await?;
-                    service_handles.push(handle);
-                }
-            } else {
-                if want_message_director {
-                    feature_warn("Message Director");
-                }
-            }
-        }
-        cfg_if! {
-            if #[cfg(feature = "state-server")] {
-                if want_state_server {
-                    info!("Booting State Server service.");
-                    todo!("SS not yet implemented.")
-                }
-            } else {
-                if want_state_server {
-                    feature_warn("State Server");
-                }
-            }
-        }
-        cfg_if! {
-            if #[cfg(feature = "database-server")] {
-                if want_database_server {
-                    info!("Booting Database Server service.");
-                    todo!("DB not yet implemented.")
-                }
-            } else {
-                if want_database_server {
-                    feature_warn("Database Server");
-                }
-            }
+        for kind in factory.unregistered(&daemon_config) {
+            feature_warn(kind);
         }
-        cfg_if! {
-            if #[cfg(feature = "dbss")] {
-                if want_dbss {
-                    info!("Booting DBSS service.");
-                    todo!("DBSS not yet implemented.")
-                }
-            } else {
-                if want_dbss {
-                    feature_warn("DBSS");
-                }
-            }
-        }
-        cfg_if! {
-            if #[cfg(feature = "event-logger")] {
-                use donet_event_logger::EventLogger;
+        let coordinator = ShutdownCoordinator::new(shutdown::GRACE_PERIOD);
 
-                if want_event_logger {
-                    info!("Booting Event Logger service.");
+        // Tokio join handles for spawned tasks of services started.
+        let service_handles: Vec<ServiceHandle> =
+            factory.spawn_from_config(&daemon_config, &coordinator).await?;
 
-                    let handle = EventLogger::start(daemon_config.clone(), None).await?;
-                    service_handles.push(handle);
-                }
-            } else {
-                if want_event_logger {
-                    feature_warn("Event Logger");
-                }
-            }
-        }
         // spawned services were given copies of these; drop originals.
         #[cfg(feature = "requires_dc")]
         drop(dc);
@@ -320,27 +275,19 @@ fn main() -> std::io::Result<()> {
 
         if service_handles.is_empty() {
             warn!("No services spawned, exiting program.")
-        } else {
-            match tokio::signal::ctrl_c().await {
-                Ok(()) => {
-                    println!();
-                    info!("Received interrupt (Ctrl + C)");
-                }
-                Err(err) => {
-                    error!("Unable to listen for shutdown signal: {}", err);
-                    panic!("Tokio was not able to listen to the interrupt signal.")
-                }
-            }
+        } else if let Err(err) = shutdown::wait_for_signal().await {
+            error!("Unable to listen for shutdown signal: {}", err);
+            panic!("Tokio was not able to listen to the shutdown signals.")
         }
         info!("Exiting...");
 
-        // Abort all spawned Tokio tasks.
-        for handle in &service_handles {
-            handle.abort();
-        }
-        // Await task handles to wrap things up; Expect a cancellation error.
-        for handle in service_handles {
-            assert!(handle.await.unwrap_err().is_cancelled());
+        // Notify all services to stop, and wait for them to wrap things up.
+        for stop in coordinator.shutdown(service_handles).await {
+            match stop.status {
+                StopStatus::Clean => debug!("{} stopped.", stop.kind),
+                StopStatus::Failed(err) => error!("{} failed to stop cleanly: {}", stop.kind, err),
+                StopStatus::Aborted => warn!("{} did not stop in time; aborted.", stop.kind),
+            }
         }
         Ok(())
     };
@@ -364,12 +311,10 @@ cfg_if! {
     // If we know that this build of Donet is specifically being made
     // for a docker image, disable these feature warnings.
     if #[cfg(feature = "dockerized")] {
-        #[allow(dead_code)]
-        fn feature_warn(_: &'static str) {}
+        fn feature_warn(_: ServiceKind) {}
     } else {
-        #[allow(dead_code)]
-        fn feature_warn(name: &'static str) {
-            warn!("This build of Donet has no {}; skipping.", name);
+        fn feature_warn(kind: ServiceKind) {
+            warn!("This build of Donet has no {}; skipping.", kind);
         }
     }
 }
@@ -386,7 +331,9 @@ fn print_help_page() {
         \n\
         -h, --help          Print the help page.\n\
         -v, --version       Print Donet binary build version & info.\n\
-        -c, --validate-dc   Run the libdonet DC parser on the given DC file.\n",
+        -c, --validate-dc   Run the libdonet DC parser on the given DC file.\n\
+        -l, --list-dc       Print the classes and fields of the given DC file.\n\
+        --check             Validate the configuration and its DC files, then exit.\n",
         BINARY, DEFAULT_TOML
     );
 }
@@ -433,6 +380,44 @@ fn print_version() {
     );
 }
 
+/// Reads the DC files listed in the daemon configuration, and checks
+/// their hash against the DC hash expected by the cluster, if any.
+#[cfg(feature = "requires_dc")]
+fn load_dc_files(conf: &DonetConfig) -> std::io::Result<DCFile> {
+    let dc_config: DCFileConfig = conf.clone().into();
+
+    let dc: DCFile = match read_dc_files(dc_config, conf.global.dc_files.clone()) {
+        Ok(dc) => dc,
+        Err(dc_err) => {
+            error!("Failed to parse DC file(s): {}", dc_err);
+            return Err(Error::new(ErrorKind::InvalidInput, "Failed to parse DC file."));
+        }
+    };
+
+    if let Err(err) = conf.global.check_dc_hash(dc.get_legacy_hash()) {
+        error!("{}", err);
+        return Err(err);
+    }
+    Ok(dc)
+}
+
+/// Performs the operation for the `--check` flag in the daemon binary.
+///
+/// The configuration has already been parsed by the time this is called,
+/// so this parses the DC files it lists and checks the expected DC hash.
+fn check_config(conf: &DonetConfig) -> std::io::Result<()> {
+    cfg_if! {
+        if #[cfg(feature = "requires_dc")] {
+            load_dc_files(conf)?;
+        } else {
+            let _ = conf;
+            warn!("This build of Donet does not include DC file support; DC files not checked.");
+        }
+    }
+    info!("No issues found in the configuration.");
+    Ok(())
+}
+
 /// Performs the operation for the `-c` flag, or the `--validate-dc`
 /// GNU-style long flag in the daemon binary.
 #[cfg(feature = "requires_dc")]
@@ -464,3 +449,29 @@ fn validate_dc_files(conf: &DonetConfig, files: Vec<String>) -> std::io::Result<
         }
     }
 }
+
+/// Performs the operation for the `-l` flag, or the `--list-dc`
+/// GNU-style long flag in the daemon binary.
+#[cfg(feature = "requires_dc")]
+fn list_dc_files(conf: &DonetConfig, files: Vec<String>) -> std::io::Result<()> {
+    use donet_core::dconfig::DCFileConfig;
+    use donet_core::read_dc_files;
+    use log::error;
+    use std::io::{Error, ErrorKind};
+
+    // DC parser pipeline requires configuration; Build from TOML config.
+    let dc_config: DCFileConfig = conf.clone().into();
+
+    match read_dc_files(dc_config, files.to_owned()) {
+        Ok(dc_file) => {
+            println!("{:<6} CLASS / FIELD", "ID");
+            print!("{}", dc_file.get_class_table());
+            Ok(())
+        }
+        Err(err) => {
+            error!("Failed to parse DC file: {:?}", err);
+
+            Err(Error::new(ErrorKind::InvalidInput, "Failed to parse DC file."))
+        }
+    }
+}
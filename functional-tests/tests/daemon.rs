@@ -0,0 +1,119 @@
+/*
+    This file is part of Donet.
+
+    Copyright © 2024 Max Rodriguez <me@maxrdz.com>
+
+    Donet is free software; you can redistribute it and/or modify
+    it under the terms of the GNU Affero General Public License,
+    as published by the Free Software Foundation, either version 3
+    of the License, or (at your option) any later version.
+
+    Donet is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+    GNU Affero General Public License for more details.
+
+    You should have received a copy of the GNU Affero General Public
+    License along with Donet. If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! Functional testing for the lifecycle of the Donet daemon:
+//! starting the services in its configuration, and shutting
+//! down cleanly when it receives a signal.
+//!
+//! Each test runs the daemon with its own TOML configuration file,
+//! located in this directory, so that the tests can run in parallel
+//! without binding the same address.
+
+use std::env;
+use std::io::Read;
+use std::net::TcpStream;
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::thread::sleep;
+use std::time::Duration;
+
+static DAEMON_BIN: &str = "donetd";
+
+/// Configuration file and Message Director bind address of
+/// each test. Must be the same as the ones found in the TOML!
+static INTERRUPT_TEST: (&str, &str) = ("daemon_interrupt.toml", "127.0.0.1:57124");
+static TERMINATE_TEST: (&str, &str) = ("daemon_terminate.toml", "127.0.0.1:57125");
+
+static STARTUP_TIME: u64 = 200; // milliseconds
+static SHUTDOWN_TIMEOUT: u64 = 5000; // milliseconds
+
+fn spawn_daemon(config: &str) -> Child {
+    let build_dir: String =
+        env::var("MESON_BUILD_ROOT").expect("Functional tests need to be ran through Meson.");
+
+    let src_dir: String =
+        env::var("MESON_SOURCE_ROOT").expect("Functional tests need to be ran through Meson.");
+
+    let pwd: String = format!("{}/functional-tests/tests", src_dir);
+
+    Command::new(format!("{}/{}", build_dir, DAEMON_BIN))
+        .current_dir(pwd)
+        .arg(config)
+        .stdout(Stdio::piped())
+        .spawn()
+        .expect("Donet daemon failed to launch.")
+}
+
+/// Sends the given signal to the daemon with `kill`, and waits
+/// for it to exit. The daemon is killed if it does not exit in time.
+fn signal_and_wait(donet: &mut Child, signal: &str) -> ExitStatus {
+    let sent: bool = Command::new("kill")
+        .arg(format!("-{}", signal))
+        .arg(donet.id().to_string())
+        .status()
+        .expect("Failed to run kill.")
+        .success();
+    assert!(sent, "Failed to send {} to the daemon.", signal);
+
+    let mut waited: u64 = 0;
+    loop {
+        if let Some(status) = donet.try_wait().unwrap() {
+            return status;
+        }
+        if waited >= SHUTDOWN_TIMEOUT {
+            donet.kill().unwrap();
+            panic!("Daemon did not exit after receiving {}.", signal);
+        }
+        sleep(Duration::from_millis(50));
+        waited += 50;
+    }
+}
+
+fn start_and_stop((config, bind_addr): (&str, &str), signal: &str) {
+    let mut donet: Child = spawn_daemon(config);
+
+    sleep(Duration::from_millis(STARTUP_TIME));
+
+    // the daemon must be up and running its configured service
+    if let Err(err) = TcpStream::connect(bind_addr) {
+        donet.kill().unwrap();
+        panic!("Could not connect to the message director: {}", err);
+    }
+    if donet.try_wait().unwrap().is_some() {
+        panic!("Daemon exited before receiving {}.", signal);
+    }
+
+    let status: ExitStatus = signal_and_wait(&mut donet, signal);
+
+    let mut stdout: String = String::new();
+    donet.stdout.take().unwrap().read_to_string(&mut stdout).unwrap();
+
+    assert!(status.success(), "Daemon did not exit cleanly: {}", status);
+    assert!(stdout.contains("Booting Message Director service."));
+    assert!(stdout.contains("Exiting..."));
+}
+
+#[test]
+fn daemon_stops_on_interrupt() {
+    start_and_stop(INTERRUPT_TEST, "INT");
+}
+
+#[test]
+fn daemon_stops_on_terminate() {
+    start_and_stop(TERMINATE_TEST, "TERM");
+}
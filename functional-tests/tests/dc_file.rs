@@ -27,6 +27,7 @@ use std::process::Command;
 
 static DAEMON_BIN: &str = "donetd";
 static DAEMON_TOML: &str = "dc_file.toml";
+static BAD_HASH_TOML: &str = "dc_file_bad_hash.toml";
 static DC_FILE: &str = "dc_file.dc";
 
 #[test]
@@ -52,3 +53,74 @@ fn dc_language_functional_testing() {
 
     assert!(donet.wait().unwrap().success(), "Test failed.");
 }
+
+#[test]
+fn dc_list_functional_testing() {
+    let build_dir: String =
+        env::var("MESON_BUILD_ROOT").expect("Functional tests need to be ran through Meson.");
+
+    let src_dir: String =
+        env::var("MESON_SOURCE_ROOT").expect("Functional tests need to be ran through Meson.");
+
+    let pwd: String = format!("{}/functional-tests/tests", src_dir);
+
+    let output = Command::new(format!("{}/{}", build_dir, DAEMON_BIN))
+        .current_dir(pwd)
+        .arg("-l")
+        .arg(DC_FILE)
+        .arg(DAEMON_TOML)
+        .output()
+        .expect("Donet daemon failed to launch.");
+
+    assert!(output.status.success(), "Test failed.");
+
+    let stdout: String = String::from_utf8_lossy(&output.stdout).into_owned();
+
+    assert!(stdout.contains("CLASS / FIELD"), "Class table was not printed.");
+
+    // rows for `dclass Avatar` and its plain and atomic fields in the DC file
+    let rows: Vec<&str> = stdout.lines().map(str::trim).collect();
+
+    for row in [
+        "1      Avatar",
+        "name : string",
+        "set_xyzh(int16 x, int16 y, int16 z, int16 h)",
+    ] {
+        assert!(rows.contains(&row), "Class table is missing `{}`.", row);
+    }
+}
+
+/// Runs the daemon with the `--check` flag on the given configuration
+/// file, and returns `true` if it exited successfully.
+fn check_config(config: &str) -> bool {
+    let build_dir: String =
+        env::var("MESON_BUILD_ROOT").expect("Functional tests need to be ran through Meson.");
+
+    let src_dir: String =
+        env::var("MESON_SOURCE_ROOT").expect("Functional tests need to be ran through Meson.");
+
+    let pwd: String = format!("{}/functional-tests/tests", src_dir);
+
+    let status = Command::new(format!("{}/{}", build_dir, DAEMON_BIN))
+        .current_dir(pwd)
+        .arg("--check")
+        .arg(config)
+        .status()
+        .expect("Donet daemon failed to launch.");
+
+    status.success()
+}
+
+#[test]
+fn check_valid_config() {
+    assert!(check_config(DAEMON_TOML), "Valid configuration was rejected.");
+}
+
+#[test]
+fn check_invalid_config() {
+    assert!(!check_config(BAD_HASH_TOML), "DC hash mismatch was not reported.");
+    assert!(
+        !check_config("missing.toml"),
+        "Missing configuration was not reported."
+    );
+}